@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::{parse, Error, Interpreter, Statement, Stmt};
+
+// --- BUILD ---
+//
+// `lazy build game.lazy` writes a copy of the `lazy` executable with the
+// program appended to it: the script and every file it imports, found the
+// way `<-` finds them. When `lazy` starts, it looks at the end of its own
+// executable, and if a bundle is there it runs that program instead of the
+// REPL.
+//
+// The bundle is each file as (path length, path, code length, code), then
+// the length of all that and `MAGIC`. Lengths are little-endian u64s. Paths
+// are relative to the script's directory, and `<-` in a bundled program only
+// looks in the bundle.
+
+const MAGIC: &[u8; 8] = b"LAZYBNDL";
+
+/// A program and the modules it imports, by path.
+pub struct Bundle {
+    // The script first.
+    files: Vec<(PathBuf, String)>,
+}
+
+impl Bundle {
+    /// Reads `script` and, through its `<-` imports, everything it needs.
+    /// Imports are looked up next to the importing file, then in
+    /// `search_path`, like when the script runs.
+    pub fn collect(script: &Path, search_path: &[PathBuf]) -> Result<Bundle, Error> {
+        let name = PathBuf::from(script.file_name().unwrap_or_default());
+        let mut files = Vec::new();
+        let mut pending = vec![(name, script.to_path_buf())];
+        while let Some((path, real)) = pending.pop() {
+            if files.iter().any(|(p, _)| *p == path) { continue; }
+            let code = fs::read_to_string(&real)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", real.display(), e)))?;
+            let mut imports = Vec::new();
+            import_paths(&parse(&code)?, &mut imports);
+            for import in imports {
+                pending.push(find_import(&path, &real, &import, search_path)?);
+            }
+            files.push((path, code));
+        }
+        Ok(Bundle { files })
+    }
+
+    /// The bundle at the end of the running executable, if it has one.
+    pub fn embedded() -> Option<Bundle> {
+        let mut exe = File::open(env::current_exe().ok()?).ok()?;
+        let mut trailer = [0; 16];
+        exe.seek(SeekFrom::End(-16)).ok()?;
+        exe.read_exact(&mut trailer).ok()?;
+        if &trailer[8..] != MAGIC { return None; }
+        let len = u64::from_le_bytes(trailer[..8].try_into().ok()?);
+        let mut data = vec![0; usize::try_from(len).ok()?.checked_add(16)?];
+        exe.seek(SeekFrom::End(-16 - i64::try_from(len).ok()?)).ok()?;
+        exe.read_exact(&mut data).ok()?;
+        Bundle::from_bytes(&data)
+    }
+
+    /// The files, the script first, each with its path relative to the
+    /// script's directory.
+    pub fn files(&self) -> impl Iterator<Item = (&Path, &str)> {
+        self.files.iter().map(|(path, code)| (path.as_path(), code.as_str()))
+    }
+
+    /// The bundle as `build` appends it to the executable, ending in `MAGIC`.
+    ///
+    /// ```
+    /// use std::fs;
+    /// use std::path::Path;
+    /// use lazy::build::Bundle;
+    ///
+    /// let dir = std::env::temp_dir().join("lazy-bundle-doc");
+    /// fs::create_dir_all(dir.join("lib")).unwrap();
+    /// fs::write(dir.join("game.lazy"), "<- \"lib/dice.lazy\" : roll\nroll()\n").unwrap();
+    /// fs::write(dir.join("lib/dice.lazy"), "roll() ~> 4\n").unwrap();
+    ///
+    /// let bytes = Bundle::collect(&dir.join("game.lazy"), &[]).unwrap().to_bytes();
+    /// assert!(bytes.ends_with(b"LAZYBNDL"));
+    /// // Whatever comes before it, like the executable, is skipped.
+    /// let exe = [b"\x7fELF...".as_slice(), &bytes].concat();
+    /// let bundle = Bundle::from_bytes(&exe).unwrap();
+    /// let files: Vec<_> = bundle.files().collect();
+    /// assert_eq!(files, [
+    ///     (Path::new("game.lazy"), "<- \"lib/dice.lazy\" : roll\nroll()\n"),
+    ///     (Path::new("lib/dice.lazy"), "roll() ~> 4\n"),
+    /// ]);
+    /// assert_eq!(bundle.to_bytes(), bytes);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (path, code) in &self.files {
+            for part in [path.to_string_lossy().as_bytes(), code.as_bytes()] {
+                data.extend((part.len() as u64).to_le_bytes());
+                data.extend(part);
+            }
+        }
+        let len = data.len() as u64;
+        data.extend(len.to_le_bytes());
+        data.extend(MAGIC);
+        data
+    }
+
+    /// The bundle at the end of `data`, as `to_bytes` wrote it.
+    pub fn from_bytes(data: &[u8]) -> Option<Bundle> {
+        let (data, trailer) = data.split_last_chunk::<16>()?;
+        if &trailer[8..] != MAGIC { return None; }
+        let len = usize::try_from(u64::from_le_bytes(trailer[..8].try_into().ok()?)).ok()?;
+        let mut data = data.get(data.len().checked_sub(len)?..)?;
+        let mut take = || -> Option<String> {
+            let (len, rest) = data.split_first_chunk::<8>()?;
+            let len = usize::try_from(u64::from_le_bytes(*len)).ok()?;
+            let (part, rest) = rest.split_at_checked(len)?;
+            data = rest;
+            String::from_utf8(part.to_vec()).ok()
+        };
+        let mut files = Vec::new();
+        while let Some(path) = take() {
+            files.push((PathBuf::from(path), take()?));
+        }
+        if files.is_empty() { None } else { Some(Bundle { files }) }
+    }
+}
+
+impl Interpreter {
+    /// Runs the script of `bundle`, with its imports read from the bundle.
+    pub fn run_bundle(&mut self, bundle: Bundle) -> Result<(), Error> {
+        let (main, code) = bundle.files[0].clone();
+        self.bundle = bundle.files.into_iter().collect::<HashMap<_, _>>();
+        let stmts = parse(&code)?;
+        self.loading.push(main);
+        let result = self.run(&stmts);
+        self.loading.pop();
+        result?;
+        Ok(())
+    }
+}
+
+// Where the import `path` in the file at `from` is found, as its path in
+// the bundle and on disk.
+fn find_import(from: &Path, real: &Path, path: &str, search_path: &[PathBuf]) -> Result<(PathBuf, PathBuf), Error> {
+    let dir = real.parent().unwrap_or(Path::new(""));
+    if dir.join(path).is_file() {
+        let bundled = normalize(&from.parent().unwrap_or(Path::new("")).join(path));
+        return Ok((bundled, dir.join(path)));
+    }
+    match search_path.iter().map(|dir| dir.join(path)).find(|candidate| candidate.is_file()) {
+        Some(candidate) => Ok((normalize(Path::new(path)), candidate)),
+        None => {
+            let message = format!("{}: cannot find module \"{}\"", from.display(), path);
+            Err(io::Error::new(io::ErrorKind::NotFound, message).into())
+        }
+    }
+}
+
+// `a/./b/../c` is `a/c`, without looking at the disk: bundled files aren't
+// there.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(out.components().next_back(), Some(Component::Normal(_))) => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+fn import_paths(stmts: &[Stmt], out: &mut Vec<String>) {
+    for stmt in stmts {
+        if let Statement::Import(path, _) = &stmt.kind {
+            out.push(path.clone());
+        }
+        for block in stmt.blocks() {
+            import_paths(block, out);
+        }
+    }
+}
+
+/// Writes a copy of the running `lazy` executable to `output`, with `script`
+/// and everything it imports appended, so the copy runs the script.
+pub fn build(script: &Path, search_path: &[PathBuf], output: &Path) -> Result<(), Error> {
+    let bundle = Bundle::collect(script, search_path)?;
+    let exe = env::current_exe()?;
+    if fs::canonicalize(output).is_ok_and(|out| Some(out) == fs::canonicalize(&exe).ok()) {
+        return Err(io::Error::other("the output would overwrite lazy itself").into());
+    }
+    // Keeps the permissions, so the copy is executable too.
+    fs::copy(&exe, output)?;
+    OpenOptions::new().append(true).open(output)?.write_all(&bundle.to_bytes())?;
+    Ok(())
+}
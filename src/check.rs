@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+use crate::pattern::{self, Vars};
+use crate::{parse_program, Child, Expr, Statement, Stmt, BUILTINS};
+
+// --- LINTER ---
+//
+// `lazy check` walks the AST looking for mistakes the interpreter silently
+// forgives. Lazy resolves names dynamically, so "defined" here means "assigned
+// somewhere in the program" rather than "visible at this point".
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+pub fn check_source(code: &str) -> Vec<Diagnostic> {
+    let (stmts, parse_errors) = parse_program(code);
+    let mut diags: Vec<Diagnostic> = parse_errors.into_iter()
+        .map(|e| Diagnostic { line: e.line, severity: Severity::Error, message: e.message })
+        .collect();
+    diags.extend(lint(&stmts));
+    diags.sort_by(|a, b| a.line.cmp(&b.line).then(b.severity.cmp(&a.severity)));
+    diags
+}
+
+pub fn lint(stmts: &[Stmt]) -> Vec<Diagnostic> {
+    let mut linter = Linter::default();
+    linter.collect(stmts, false);
+    linter.visit(stmts, &[]);
+
+    let mut seen = HashSet::new();
+    for (name, line) in std::mem::take(&mut linter.assigned) {
+        if seen.insert(name.clone()) && !linter.reads.contains(&name) && !name.starts_with('_') {
+            linter.warn(line, format!("`{}` is assigned but never used", name));
+        }
+    }
+    linter.diags
+}
+
+#[derive(Default)]
+struct Linter {
+    diags: Vec<Diagnostic>,
+    // Every name that gets a value somewhere: variables, parameters, functions.
+    defined: HashSet<String>,
+    // Variables assigned outside of any function.
+    globals: HashSet<String>,
+    // Parameter count of each function, `None` if it's defined twice with different counts.
+    functions: HashMap<String, Option<usize>>,
+    namespaces: HashSet<String>,
+    reads: HashSet<String>,
+    // Reads inside each function being visited, innermost last.
+    fn_reads: Vec<HashSet<String>>,
+    assigned: Vec<(String, usize)>,
+    reported: HashSet<String>,
+}
+
+impl Linter {
+    fn warn(&mut self, line: usize, message: String) {
+        self.diags.push(Diagnostic { line, severity: Severity::Warning, message });
+    }
+
+    fn error(&mut self, line: usize, message: String) {
+        self.diags.push(Diagnostic { line, severity: Severity::Error, message });
+    }
+
+    fn is_known(&self, name: &str) -> bool {
+        self.defined.contains(name)
+            || name.split_once('.').is_some_and(|(ns, _)| self.namespaces.contains(ns))
+    }
+
+    fn assign(&mut self, name: &str, line: usize, in_function: bool) {
+        self.defined.insert(name.to_string());
+        if !in_function { self.globals.insert(name.to_string()); }
+        self.assigned.push((name.to_string(), line));
+    }
+
+    fn define_function(&mut self, name: &str, params: &[Vars]) {
+        self.defined.insert(name.to_string());
+        self.defined.extend(pattern::param_names(params));
+        let count = params.len();
+        self.functions.entry(name.to_string())
+            .and_modify(|c| if *c != Some(count) { *c = None })
+            .or_insert(Some(count));
+    }
+
+    // First pass: find every name that is ever given a value.
+    fn collect(&mut self, stmts: &[Stmt], in_function: bool) {
+        for stmt in stmts {
+            match &stmt.kind {
+                Statement::Assign(vars, _) | Statement::For(vars, _, _) => {
+                    for name in vars.names() {
+                        self.assign(name, stmt.line, in_function);
+                    }
+                }
+                Statement::AugAssign(name, _, _) | Statement::IncDec(name, _) => {
+                    self.assign(name, stmt.line, in_function);
+                }
+                Statement::Input(vars, _, _) => {
+                    for var in vars {
+                        self.assign(var, stmt.line, in_function);
+                    }
+                }
+                Statement::Match(_, arms) => {
+                    for arm in arms {
+                        self.defined.extend(arm.pattern.names().into_iter().map(String::from));
+                    }
+                }
+                Statement::FunctionDef(name, params, _) | Statement::QuickFunctionDef(name, params, _) => {
+                    self.define_function(name, params);
+                }
+                Statement::Import(path, names) => {
+                    if let Some(stem) = Path::new(path).file_stem() {
+                        self.namespaces.insert(stem.to_string_lossy().to_string());
+                    }
+                    self.defined.extend(names.iter().map(|(_, bind_as)| bind_as.clone()));
+                }
+                _ => {}
+            }
+            let in_function = in_function || matches!(stmt.kind, Statement::FunctionDef(..));
+            for block in stmt.blocks() {
+                self.collect(block, in_function);
+            }
+        }
+    }
+
+    // Second pass: check every statement and expression.
+    fn visit(&mut self, stmts: &[Stmt], params: &[String]) {
+        let mut returned = false;
+        let mut unreachable_reported = false;
+        for stmt in stmts {
+            if matches!(stmt.kind, Statement::Comment(_)) { continue; }
+            if returned && !unreachable_reported {
+                self.warn(stmt.line, "unreachable code: the `->` above always returns first".to_string());
+                unreachable_reported = true;
+            }
+            let line = stmt.line;
+            match &stmt.kind {
+                Statement::FunctionDef(name, fn_params, body) => {
+                    let fn_params = pattern::param_names(fn_params);
+                    self.fn_reads.push(HashSet::new());
+                    self.visit(body, &fn_params);
+                    let reads = self.fn_reads.pop().unwrap_or_default();
+                    self.check_params(name, &fn_params, &reads, line);
+                }
+                Statement::QuickFunctionDef(name, fn_params, expr) => {
+                    self.fn_reads.push(HashSet::new());
+                    self.expr(expr, line);
+                    let reads = self.fn_reads.pop().unwrap_or_default();
+                    self.check_params(name, &pattern::param_names(fn_params), &reads, line);
+                }
+                kind => {
+                    if let Statement::AugAssign(name, _, _) | Statement::IncDec(name, _) = kind {
+                        self.read(name, line);
+                    }
+                    for child in stmt.children() {
+                        match child {
+                            Child::Expr(expr, line) => self.expr(expr, line),
+                            Child::Block(block) => self.visit(block, params),
+                        }
+                    }
+                    match kind {
+                        Statement::Assign(vars, _) | Statement::For(vars, _, _) => {
+                            for name in vars.names() {
+                                self.check_shadow(name, params, line);
+                            }
+                        }
+                        Statement::AugAssign(name, _, _) | Statement::IncDec(name, _) => self.check_shadow(name, params, line),
+                        Statement::Input(vars, _, _) => {
+                            for var in vars {
+                                self.check_shadow(var, params, line);
+                            }
+                        }
+                        Statement::Match(_, arms) => {
+                            for arm in pattern::unreachable(arms) {
+                                self.warn(arm.line, format!("unreachable arm: an arm above matches everything `{}` does", arm.pattern));
+                            }
+                        }
+                        Statement::FunctionCall(name, args, _) => self.call(name, args.len(), line),
+                        _ => {}
+                    }
+                }
+            }
+            if always_returns(stmt) { returned = true; }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, line: usize) {
+        for child in expr.children() {
+            self.expr(child, line);
+        }
+        match expr {
+            Expr::Variable(name) => self.read(name, line),
+            Expr::FunctionCall(name, args, _) => self.call(name, args.len(), line),
+            _ => {}
+        }
+    }
+
+    fn mark_read(&mut self, name: &str) {
+        self.reads.insert(name.to_string());
+        for reads in &mut self.fn_reads {
+            reads.insert(name.to_string());
+        }
+    }
+
+    fn read(&mut self, name: &str, line: usize) {
+        self.mark_read(name);
+        if !self.is_known(name) && self.reported.insert(name.to_string()) {
+            self.warn(line, format!("`{}` is never assigned, so it is always nothing", name));
+        }
+    }
+
+    fn call(&mut self, name: &str, argc: usize, line: usize) {
+        if let Some(builtin) = BUILTINS.iter().find(|b| b.symbol == name) {
+            if argc < builtin.min_args || argc > builtin.max_args {
+                let expected = if builtin.min_args == builtin.max_args {
+                    builtin.min_args.to_string()
+                } else {
+                    format!("{} or {}", builtin.min_args, builtin.max_args)
+                };
+                self.warn(line, format!("`{}` takes {} argument(s) but got {}: {}", name, expected, argc, builtin.usage));
+            }
+            return;
+        }
+
+        self.mark_read(name);
+        match self.functions.get(name) {
+            Some(Some(count)) if *count != argc => {
+                let count = *count;
+                self.warn(line, format!("`{}` takes {} argument(s) but got {}", name, count, argc));
+            }
+            Some(_) => {}
+            // A variable or parameter that may hold a function value.
+            None if self.is_known(name) => {}
+            None => {
+                if self.reported.insert(name.to_string()) {
+                    self.error(line, format!("call to undefined function `{}`", name));
+                }
+            }
+        }
+    }
+
+    fn check_shadow(&mut self, name: &str, params: &[String], line: usize) {
+        if params.iter().any(|p| p == name) && self.globals.contains(name) {
+            self.warn(line, format!(
+                "`{}` is a parameter here, so this changes the parameter and not the outer `{}`", name, name));
+        }
+    }
+
+    fn check_params(&mut self, function: &str, params: &[String], reads: &HashSet<String>, line: usize) {
+        for param in params {
+            if !reads.contains(param) && !param.starts_with('_') {
+                self.warn(line, format!("parameter `{}` of `{}` is never used", param, function));
+            }
+        }
+    }
+}
+
+// A `->`, a `?` chain with a `??` where every branch ends in `->`, or a `?>`
+// where every arm does, since a value no arm matches stops the program.
+fn always_returns(stmt: &Stmt) -> bool {
+    match &stmt.kind {
+        Statement::Return(_) => true,
+        Statement::Match(_, arms) => !arms.is_empty() && arms.iter().all(|arm| block_returns(&arm.body)),
+        Statement::If(_, then_block, else_ifs, else_block) => {
+            !else_block.is_empty()
+                && block_returns(then_block)
+                && else_ifs.iter().all(|(_, block)| block_returns(block))
+                && block_returns(else_block)
+        }
+        _ => false,
+    }
+}
+
+fn block_returns(block: &[Stmt]) -> bool {
+    block.iter().any(always_returns)
+}
@@ -0,0 +1,100 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// --- CONSOLE ---
+//
+// Everything a program prints and every `+?` answer goes through the
+// interpreter's `Console`, so Lazy can run somewhere other than a terminal.
+
+pub trait Console {
+    /// Output of the program, e.g. `"42\n"` for a line with just `42`.
+    fn write(&mut self, text: &str);
+
+    /// A `+?` prompt, written just before `read_line`.
+    fn prompt(&mut self, text: &str) {
+        self.write(text);
+    }
+
+    /// The next line of input without its line ending, or `None` at the end
+    /// of input.
+    fn read_line(&mut self) -> Option<String>;
+}
+
+/// The terminal: stdout and stdin.
+#[derive(Default)]
+pub struct StdConsole;
+
+impl Console for StdConsole {
+    fn write(&mut self, text: &str) {
+        print!("{}", text);
+        io::stdout().flush().unwrap();
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(line.trim_end_matches(['\r', '\n']).to_string()),
+        }
+    }
+}
+
+/// Collects output in memory. Clones share the same buffer, so keep one to
+/// read what the program printed. There is no input: `+?` gets nothing.
+#[derive(Clone, Default)]
+pub struct BufferConsole {
+    output: Rc<RefCell<String>>,
+}
+
+impl BufferConsole {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contents(&self) -> String {
+        self.output.borrow().clone()
+    }
+
+    pub fn clear(&self) {
+        self.output.borrow_mut().clear();
+    }
+}
+
+impl Console for BufferConsole {
+    fn write(&mut self, text: &str) {
+        self.output.borrow_mut().push_str(text);
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        None
+    }
+}
+
+/// Answers `+?` prompts from a list, one line each, and passes output on to
+/// another console. When the answers run out, input is at its end.
+pub struct ScriptedConsole<C: Console> {
+    answers: VecDeque<String>,
+    output: C,
+}
+
+impl<C: Console> ScriptedConsole<C> {
+    pub fn new<S: Into<String>>(answers: impl IntoIterator<Item = S>, output: C) -> Self {
+        Self { answers: answers.into_iter().map(Into::into).collect(), output }
+    }
+}
+
+impl<C: Console> Console for ScriptedConsole<C> {
+    fn write(&mut self, text: &str) {
+        self.output.write(text);
+    }
+
+    fn prompt(&mut self, text: &str) {
+        self.output.prompt(text);
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        self.answers.pop_front()
+    }
+}
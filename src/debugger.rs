@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::pattern;
+use crate::{parse, parse_expr, Error, Function, Interpreter, Statement, Stmt, Value};
+
+// --- DEBUGGER ---
+//
+// `lazy debug file.lazy` runs a program one statement at a time. `execute`
+// calls `debug_hook` before every statement; the hook decides whether to stop
+// and, if so, reads commands until told to go on. The debugger is taken out of
+// the interpreter while it has control, so expressions evaluated at the prompt
+// run without stopping.
+//
+// Each frame knows the file its lines are in: a function's is where it was
+// defined, so stops in an imported module show that module's code. Line
+// breakpoints are in the file being debugged.
+
+const PROMPT: &str = "(debug) ";
+
+const COMMANDS: &[(&str, &str)] = &[
+    ("s, step", "Run to the next statement, stepping into calls"),
+    ("n, next", "Run to the next statement in this function"),
+    ("o, out", "Run until the current function returns"),
+    ("c, continue", "Run until a breakpoint"),
+    ("b, break <line|fn>", "Stop at a line or when a function is called"),
+    ("d, delete <n>", "Remove breakpoint n"),
+    ("breaks", "List breakpoints"),
+    ("p, print <expr>", "Evaluate an expression in the current function"),
+    ("v, vars", "Show the variables in every scope"),
+    ("bt, where", "Show the call stack"),
+    ("l, list", "Show the code around the current line"),
+    ("q, quit", "Stop the program"),
+    ("h, help", "Show this list"),
+];
+
+enum Breakpoint {
+    Line(usize),
+    Function(String),
+}
+
+enum Mode {
+    Step,
+    // Stop once the call stack is at most this deep.
+    Next(usize),
+    Continue,
+}
+
+struct Frame {
+    name: String,
+    line: usize,
+    // Set until the first statement of the call, for function breakpoints.
+    entered: bool,
+    file: PathBuf,
+}
+
+pub struct Debugger {
+    // The file being debugged.
+    file: PathBuf,
+    // The lines of it and of every module it imported, by file.
+    sources: HashMap<PathBuf, Vec<String>>,
+    breakpoints: Vec<Breakpoint>,
+    mode: Mode,
+    frames: Vec<Frame>,
+    // The call stacks of the importers while a module runs, innermost last.
+    importers: Vec<Vec<Frame>>,
+    last_command: String,
+    // Set by `quit`, which stops the program like an error does.
+    quit: bool,
+}
+
+/// Runs `file` under the debugger, reading commands from stdin. `quit` stops
+/// the program without an error.
+pub fn debug(file: &Path, search_path: Vec<PathBuf>) -> Result<(), Error> {
+    let code = fs::read_to_string(file)?;
+    let stmts = parse(&code)?;
+    let path = fs::canonicalize(file)?;
+    let mut interp = Interpreter::new();
+    interp.search_path = search_path;
+    // Stops are at the statements as written.
+    interp.set_optimize(false);
+    interp.loading.push(path.clone());
+    interp.debugger = Some(Box::new(Debugger {
+        file: path.clone(),
+        sources: HashMap::from([(path.clone(), lines(&code))]),
+        breakpoints: Vec::new(),
+        mode: Mode::Step,
+        frames: vec![Frame { name: "<main>".to_string(), line: 0, entered: false, file: path }],
+        importers: Vec::new(),
+        last_command: String::new(),
+        quit: false,
+    }));
+    println!("Debugging {}. Type 'help' for commands.", file.display());
+    let result = interp.run(&stmts);
+    if interp.debugger.as_ref().is_some_and(|dbg| dbg.quit) {
+        return Ok(());
+    }
+    result?;
+    println!("Program finished.");
+    Ok(())
+}
+
+impl Interpreter {
+    pub(crate) fn enter_frame(&mut self, name: &str, func: &Function) {
+        if let Some(dbg) = &mut self.debugger {
+            let file = match &func.file {
+                Some(file) => file.clone(),
+                None => dbg.current_file().to_path_buf(),
+            };
+            dbg.frames.push(Frame { name: name.to_string(), line: 0, entered: true, file });
+        }
+    }
+
+    pub(crate) fn leave_frame(&mut self) {
+        if let Some(dbg) = &mut self.debugger {
+            dbg.frames.pop();
+        }
+    }
+
+    // A module runs at the top level, on a call stack of its own.
+    pub(crate) fn enter_module(&mut self, file: &Path, code: &str) {
+        if let Some(dbg) = &mut self.debugger {
+            dbg.sources.entry(file.to_path_buf()).or_insert_with(|| lines(code));
+            let name = format!("<{}>", file.file_name().unwrap_or_default().to_string_lossy());
+            let frames = vec![Frame { name, line: 0, entered: false, file: file.to_path_buf() }];
+            let importer = std::mem::replace(&mut dbg.frames, frames);
+            dbg.importers.push(importer);
+        }
+    }
+
+    pub(crate) fn leave_module(&mut self) {
+        if let Some(dbg) = &mut self.debugger
+            && let Some(importer) = dbg.importers.pop() {
+            dbg.frames = importer;
+        }
+    }
+
+    pub(crate) fn debug_hook(&mut self, stmt: &Stmt) {
+        if matches!(stmt.kind, Statement::Comment(_)) { return; }
+        let Some(mut dbg) = self.debugger.take() else { return };
+        if dbg.should_stop(stmt.line) {
+            dbg.mode = Mode::Continue;
+            dbg.show_location();
+            self.prompt(&mut dbg);
+        }
+        self.debugger = Some(dbg);
+    }
+
+    fn prompt(&mut self, dbg: &mut Debugger) {
+        loop {
+            print!("{}", PROMPT);
+            io::stdout().flush().unwrap();
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+                // End of input: let the program finish on its own.
+                println!();
+                dbg.breakpoints.clear();
+                return;
+            }
+            let mut line = line.trim().to_string();
+            if line.is_empty() {
+                line = dbg.last_command.clone();
+            } else {
+                dbg.last_command = line.clone();
+            }
+            let (cmd, arg) = match line.split_once(char::is_whitespace) {
+                Some((cmd, arg)) => (cmd, arg.trim()),
+                None => (line.as_str(), ""),
+            };
+
+            let depth = dbg.frames.len();
+            match cmd {
+                "s" | "step" => dbg.mode = Mode::Step,
+                "n" | "next" => dbg.mode = Mode::Next(depth),
+                "o" | "out" => {
+                    if depth == 1 {
+                        println!("Already at the top level, use 'continue'");
+                        continue;
+                    }
+                    dbg.mode = Mode::Next(depth - 1);
+                }
+                "c" | "continue" => dbg.mode = Mode::Continue,
+                "b" | "break" => dbg.add_breakpoint(arg),
+                "d" | "delete" => match arg.parse::<usize>() {
+                    Ok(n) if n >= 1 && n <= dbg.breakpoints.len() => {
+                        dbg.breakpoints.remove(n - 1);
+                    }
+                    _ => eprintln!("Error: no breakpoint {} (see 'breaks')", arg),
+                },
+                "breaks" => dbg.list_breakpoints(),
+                "p" | "print" => {
+                    let val = self.eval_expr(&parse_expr(arg));
+                    // A failed assertion at the prompt shouldn't stop the program.
+                    match self.error.take() {
+                        Some(err) => eprintln!("Error: {}", err.message),
+                        None => println!("{}", val.repr()),
+                    }
+                }
+                "v" | "vars" => self.show_scopes(dbg),
+                "bt" | "where" => dbg.show_stack(),
+                "l" | "list" => dbg.list_source(),
+                "q" | "quit" => {
+                    dbg.quit = true;
+                    self.fail("stopped by the debugger".to_string());
+                    return;
+                }
+                "h" | "help" => {
+                    for (cmd, help) in COMMANDS {
+                        println!("  {:<20} {}", cmd, help);
+                    }
+                }
+                _ => eprintln!("Error: unknown command {} (try 'help')", cmd),
+            }
+            if matches!(cmd, "s" | "step" | "n" | "next" | "o" | "out" | "c" | "continue") {
+                return;
+            }
+        }
+    }
+
+    // Each scope is one function call, outermost first.
+    fn show_scopes(&self, dbg: &Debugger) {
+        for (i, mut scope) in self.scope_vars().into_iter().enumerate() {
+            let label = match dbg.frames.get(i) {
+                Some(frame) if i > 0 => frame.name.as_str(),
+                _ if i == 0 => "globals",
+                _ => "scope",
+            };
+            println!("{}:", label);
+            scope.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, val) in scope {
+                match val {
+                    Value::Function(func) => println!("  {}({})", name, pattern::show_params(&func.params)),
+                    val => println!("  {} = {}", name, val.repr()),
+                }
+            }
+        }
+    }
+}
+
+impl Debugger {
+    fn should_stop(&mut self, line: usize) -> bool {
+        let depth = self.frames.len();
+        let Some(frame) = self.frames.last_mut() else { return false };
+        let entered = std::mem::replace(&mut frame.entered, false);
+        frame.line = line;
+        let name = frame.name.clone();
+        let in_file = frame.file == self.file;
+
+        let hit = self.breakpoints.iter().any(|bp| match bp {
+            Breakpoint::Line(l) => in_file && *l == line,
+            Breakpoint::Function(f) => entered && names_function(&name, f),
+        });
+        hit || match self.mode {
+            Mode::Step => true,
+            Mode::Next(max_depth) => depth <= max_depth,
+            Mode::Continue => false,
+        }
+    }
+
+    fn add_breakpoint(&mut self, arg: &str) {
+        if arg.is_empty() {
+            eprintln!("Error: break needs a line number or a function name");
+            return;
+        }
+        let bp = match arg.parse::<usize>() {
+            Ok(line) => Breakpoint::Line(line),
+            Err(_) => Breakpoint::Function(arg.to_string()),
+        };
+        self.breakpoints.push(bp);
+        println!("Breakpoint {} at {}", self.breakpoints.len(), describe_breakpoint(self.breakpoints.last().unwrap()));
+    }
+
+    fn list_breakpoints(&self) {
+        if self.breakpoints.is_empty() {
+            println!("No breakpoints");
+        }
+        for (i, bp) in self.breakpoints.iter().enumerate() {
+            println!("  {}: {}", i + 1, describe_breakpoint(bp));
+        }
+    }
+
+    fn current_line(&self) -> usize {
+        self.frames.last().map(|f| f.line).unwrap_or(0)
+    }
+
+    fn current_file(&self) -> &Path {
+        self.frames.last().map(|f| f.file.as_path()).unwrap_or(&self.file)
+    }
+
+    // The lines of the file the current frame is in.
+    fn source(&self) -> &[String] {
+        self.sources.get(self.current_file()).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    // `line 3`, or `line 3 of shapes.lazy` outside the file being debugged.
+    fn describe_line(&self, frame: &Frame) -> String {
+        if frame.file == self.file {
+            format!("line {}", frame.line)
+        } else {
+            format!("line {} of {}", frame.line, frame.file.file_name().unwrap_or_default().to_string_lossy())
+        }
+    }
+
+    fn show_location(&self) {
+        let Some(frame) = self.frames.last() else { return };
+        let code = self.source().get(frame.line.wrapping_sub(1)).map(|l| l.trim()).unwrap_or("");
+        println!("{} {}: {}", frame.name, self.describe_line(frame), code);
+    }
+
+    fn show_stack(&self) {
+        for (i, frame) in self.frames.iter().rev().enumerate() {
+            println!("  #{} {} at {}", i, frame.name, self.describe_line(frame));
+        }
+    }
+
+    fn list_source(&self) {
+        let line = self.current_line();
+        let source = self.source();
+        let first = line.saturating_sub(5).max(1);
+        let last = (line + 5).min(source.len());
+        for n in first..=last {
+            let marker = if n == line { ">" } else { " " };
+            println!("{} {:>4}  {}", marker, n, source[n - 1]);
+        }
+    }
+}
+
+fn lines(code: &str) -> Vec<String> {
+    code.lines().map(|l| l.to_string()).collect()
+}
+
+// `b area` stops in `area` however it was called, `shapes.area` too;
+// `b shapes.area` only in that one.
+fn names_function(name: &str, breakpoint: &str) -> bool {
+    name == breakpoint || name.rsplit_once('.').is_some_and(|(_, bare)| bare == breakpoint)
+}
+
+fn describe_breakpoint(bp: &Breakpoint) -> String {
+    match bp {
+        Breakpoint::Line(line) => format!("line {}", line),
+        Breakpoint::Function(name) => format!("function {}", name),
+    }
+}
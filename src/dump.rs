@@ -0,0 +1,234 @@
+use crate::json::Json;
+use crate::pattern::{show_params, Vars};
+use crate::{Expr, ParseError, Statement, Stmt};
+
+// --- AST DUMP ---
+//
+// `--dump-ast` shows how the parser understood a program, as indented
+// S-expressions for people or (with `--dump-ast=json`) as JSON for tools.
+
+pub fn sexpr(stmts: &[Stmt]) -> String {
+    let mut out = String::new();
+    for stmt in stmts {
+        write_stmt(&mut out, stmt, 0);
+        out.push('\n');
+    }
+    out
+}
+
+fn write_block(out: &mut String, tag: &str, block: &[Stmt], indent: usize) {
+    out.push('\n');
+    out.push_str(&"  ".repeat(indent));
+    out.push('(');
+    out.push_str(tag);
+    for stmt in block {
+        out.push('\n');
+        write_stmt(out, stmt, indent + 1);
+    }
+    out.push(')');
+}
+
+fn write_stmt(out: &mut String, stmt: &Stmt, indent: usize) {
+    out.push_str(&"  ".repeat(indent));
+    let inner = indent + 1;
+    match &stmt.kind {
+        Statement::Print(expr) => out.push_str(&format!("(print {}", sexpr_expr(expr))),
+        Statement::Assign(name, expr) => out.push_str(&format!("(assign {} {}", name, sexpr_expr(expr))),
+        Statement::AugAssign(name, op, expr) => {
+            out.push_str(&format!("(assign-{} {} {}", op, name, sexpr_expr(expr)));
+        }
+        Statement::IncDec(name, op) => out.push_str(&format!("({} {}", op, name)),
+        Statement::If(cond, then_block, else_ifs, else_block) => {
+            out.push_str(&format!("(if {}", sexpr_expr(cond)));
+            write_block(out, "then", then_block, inner);
+            for (elif_cond, block) in else_ifs {
+                write_block(out, &format!("else-if {}", sexpr_expr(elif_cond)), block, inner);
+            }
+            if !else_block.is_empty() {
+                write_block(out, "else", else_block, inner);
+            }
+        }
+        Statement::While(cond, body) => {
+            out.push_str(&format!("(while {}", sexpr_expr(cond)));
+            write_block(out, "do", body, inner);
+        }
+        Statement::For(var, list, body) => {
+            out.push_str(&format!("(for {} {}", var, sexpr_expr(list)));
+            write_block(out, "do", body, inner);
+        }
+        Statement::Match(subject, arms) => {
+            out.push_str(&format!("(match {}", sexpr_expr(subject)));
+            for arm in arms {
+                let tag = match &arm.guard {
+                    Some(guard) => format!("arm {} (when {})", arm.pattern, sexpr_expr(guard)),
+                    None => format!("arm {}", arm.pattern),
+                };
+                write_block(out, &tag, &arm.body, inner);
+            }
+        }
+        Statement::FunctionDef(name, params, body) => {
+            out.push_str(&format!("(function {} ({})", name, show_params(params)));
+            write_block(out, "do", body, inner);
+        }
+        Statement::QuickFunctionDef(name, params, expr) => {
+            out.push_str(&format!("(quick-function {} ({}) {}", name, show_params(params), sexpr_expr(expr)));
+        }
+        Statement::FunctionCall(name, args, mutates) => out.push_str(&call_sexpr(name, args, *mutates)),
+        Statement::Return(expr) => out.push_str(&format!("(return {}", sexpr_expr(expr))),
+        Statement::Input(vars, prompt, _) => {
+            out.push_str(&format!("(input ({})", vars.join(" ")));
+            if let Some(prompt) = prompt {
+                out.push_str(&format!(" {:?}", prompt));
+            }
+        }
+        Statement::Import(path, names) => {
+            out.push_str(&format!("(import {:?}", path));
+            if !names.is_empty() {
+                let names: Vec<&str> = names.iter().map(|(n, _)| n.as_str()).collect();
+                out.push_str(&format!(" ({})", names.join(" ")));
+            }
+        }
+        Statement::Comment(text) => out.push_str(&format!("(comment {:?}", text)),
+    }
+    out.push(')');
+}
+
+fn call_sexpr(name: &str, args: &[Expr], mutates: bool) -> String {
+    let mut parts = vec![if mutates { "call*" } else { "call" }.to_string(), name.to_string()];
+    parts.extend(args.iter().map(sexpr_expr));
+    // Statement-level calls are written without their closing paren.
+    format!("({}", parts.join(" "))
+}
+
+pub fn sexpr_expr(expr: &Expr) -> String {
+    let head = match expr {
+        Expr::Number(n) => return n.to_string(),
+        Expr::Text(s) => return format!("{:?}", s),
+        Expr::Bool(b) => return (if *b { "yes" } else { "no" }).to_string(),
+        Expr::Variable(name) => return name.to_string(),
+        Expr::Input => return "(input)".to_string(),
+        Expr::List(_) => "list".to_string(),
+        Expr::Index(_, _) => "index".to_string(),
+        Expr::BinaryOp(_, op, _) => op.clone(),
+        Expr::FunctionCall(name, _, mutates) => format!("{} {}", if *mutates { "call*" } else { "call" }, name),
+    };
+    let parts: Vec<String> = std::iter::once(head).chain(expr.children().into_iter().map(sexpr_expr)).collect();
+    format!("({})", parts.join(" "))
+}
+
+pub fn json(stmts: &[Stmt], errors: &[ParseError]) -> Json {
+    let errors = errors.iter()
+        .map(|e| Json::object(vec![("line", Json::Number(e.line as f64)), ("message", Json::str(&e.message))]))
+        .collect();
+    Json::object(vec![
+        ("statements", json_block(stmts)),
+        ("errors", Json::Array(errors)),
+    ])
+}
+
+fn json_block(stmts: &[Stmt]) -> Json {
+    Json::Array(stmts.iter().map(json_stmt).collect())
+}
+
+fn names(names: &[String]) -> Json {
+    Json::Array(names.iter().map(|n| Json::str(n)).collect())
+}
+
+fn params(params: &[Vars]) -> Json {
+    Json::Array(params.iter().map(|p| Json::str(&p.to_string())).collect())
+}
+
+fn json_stmt(stmt: &Stmt) -> Json {
+    let mut fields = match &stmt.kind {
+        Statement::Print(expr) => vec![("type", Json::str("Print")), ("value", json_expr(expr))],
+        Statement::Assign(vars, expr) => vec![
+            ("type", Json::str("Assign")), ("name", Json::str(&vars.to_string())), ("value", json_expr(expr)),
+        ],
+        Statement::AugAssign(name, op, expr) => vec![
+            ("type", Json::str("AugAssign")), ("name", Json::str(name)), ("op", Json::str(op)), ("value", json_expr(expr)),
+        ],
+        Statement::IncDec(name, op) => vec![("type", Json::str("IncDec")), ("name", Json::str(name)), ("op", Json::str(op))],
+        Statement::If(cond, then_block, else_ifs, else_block) => {
+            let else_ifs = else_ifs.iter()
+                .map(|(c, b)| Json::object(vec![("condition", json_expr(c)), ("body", json_block(b))]))
+                .collect();
+            vec![
+                ("type", Json::str("If")),
+                ("condition", json_expr(cond)),
+                ("then", json_block(then_block)),
+                ("else_ifs", Json::Array(else_ifs)),
+                ("else", json_block(else_block)),
+            ]
+        }
+        Statement::While(cond, body) => vec![
+            ("type", Json::str("While")), ("condition", json_expr(cond)), ("body", json_block(body)),
+        ],
+        Statement::For(var, list, body) => vec![
+            ("type", Json::str("For")), ("var", Json::str(&var.to_string())), ("list", json_expr(list)), ("body", json_block(body)),
+        ],
+        Statement::Match(subject, arms) => {
+            let arms = arms.iter()
+                .map(|arm| Json::object(vec![
+                    ("pattern", Json::str(&arm.pattern.to_string())),
+                    ("guard", arm.guard.as_ref().map(json_expr).unwrap_or(Json::Null)),
+                    ("body", json_block(&arm.body)),
+                ]))
+                .collect();
+            vec![("type", Json::str("Match")), ("subject", json_expr(subject)), ("arms", Json::Array(arms))]
+        }
+        Statement::FunctionDef(name, params, body) => vec![
+            ("type", Json::str("FunctionDef")), ("name", Json::str(name)), ("params", self::params(params)), ("body", json_block(body)),
+        ],
+        Statement::QuickFunctionDef(name, params, expr) => vec![
+            ("type", Json::str("QuickFunctionDef")), ("name", Json::str(name)), ("params", self::params(params)), ("body", json_expr(expr)),
+        ],
+        Statement::FunctionCall(name, args, mutates) => vec![
+            ("type", Json::str("FunctionCall")),
+            ("name", Json::str(name)),
+            ("args", Json::Array(args.iter().map(json_expr).collect())),
+            ("mutates", Json::Bool(*mutates)),
+        ],
+        Statement::Return(expr) => vec![("type", Json::str("Return")), ("value", json_expr(expr))],
+        Statement::Input(vars, prompt, is_iter) => vec![
+            ("type", Json::str("Input")),
+            ("vars", names(vars)),
+            ("prompt", prompt.as_ref().map(|p| Json::str(p)).unwrap_or(Json::Null)),
+            ("numbered", Json::Bool(*is_iter)),
+        ],
+        Statement::Import(path, bindings) => {
+            let bindings: Vec<String> = bindings.iter().map(|(n, _)| n.clone()).collect();
+            vec![("type", Json::str("Import")), ("path", Json::str(path)), ("names", names(&bindings))]
+        }
+        Statement::Comment(text) => vec![("type", Json::str("Comment")), ("text", Json::str(text))],
+    };
+    fields.insert(1, ("span", Json::object(vec![
+        ("start_line", Json::Number(stmt.line as f64)),
+        ("end_line", Json::Number(stmt.end_line as f64)),
+    ])));
+    Json::object(fields)
+}
+
+fn json_expr(expr: &Expr) -> Json {
+    match expr {
+        Expr::Number(n) => Json::object(vec![("type", Json::str("Number")), ("value", Json::Number(*n))]),
+        Expr::Text(s) => Json::object(vec![("type", Json::str("Text")), ("value", Json::str(s))]),
+        Expr::Bool(b) => Json::object(vec![("type", Json::str("Bool")), ("value", Json::Bool(*b))]),
+        Expr::Variable(name) => Json::object(vec![("type", Json::str("Variable")), ("name", Json::str(name))]),
+        Expr::List(items) => Json::object(vec![
+            ("type", Json::str("List")), ("items", Json::Array(items.iter().map(json_expr).collect())),
+        ]),
+        Expr::Index(list, index) => Json::object(vec![
+            ("type", Json::str("Index")), ("list", json_expr(list)), ("index", json_expr(index)),
+        ]),
+        Expr::BinaryOp(left, op, right) => Json::object(vec![
+            ("type", Json::str("BinaryOp")), ("op", Json::str(op)), ("left", json_expr(left)), ("right", json_expr(right)),
+        ]),
+        Expr::FunctionCall(name, args, mutates) => Json::object(vec![
+            ("type", Json::str("FunctionCall")),
+            ("name", Json::str(name)),
+            ("args", Json::Array(args.iter().map(json_expr).collect())),
+            ("mutates", Json::Bool(*mutates)),
+        ]),
+        Expr::Input => Json::object(vec![("type", Json::str("Input"))]),
+    }
+}
@@ -0,0 +1,227 @@
+use crate::pattern::show_params;
+use crate::{parse_program, Expr, Statement, Stmt, BUILTINS};
+
+// --- FORMATTER ---
+//
+// Prints the AST back as source in one canonical style: two spaces per block
+// level, spaces around operators, ` -> ` between the arguments of built-in
+// symbols, and at most one blank line between statements. Comments are kept
+// where they were. Printing and re-parsing gives back the same AST.
+
+const INDENT: &str = "  ";
+
+// Operators in the order `parse_expr` splits on them, loosest first.
+const OPERATORS: [&str; 11] = ["==", "!=", ">=", "<=", ">", "<", "+", "-", "*", "/", "%"];
+
+// Files that don't parse cleanly are left alone: the parser drops the
+// code it can't place, and formatting would delete it.
+pub fn format_source(code: &str) -> Result<String, Vec<crate::ParseError>> {
+    let (stmts, errors) = parse_program(code);
+    if !errors.is_empty() { return Err(errors); }
+    Ok(format_program(&stmts))
+}
+
+pub fn format_program(stmts: &[Stmt]) -> String {
+    let mut printer = Printer { lines: Vec::new(), indent: 0 };
+    printer.block(stmts);
+    let mut out = String::new();
+    for (_, line) in printer.lines {
+        out.push_str(&line);
+        out.push('\n');
+    }
+    out
+}
+
+struct Printer {
+    // Output lines with the source line each one came from (0 if none).
+    lines: Vec<(usize, String)>,
+    indent: usize,
+}
+
+impl Printer {
+    fn line(&mut self, source_line: usize, text: String) {
+        let text = if text.is_empty() { text } else { format!("{}{}", INDENT.repeat(self.indent), text) };
+        self.lines.push((source_line, text));
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        let mut prev_end = None;
+        for stmt in stmts {
+            if let Statement::Comment(text) = &stmt.kind
+                && let Some((last, line)) = self.lines.last_mut()
+                && *last == stmt.line && stmt.line != 0 {
+                // A comment that shared its line with code stays on that line.
+                line.push_str(&format!(" //{}", text));
+                continue;
+            }
+            if let Some(end) = prev_end && stmt.line > end + 1 {
+                self.line(0, String::new());
+            }
+            self.stmt(stmt);
+            prev_end = Some(stmt.end_line);
+        }
+    }
+
+    fn nested(&mut self, header_line: usize, header: String, body: &[Stmt]) {
+        self.line(header_line, header);
+        self.indent += 1;
+        self.block(body);
+        self.indent -= 1;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let line = stmt.line;
+        match &stmt.kind {
+            Statement::Print(expr) => self.line(line, format_expr(expr)),
+            // `a b = pair` without the brackets `>>` needs, but `[a] = pair`
+            // would be `a = pair` without them.
+            Statement::Assign(vars, expr) => {
+                let vars = vars.to_string();
+                let vars = match vars.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                    Some(inner) if inner.contains(' ') || inner.starts_with("...") => inner,
+                    _ => &vars,
+                };
+                self.line(line, format!("{} = {}", vars, format_expr(expr)));
+            }
+            Statement::AugAssign(name, op, expr) => self.line(line, format!("{} {}= {}", name, op, format_expr(expr))),
+            Statement::IncDec(name, op) => self.line(line, format!("{}{}", name, op)),
+            Statement::If(cond, then_block, else_ifs, else_block) => {
+                self.nested(line, format!("? {} {{", format_expr(cond)), then_block);
+                for (elif_cond, block) in else_ifs {
+                    self.line(0, "}".to_string());
+                    self.nested(0, format!("?? {} {{", format_expr(elif_cond)), block);
+                }
+                if !else_block.is_empty() {
+                    self.line(0, "}".to_string());
+                    self.nested(0, "?? {".to_string(), else_block);
+                }
+                self.line(stmt.end_line, "}".to_string());
+            }
+            Statement::While(cond, body) => {
+                self.nested(line, format!("@ {} {{", format_expr(cond)), body);
+                self.line(stmt.end_line, "}".to_string());
+            }
+            Statement::For(vars, list, body) => {
+                self.nested(line, format!(">> {} {} {{", vars, format_expr(list)), body);
+                self.line(stmt.end_line, "}".to_string());
+            }
+            Statement::Match(subject, arms) => {
+                self.line(line, format!("?> {} {{", format_expr(subject)));
+                self.indent += 1;
+                let mut prev_end = None;
+                for arm in arms {
+                    // The parser keeps comments between arms in the arm after
+                    // them, or the last one: they go back around the arm.
+                    let start = arm.body.iter().position(|s| s.line >= arm.line).unwrap_or(arm.body.len());
+                    let end = arm.body.iter().rposition(|s| s.line <= arm.end_line).map_or(start, |i| i + 1).max(start);
+                    for comment in &arm.body[..start] {
+                        if let Some(end) = prev_end && comment.line > end + 1 {
+                            self.line(0, String::new());
+                        }
+                        self.block(std::slice::from_ref(comment));
+                        prev_end = Some(comment.line);
+                    }
+                    if let Some(end) = prev_end && arm.line > end + 1 {
+                        self.line(0, String::new());
+                    }
+                    let header = match &arm.guard {
+                        Some(guard) => format!("{} ? {} {{", arm.pattern, format_expr(guard)),
+                        None => format!("{} {{", arm.pattern),
+                    };
+                    self.nested(arm.line, header, &arm.body[start..end]);
+                    self.line(arm.end_line, "}".to_string());
+                    prev_end = Some(arm.end_line);
+                    self.block(&arm.body[end..]);
+                }
+                self.indent -= 1;
+                self.line(stmt.end_line, "}".to_string());
+            }
+            Statement::FunctionDef(name, params, body) => {
+                self.nested(line, format!("{}({}) => {{", name, show_params(params)), body);
+                self.line(stmt.end_line, "}".to_string());
+            }
+            Statement::QuickFunctionDef(name, params, expr) => {
+                self.line(line, format!("{}({}) ~> {}", name, show_params(params), format_expr(expr)));
+            }
+            Statement::FunctionCall(name, args, mutates) => {
+                self.line(line, format_call(name, args, *mutates));
+            }
+            Statement::Return(expr) => self.line(line, format!("-> {}", format_expr(expr))),
+            Statement::Input(vars, prompt, _) => {
+                let text = match prompt {
+                    Some(prompt) => format!("+? {} : \"{}\"", vars.join(" "), prompt),
+                    None => format!("+? {}", vars.join(" ")),
+                };
+                self.line(line, text);
+            }
+            Statement::Import(path, names) => {
+                let mut text = format!("<- \"{}\"", path);
+                if !names.is_empty() {
+                    let names: Vec<&str> = names.iter().map(|(name, _)| name.as_str()).collect();
+                    text.push_str(&format!(" : {}", names.join(" ")));
+                }
+                self.line(line, text);
+            }
+            Statement::Comment(text) => self.line(line, format!("//{}", text)),
+        }
+    }
+}
+
+pub fn format_expr(expr: &Expr) -> String {
+    match expr {
+        Expr::Number(n) => n.to_string(),
+        Expr::Text(s) => format!("\"{}\"", s),
+        Expr::Bool(b) => (if *b { "yes" } else { "no" }).to_string(),
+        Expr::Variable(name) => name.to_string(),
+        Expr::List(items) => {
+            let items: Vec<String> = items.iter().map(format_expr).collect();
+            format!("[{}]", items.join(" "))
+        }
+        Expr::Index(list, index) => {
+            let list_str = match list.as_ref() {
+                Expr::BinaryOp(..) | Expr::List(_) => format!("({})", format_expr(list)),
+                _ => guard_not(format_expr(list)),
+            };
+            format!("{}[{}]", list_str, format_expr(index))
+        }
+        Expr::BinaryOp(left, op, right) => {
+            let left_str = operand(left, op, false);
+            format!("{} {} {}", guard_not(left_str), op, operand(right, op, true))
+        }
+        Expr::FunctionCall(name, args, mutates) => format_call(name, args, *mutates),
+        Expr::Input => "+??".to_string(),
+    }
+}
+
+fn format_call(name: &str, args: &[Expr], mutates: bool) -> String {
+    let args: Vec<String> = args.iter().map(format_expr).collect();
+    let sep = if BUILTINS.iter().any(|b| b.symbol == name) { " -> " } else { " " };
+    format!("{}({}){}", name, args.join(sep), if mutates { "*" } else { "" })
+}
+
+// `!x == y` parses as `!(x == y)`, so a leading `!` has to be wrapped.
+fn guard_not(text: String) -> String {
+    if text.starts_with('!') { format!("({})", text) } else { text }
+}
+
+fn operand(expr: &Expr, parent_op: &str, is_right: bool) -> String {
+    let text = format_expr(expr);
+    let wrap = match expr {
+        Expr::BinaryOp(_, op, _) => {
+            let rank = |o: &str| OPERATORS.iter().position(|p| *p == o).unwrap_or(0);
+            let (child, parent) = (rank(op), rank(parent_op));
+            if child != parent {
+                child < parent
+            } else if parent < 6 {
+                // Comparisons split at the leftmost operator, arithmetic at the rightmost.
+                !is_right
+            } else {
+                is_right
+            }
+        }
+        // `a - -1` would split at the second minus.
+        Expr::Number(n) => is_right && *n < 0.0,
+        _ => false,
+    };
+    if wrap { format!("({})", text) } else { text }
+}
@@ -0,0 +1,1979 @@
+//! Lazy, the programming language with no keywords.
+//!
+//! The `lazy` command is a thin wrapper around this crate, so everything it
+//! does can also be done from Rust:
+//!
+//! ```
+//! use lazy::{Interpreter, Value};
+//!
+//! let mut interp = Interpreter::new();
+//! interp.eval_str("square(x) ~> x * x").unwrap();
+//! assert_eq!(interp.eval_str("square(7)").unwrap(), Value::Number(49.0));
+//!
+//! interp.set_var("name", Value::from("Ada"));
+//! assert_eq!(interp.eval_str("\"hi \" + name").unwrap(), Value::from("hi Ada"));
+//! ```
+
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt as std_fmt;
+use std::fs;
+use std::io;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+pub mod build;
+pub mod check;
+pub mod console;
+pub mod debugger;
+pub mod dump;
+pub mod fmt;
+pub mod json;
+mod line_editor;
+pub mod lsp;
+mod modules;
+pub mod optimize;
+pub mod pattern;
+pub mod profile;
+pub mod repl;
+mod resolve;
+pub mod sandbox;
+pub mod testing;
+pub mod transpile;
+mod vm;
+
+use console::{Console, StdConsole};
+use debugger::Debugger;
+use modules::Module;
+use pattern::{Arm, Vars};
+use profile::Profiler;
+use resolve::{Address, Frame, Layout, Symbols};
+use sandbox::{Limit, Limits};
+use vm::{BinOp, Chunk};
+
+// --- DATA TYPES ---
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Number(f64),
+    // Text and lists are shared between copies until one of them changes.
+    Text(Rc<String>),
+    Bool(bool),
+    Nothing,
+    List(Rc<Vec<Value>>),
+    Function(Rc<Function>),
+}
+
+/// A function value. Copies of it share one body, which the bytecode VM
+/// compiles the first time the function is called, and again if it is called
+/// from another interpreter.
+///
+/// ```
+/// use lazy::{Interpreter, Value};
+///
+/// let mut first = Interpreter::new();
+/// first.use_bytecode(true);
+/// first.eval_str("scale = 10\ntimes(x) ~> x * scale\ntimes(2)").unwrap();
+///
+/// let mut second = Interpreter::new();
+/// second.use_bytecode(true);
+/// second.set_var("offset", Value::Number(1.0));
+/// second.set_var("scale", Value::Number(3.0));
+/// second.set_var("times", first.get_var("times"));
+/// assert_eq!(second.call("times", vec![Value::Number(2.0)]).unwrap(), Value::Number(6.0));
+/// ```
+#[derive(Debug)]
+pub struct Function {
+    pub params: Vec<Vars>,
+    pub body: Vec<Stmt>,
+    code: RefCell<Option<Rc<Chunk>>>,
+    layout: RefCell<Option<Rc<Layout>>>,
+    // The file the body's lines are in, for the debugger.
+    file: Option<PathBuf>,
+}
+
+impl Function {
+    pub fn new(params: Vec<Vars>, body: Vec<Stmt>) -> Self {
+        Function { params, body, code: RefCell::new(None), layout: RefCell::new(None), file: None }
+    }
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.params == other.params && self.body == other.body
+    }
+}
+
+impl std_fmt::Display for Value {
+    fn fmt(&self, f: &mut std_fmt::Formatter) -> std_fmt::Result {
+        match self {
+            Value::Number(n) => {
+                if n.fract() == 0.0 { write!(f, "{}", *n as i64) } else { write!(f, "{}", n) }
+            }
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Bool(b) => write!(f, "{}", if *b { "yes" } else { "no" }),
+            Value::Nothing => write!(f, ""),
+            Value::List(items) => {
+                let strs: Vec<String> = items.iter().map(|v| {
+                    match v {
+                        Value::Text(t) => format!("\"{}\"", t),
+                        _ => format!("{}", v)
+                    }
+                }).collect();
+                write!(f, "[{}]", strs.join(" "))
+            }
+            Value::Function(_) => write!(f, "<function>"),
+        }
+    }
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::Text(_) => "text",
+            Value::Bool(_) => "bool",
+            Value::Nothing => "nothing",
+            Value::List(_) => "list",
+            Value::Function(_) => "function",
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Nothing => 0,
+            Value::Bool(_) => 1,
+            Value::Number(_) => 2,
+            Value::Text(_) => 3,
+            Value::List(_) => 4,
+            Value::Function(_) => 5,
+        }
+    }
+
+    // Total order over every value, used by `++` and `--`:
+    // nothing < booleans < numbers < text < lists < functions.
+    // Lists compare element by element, then by length.
+    fn total_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a.total_cmp(b),
+            (Value::Text(a), Value::Text(b)) => a.cmp(b),
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::List(a), Value::List(b)) => {
+                for (x, y) in a.iter().zip(b.iter()) {
+                    let order = x.total_cmp(y);
+                    if order != Ordering::Equal { return order; }
+                }
+                a.len().cmp(&b.len())
+            }
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+
+    // How a value is shown in messages: text quoted, nothing spelled out.
+    pub fn repr(&self) -> String {
+        match self {
+            Value::Text(t) => format!("\"{}\"", t),
+            Value::Nothing => "nothing".to_string(),
+            Value::Function(func) => format!("<function({})>", pattern::show_params(&func.params)),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::Text(Rc::new(s.to_string()))
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::Text(Rc::new(s))
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(items: Vec<Value>) -> Self {
+        Value::List(Rc::new(items))
+    }
+}
+
+// --- STATEMENTS ---
+// A statement together with the source lines it covers. For block statements
+// `end_line` is the line of the closing `}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub line: usize,
+    pub end_line: usize,
+    pub kind: Statement,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Print(Expr),
+    Assign(Vars, Expr),
+    AugAssign(Name, String, Expr),
+    IncDec(Name, String),
+    If(Expr, Vec<Stmt>, Vec<(Expr, Vec<Stmt>)>, Vec<Stmt>),
+    While(Expr, Vec<Stmt>),
+    For(Vars, Expr, Vec<Stmt>),
+    Match(Expr, Vec<Arm>),
+    FunctionDef(String, Vec<Vars>, Vec<Stmt>),
+    QuickFunctionDef(String, Vec<Vars>, Expr),
+    FunctionCall(String, Vec<Expr>, bool), // name, args, mutates
+    Return(Expr),
+    Input(Vec<String>, Option<String>, bool),
+    Import(String, Vec<(String, String)>), // path, (name, bound as)
+    Comment(String),
+}
+
+// --- EXPRESSIONS ---
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    Variable(Name),
+    List(Vec<Expr>),
+    Index(Box<Expr>, Box<Expr>),
+    BinaryOp(Box<Expr>, String, Box<Expr>),
+    FunctionCall(String, Vec<Expr>, bool), // name, args, mutates
+    Input,
+}
+
+/// A variable, as the program names it. Before a program runs, `run` works
+/// out where each one is kept, see `resolve`, so reading or assigning it
+/// doesn't have to look for it by name. Names are equal when they are spelled
+/// the same.
+///
+/// ```
+/// use lazy::{parse, Expr, Name, Statement};
+///
+/// let program = parse("total").unwrap();
+/// assert_eq!(program[0].kind, Statement::Print(Expr::Variable(Name::from("total"))));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Name {
+    text: String,
+    address: Address,
+}
+
+impl Name {
+    pub fn new(text: impl Into<String>) -> Self {
+        Name { text: text.into(), address: Address::default() }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub(crate) fn address(&self) -> Address {
+        self.address
+    }
+
+    pub(crate) fn set_address(&mut self, address: Address) {
+        self.address = address;
+    }
+
+    // A new spelling means a different variable: where the old one was kept
+    // says nothing about it.
+    pub(crate) fn rename(&mut self, text: String) {
+        *self = Name::new(text);
+    }
+}
+
+impl std::ops::Deref for Name {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl AsRef<str> for Name {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+impl std_fmt::Display for Name {
+    fn fmt(&self, f: &mut std_fmt::Formatter) -> std_fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+impl From<&str> for Name {
+    fn from(text: &str) -> Self {
+        Name::new(text)
+    }
+}
+
+impl From<String> for Name {
+    fn from(text: String) -> Self {
+        Name::new(text)
+    }
+}
+
+// --- CHILDREN ---
+// The code directly inside a statement, for passes that only care about some
+// kinds of statement and go through the rest: they handle those and call
+// `children` for everything else, so new syntax can't be skipped over.
+
+/// An expression or block directly inside a statement. An expression comes
+/// with its line, which for a `?>` guard is the arm's.
+#[derive(Debug)]
+pub enum Child<'a> {
+    Expr(&'a Expr, usize),
+    Block(&'a [Stmt]),
+}
+
+/// [`Child`], for passes that rewrite the program.
+#[derive(Debug)]
+pub enum ChildMut<'a> {
+    Expr(&'a mut Expr, usize),
+    Block(&'a mut Vec<Stmt>),
+}
+
+impl Stmt {
+    /// The expressions and blocks directly in this statement, in the order
+    /// they are written.
+    ///
+    /// ```
+    /// use lazy::{parse, Child};
+    ///
+    /// let program = parse("@ n < 3 {\n  n++\n}").unwrap();
+    /// let children = program[0].children();
+    /// assert!(matches!(children[..], [Child::Expr(_, 1), Child::Block([_])]));
+    /// ```
+    pub fn children(&self) -> Vec<Child<'_>> {
+        let line = self.line;
+        match &self.kind {
+            Statement::Print(expr) | Statement::Assign(_, expr) | Statement::AugAssign(_, _, expr)
+            | Statement::QuickFunctionDef(_, _, expr) | Statement::Return(expr) => vec![Child::Expr(expr, line)],
+            Statement::If(cond, then_block, else_ifs, else_block) => {
+                let mut children = vec![Child::Expr(cond, line), Child::Block(then_block)];
+                for (cond, block) in else_ifs {
+                    children.push(Child::Expr(cond, line));
+                    children.push(Child::Block(block));
+                }
+                children.push(Child::Block(else_block));
+                children
+            }
+            Statement::While(expr, body) | Statement::For(_, expr, body) => vec![Child::Expr(expr, line), Child::Block(body)],
+            Statement::Match(subject, arms) => {
+                let mut children = vec![Child::Expr(subject, line)];
+                for arm in arms {
+                    if let Some(guard) = &arm.guard {
+                        children.push(Child::Expr(guard, arm.line));
+                    }
+                    children.push(Child::Block(&arm.body));
+                }
+                children
+            }
+            Statement::FunctionDef(_, _, body) => vec![Child::Block(body)],
+            Statement::FunctionCall(_, args, _) => args.iter().map(|arg| Child::Expr(arg, line)).collect(),
+            Statement::IncDec(_, _) | Statement::Input(_, _, _) | Statement::Import(_, _) | Statement::Comment(_) => Vec::new(),
+        }
+    }
+
+    /// The blocks directly in this statement.
+    pub fn blocks(&self) -> impl Iterator<Item = &[Stmt]> {
+        self.children().into_iter().filter_map(|child| match child {
+            Child::Block(block) => Some(block),
+            Child::Expr(..) => None,
+        })
+    }
+
+    /// [`Stmt::children`], to change in place.
+    pub fn children_mut(&mut self) -> Vec<ChildMut<'_>> {
+        let line = self.line;
+        match &mut self.kind {
+            Statement::Print(expr) | Statement::Assign(_, expr) | Statement::AugAssign(_, _, expr)
+            | Statement::QuickFunctionDef(_, _, expr) | Statement::Return(expr) => vec![ChildMut::Expr(expr, line)],
+            Statement::If(cond, then_block, else_ifs, else_block) => {
+                let mut children = vec![ChildMut::Expr(cond, line), ChildMut::Block(then_block)];
+                for (cond, block) in else_ifs {
+                    children.push(ChildMut::Expr(cond, line));
+                    children.push(ChildMut::Block(block));
+                }
+                children.push(ChildMut::Block(else_block));
+                children
+            }
+            Statement::While(expr, body) | Statement::For(_, expr, body) => {
+                vec![ChildMut::Expr(expr, line), ChildMut::Block(body)]
+            }
+            Statement::Match(subject, arms) => {
+                let mut children = vec![ChildMut::Expr(subject, line)];
+                for arm in arms {
+                    if let Some(guard) = &mut arm.guard {
+                        children.push(ChildMut::Expr(guard, arm.line));
+                    }
+                    children.push(ChildMut::Block(&mut arm.body));
+                }
+                children
+            }
+            Statement::FunctionDef(_, _, body) => vec![ChildMut::Block(body)],
+            Statement::FunctionCall(_, args, _) => args.iter_mut().map(|arg| ChildMut::Expr(arg, line)).collect(),
+            Statement::IncDec(_, _) | Statement::Input(_, _, _) | Statement::Import(_, _) | Statement::Comment(_) => Vec::new(),
+        }
+    }
+}
+
+impl Expr {
+    /// The expressions directly inside this one.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::List(items) | Expr::FunctionCall(_, items, _) => items.iter().collect(),
+            Expr::Index(a, b) | Expr::BinaryOp(a, _, b) => vec![a, b],
+            Expr::Number(_) | Expr::Text(_) | Expr::Bool(_) | Expr::Variable(_) | Expr::Input => Vec::new(),
+        }
+    }
+
+    /// [`Expr::children`], to change in place.
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::List(items) | Expr::FunctionCall(_, items, _) => items.iter_mut().collect(),
+            Expr::Index(a, b) | Expr::BinaryOp(a, _, b) => vec![a, b],
+            Expr::Number(_) | Expr::Text(_) | Expr::Bool(_) | Expr::Variable(_) | Expr::Input => Vec::new(),
+        }
+    }
+}
+
+// --- BUILTINS ---
+struct Builtin {
+    symbol: &'static str,
+    usage: &'static str,
+    help: &'static str,
+    min_args: usize,
+    max_args: usize,
+}
+
+const BUILTINS: &[Builtin] = &[
+    Builtin { symbol: "#", usage: "#(x)", help: "Length of a list or text", min_args: 1, max_args: 1 },
+    Builtin { symbol: "$", usage: "$(x)", help: "Convert to text", min_args: 1, max_args: 1 },
+    Builtin { symbol: "~", usage: "~(x)", help: "Convert to number", min_args: 1, max_args: 1 },
+    Builtin { symbol: "!", usage: "!(bool)", help: "Opposite of yes/no", min_args: 1, max_args: 1 },
+    Builtin { symbol: "?=", usage: "?=(max)", help: "Random whole number from 0 to max-1", min_args: 1, max_args: 1 },
+    Builtin { symbol: "^", usage: "^(list -> val)", help: "Push val to the end of list", min_args: 2, max_args: 2 },
+    Builtin { symbol: "v", usage: "v(list)", help: "Pop the last item of list", min_args: 1, max_args: 1 },
+    Builtin { symbol: "<>", usage: "<>(list)", help: "Reverse list", min_args: 1, max_args: 1 },
+    Builtin { symbol: "++", usage: "++(list) / ++(list -> f)", help: "Sort ascending, optionally by key f(x) or comparator f(a b)", min_args: 1, max_args: 2 },
+    Builtin { symbol: "--", usage: "--(list) / --(list -> f)", help: "Sort descending, optionally by key f(x) or comparator f(a b)", min_args: 1, max_args: 2 },
+    Builtin { symbol: "><", usage: "><(list -> val)", help: "Does list contain val?", min_args: 2, max_args: 2 },
+    Builtin { symbol: "<<", usage: "<<(list)", help: "Remove duplicates from list", min_args: 1, max_args: 1 },
+    Builtin { symbol: "&", usage: "&(list -> sep)", help: "Join list into text with sep between items", min_args: 2, max_args: 2 },
+    Builtin { symbol: "|", usage: "|(text -> sep)", help: "Split text into a list at every sep", min_args: 2, max_args: 2 },
+    Builtin { symbol: "==", usage: "==(actual -> expected)", help: "Stop with an error showing the difference unless actual equals expected", min_args: 2, max_args: 2 },
+    Builtin { symbol: "?!", usage: "?!(bool) / ?!(bool -> msg)", help: "Stop with an error (and msg) unless bool is yes", min_args: 1, max_args: 2 },
+];
+
+// --- INTERPRETER ---
+pub struct Interpreter {
+    // The numbers variable names go by, see `resolve`.
+    symbols: Rc<Symbols>,
+    // Indexed by symbol.
+    globals: Vec<Option<Value>>,
+    // One per function call, innermost last.
+    frames: Vec<Frame>,
+    rng_state: u64,
+    search_path: Vec<PathBuf>,
+    modules: HashMap<PathBuf, Module>,
+    loading: Vec<PathBuf>,
+    debugger: Option<Box<Debugger>>,
+    profiler: Option<Box<Profiler>>,
+    natives: HashMap<String, Native>,
+    console: Box<dyn Console>,
+    // Line of the statement being executed, for error messages.
+    line: usize,
+    // Set by a failed assertion; every block stops until it is taken.
+    error: Option<RuntimeError>,
+    // Syntax errors of a module the program imported. They stop it like
+    // `error`, but `run` returns them instead.
+    syntax_errors: Vec<ParseError>,
+    // Run programs as bytecode on the VM instead of walking the tree.
+    bytecode: bool,
+    // Run programs through `optimize` first.
+    optimize: bool,
+    // Set by `-> f(x)` in a function; `call_value` makes the call in place of
+    // the one returning.
+    tail_call: Option<TailCall>,
+    limits: Limits,
+    // Statements run, items builtins looked at and when time is up, against
+    // `limits`.
+    steps: u64,
+    visits: u64,
+    deadline: Option<Instant>,
+    // The files of a program run from a `build`, by path; `<-` looks only
+    // here when there are any.
+    bundle: HashMap<PathBuf, String>,
+}
+
+struct TailCall {
+    name: String,
+    func: Rc<Function>,
+    args: Vec<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub line: usize,
+    pub message: String,
+    /// The sandbox limit the program ran out of, if that is what stopped it.
+    pub limit: Option<Limit>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        let mut seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64;
+        let hasher = RandomState::new().build_hasher();
+        seed ^= hasher.finish();
+        if seed == 0 { seed = 123456789; }
+
+        Self {
+            symbols: Rc::default(),
+            globals: Vec::new(),
+            frames: Vec::new(),
+            rng_state: seed,
+            search_path: Vec::new(),
+            modules: HashMap::new(),
+            loading: Vec::new(),
+            debugger: None,
+            profiler: None,
+            natives: HashMap::new(),
+            console: Box::new(StdConsole),
+            line: 0,
+            error: None,
+            syntax_errors: Vec::new(),
+            bytecode: false,
+            optimize: true,
+            tail_call: None,
+            limits: Limits::default(),
+            steps: 0,
+            visits: 0,
+            deadline: None,
+            bundle: HashMap::new(),
+        }
+    }
+
+    // Forgets the program: its variables, functions and imported modules.
+    // The configuration it was run with (limits, console, natives, engine,
+    // search path) stays, for the REPL's `:reset`.
+    pub(crate) fn reset(&mut self) {
+        self.symbols = Rc::default();
+        self.globals.clear();
+        self.frames.clear();
+        self.modules.clear();
+        self.loading.clear();
+        self.line = 0;
+        self.error = None;
+        self.syntax_errors.clear();
+        self.tail_call = None;
+    }
+
+    pub fn get_var(&self, name: &str) -> Value {
+        self.lookup(self.symbol(name))
+    }
+
+    pub fn set_var(&mut self, name: &str, val: Value) {
+        self.assign(self.symbol(name), val);
+    }
+
+    fn fail(&mut self, message: String) {
+        if self.error.is_none() {
+            self.error = Some(RuntimeError { line: self.line, message, limit: None });
+        }
+    }
+
+    // The end of input reads as an empty answer.
+    fn read_input(&mut self, prompt: &str) -> String {
+        if !self.input_allowed() { return String::new(); }
+        self.console.prompt(prompt);
+        self.console.read_line().unwrap_or_default().trim().to_string()
+    }
+
+    fn parse_input_value(&self, input: &str) -> Value {
+        if let Ok(num) = input.parse::<f64>() {
+            Value::Number(num)
+        } else {
+            Value::Text(Rc::new(input.to_string()))
+        }
+    }
+
+    // `+? a b : "prompt"`, or with `is_iter` a prompt per variable where `{?}`
+    // is its number.
+    fn read_vars(&mut self, vars: &[String], prompt: &Option<String>, is_iter: bool) {
+        if is_iter {
+            let base_prompt = prompt.as_ref().map(|s| s.as_str()).unwrap_or("+? ");
+            for (i, var) in vars.iter().enumerate() {
+                let actual_prompt = base_prompt.replace("{?}", &(i + 1).to_string());
+                let input = self.read_input(&actual_prompt);
+                let val = self.parse_input_value(&input);
+                self.set_var(var, val);
+            }
+        } else {
+            let actual_prompt = prompt.as_ref().map(|s| s.as_str()).unwrap_or("+? ");
+            for var in vars {
+                let input = self.read_input(actual_prompt);
+                let val = self.parse_input_value(&input);
+                self.set_var(var, val);
+            }
+        }
+    }
+
+    fn execute(&mut self, stmt: &Stmt) -> Option<Value> {
+        if self.error.is_some() { return Some(Value::Nothing); }
+        self.line = stmt.line;
+        if !self.tick() { return Some(Value::Nothing); }
+        if self.debugger.is_some() { self.debug_hook(stmt); }
+        if let Some(profiler) = &mut self.profiler { profiler.hit(stmt.line); }
+        match &stmt.kind {
+            Statement::Print(expr) => {
+                let val = self.eval_expr(expr);
+                if val != Value::Nothing && self.error.is_none() {
+                    let text = self.render(&val, false);
+                    if self.error.is_none() { self.console.write(&format!("{}\n", text)); }
+                }
+                None
+            }
+            Statement::Assign(vars, expr) => {
+                let val = self.eval_expr(expr);
+                self.assign_vars(vars, val);
+                None
+            }
+            Statement::AugAssign(name, op, expr) => {
+                let slot = self.slot(name);
+                let current_val = self.load(slot);
+                if current_val == Value::Nothing { return None; }
+                let operand = self.eval_expr(expr);
+                let new_val = match BinOp::from_symbol(op) {
+                    Some(op) => {
+                        self.release(slot, &current_val);
+                        let val = append(current_val, op, &operand);
+                        self.sized(val)
+                    }
+                    None => Value::Nothing,
+                };
+                self.store(slot, new_val);
+                None
+            }
+            Statement::IncDec(name, op) => {
+                let slot = self.slot(name);
+                let current_val = self.load(slot);
+                let one = Value::Number(1.0);
+                let new_val = match op.as_str() {
+                    "++" => self.apply_op(&current_val, "+", &one),
+                    "--" => self.apply_op(&current_val, "-", &one),
+                    _ => current_val
+                };
+                self.store(slot, new_val);
+                None
+            }
+            Statement::If(cond, then_block, else_ifs, else_block) => {
+                let c_val = self.eval_expr(cond);
+                if matches!(c_val, Value::Bool(true)) {
+                    return self.run_block(then_block);
+                }
+                for (elif_cond, elif_block) in else_ifs {
+                    let elif_val = self.eval_expr(elif_cond);
+                    if matches!(elif_val, Value::Bool(true)) {
+                        return self.run_block(elif_block);
+                    }
+                }
+                self.run_block(else_block)
+            }
+            Statement::While(cond, body) => {
+                while matches!(self.eval_expr(cond), Value::Bool(true)) && self.error.is_none() {
+                    if let Some(v) = self.run_block(body) {
+                        return Some(v);
+                    }
+                    // One more time round, like the VM's jump back.
+                    if !self.tick() { return Some(Value::Nothing); }
+                }
+                None
+            }
+            Statement::For(vars, list_expr, body) => {
+                if let Value::List(items) = self.eval_expr(list_expr) {
+                    for item in items.iter() {
+                        self.assign_vars(vars, item.clone());
+                        if let Some(v) = self.run_block(body) {
+                            return Some(v);
+                        }
+                        if !self.tick() { return Some(Value::Nothing); }
+                    }
+                }
+                None
+            }
+            Statement::Match(subject, arms) => self.run_match(subject, arms),
+            Statement::FunctionDef(name, params, body) => {
+                let func = Function { file: self.loading.last().cloned(), ..Function::new(params.clone(), body.clone()) };
+                self.define(self.symbol(name), Value::Function(Rc::new(func)));
+                None
+            }
+            Statement::QuickFunctionDef(name, params, expr) => {
+                let body = vec![Stmt { line: stmt.line, end_line: stmt.line, kind: Statement::Return(expr.clone()) }];
+                let func = Function { file: self.loading.last().cloned(), ..Function::new(params.clone(), body) };
+                self.define(self.symbol(name), Value::Function(Rc::new(func)));
+                None
+            }
+            Statement::FunctionCall(name, args, mutates) => {
+                self.eval_call(name, args, *mutates);
+                None
+            }
+            Statement::Input(vars, prompt, is_iter) => {
+                self.read_vars(vars, prompt, *is_iter);
+                None
+            }
+            Statement::Import(path, names) => {
+                self.import(path, names);
+                None
+            }
+            Statement::Return(Expr::FunctionCall(name, args, false)) if !BUILTINS.iter().any(|b| b.symbol == name) => {
+                let args = args.iter().map(|a| self.eval_expr(a)).collect();
+                let fn_val = self.get_var(name);
+                Some(self.return_call(name, fn_val, args))
+            }
+            Statement::Return(expr) => {
+                Some(self.eval_expr(expr))
+            }
+            Statement::Comment(_) => None,
+        }
+    }
+
+    fn run_block(&mut self, body: &[Stmt]) -> Option<Value> {
+        for stmt in body {
+            if let Some(val) = self.execute(stmt) {
+                return Some(val);
+            }
+        }
+        None
+    }
+
+    // A whole program or module, on whichever engine is selected.
+    fn run_program(&mut self, stmts: &[Stmt]) -> Option<Value> {
+        let mut stmts = stmts.to_vec();
+        if self.optimize {
+            stmts = optimize::optimize(stmts);
+        }
+        self.resolve(&mut stmts);
+        self.run_optimized(&stmts)
+    }
+
+    fn run_optimized(&mut self, stmts: &[Stmt]) -> Option<Value> {
+        if self.bytecode {
+            self.run_chunk(&vm::compile(stmts, &self.symbols, self.loading.last().map(PathBuf::as_path)))
+        } else {
+            self.run_block(stmts)
+        }
+    }
+
+    fn eval_expr(&mut self, expr: &Expr) -> Value {
+        match expr {
+            Expr::Number(n) => Value::Number(*n),
+            Expr::Text(s) => Value::Text(Rc::new(s.clone())),
+            Expr::Bool(b) => Value::Bool(*b),
+            Expr::Variable(name) => self.load(self.slot(name)),
+            Expr::List(items) => {
+                let vals: Vec<Value> = items.iter().map(|e| self.eval_expr(e)).collect();
+                self.sized(Value::List(Rc::new(vals)))
+            }
+            Expr::Index(list_expr, index_expr) => {
+                let list_val = self.eval_expr(list_expr);
+                let index_val = self.eval_expr(index_expr);
+                index_value(list_val, index_val)
+            }
+            Expr::BinaryOp(left, op, right) => {
+                let l = self.eval_expr(left);
+                let r = self.eval_expr(right);
+                let val = self.apply_op(&l, op, &r);
+                self.sized(val)
+            }
+            Expr::FunctionCall(name, args, mutates) => self.eval_call(name, args, *mutates),
+            Expr::Input => {
+                let input = self.read_input("+? ");
+                self.parse_input_value(&input)
+            }
+        }
+    }
+
+    fn eval_call(&mut self, name: &str, args: &[Expr], mutates: bool) -> Value {
+        let arg_vals: Vec<Value> = args.iter().map(|a| self.eval_expr(a)).collect();
+        let target = match args.first() {
+            Some(Expr::Variable(var_name)) if mutates => Some(var_name),
+            _ => None,
+        };
+        let target = target.map(|var_name| self.slot(var_name));
+        if let (Some(slot), Some(val)) = (target, arg_vals.first()) && in_place(name, arg_vals.len()) {
+            self.release(slot, val);
+        }
+        let result = self.call_function(name, arg_vals, mutates);
+        let result = self.sized(result);
+
+        if let Some(slot) = target {
+            self.store(slot, result.clone());
+        }
+        result
+    }
+
+    fn next_random(&mut self, max: u64) -> f64 {
+        if max == 0 { return 0.0; }
+        let mut x = self.rng_state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.rng_state = x;
+        (x % max) as f64
+    }
+
+    fn apply_op(&self, left: &Value, op: &str, right: &Value) -> Value {
+        match BinOp::from_symbol(op) {
+            Some(op) => binary(left, op, right),
+            None => Value::Nothing,
+        }
+    }
+
+    fn call_function(&mut self, name: &str, args: Vec<Value>, _mutates: bool) -> Value {
+        match name {
+            "?=" => {
+                if let Some(Value::Number(max_float)) = args.first() {
+                    return Value::Number(self.next_random(*max_float as u64));
+                }
+                Value::Number(0.0)
+            },
+            "#" => {
+                if let Some(Value::List(i)) = args.first() { return Value::Number(i.len() as f64); }
+                if let Some(Value::Text(s)) = args.first() { return Value::Number(s.len() as f64); }
+                Value::Number(0.0)
+            }
+            "$" => {
+                if let Some(v) = args.first() { return Value::Text(Rc::new(self.render(v, false))); }
+                Value::Text(Rc::default())
+            }
+            "~" => {
+                if let Some(Value::Text(s)) = args.first() {
+                    return s.parse::<f64>().map(Value::Number).unwrap_or(Value::Number(0.0));
+                }
+                if let Some(Value::Number(n)) = args.first() { return Value::Number(*n); }
+                Value::Number(0.0)
+            }
+            "^" => {
+                let mut args = args.into_iter();
+                if let (Some(Value::List(mut items)), Some(val)) = (args.next(), args.next()) {
+                    Rc::make_mut(&mut items).push(val);
+                    return Value::List(items);
+                }
+                Value::Nothing
+            },
+            "v" => {
+                if let Some(Value::List(mut items)) = args.into_iter().next() && !items.is_empty() {
+                    Rc::make_mut(&mut items).pop();
+                    return Value::List(items);
+                }
+                Value::Nothing
+            },
+            "&" => {
+                if let (Some(Value::List(items)), Some(Value::Text(sep))) = (args.first(), args.get(1)) {
+                    let strs: Vec<String> = items.iter().map(|v| self.render(v, false)).collect();
+                    return Value::Text(Rc::new(strs.join(sep.as_str())));
+                }
+                Value::Text(Rc::default())
+            },
+            "|" => {
+                if let (Some(Value::Text(s)), Some(Value::Text(sep))) = (args.first(), args.get(1)) {
+                    if sep.is_empty() { return Value::List(Rc::default()); }
+                    let parts: Vec<Value> = s.split(sep.as_str()).map(|p| Value::Text(Rc::new(p.to_string()))).collect();
+                    return Value::List(Rc::new(parts));
+                }
+                Value::List(Rc::default())
+            },
+            "!" => {
+                if let Some(Value::Bool(b)) = args.first() { return Value::Bool(!b); }
+                Value::Bool(false)
+            },
+            "<>" => {
+                if let Some(Value::List(mut items)) = args.into_iter().next() {
+                    Rc::make_mut(&mut items).reverse();
+                    return Value::List(items);
+                }
+                Value::Nothing
+            },
+            "++" | "--" => {
+                let mut args = args.into_iter();
+                if let Some(Value::List(mut items)) = args.next() {
+                    self.sort_values(Rc::make_mut(&mut items), args.next().as_ref(), name == "--");
+                    return Value::List(items);
+                }
+                Value::Nothing
+            },
+            "><" => {
+                if let (Some(Value::List(items)), Some(val)) = (args.first(), args.get(1)) {
+                    for item in items.iter() {
+                        if self.equal(item, val) {
+                            return Value::Bool(true);
+                        }
+                    }
+                    return Value::Bool(false);
+                }
+                Value::Bool(false)
+            },
+            "==" => {
+                self.assert_equal(args.first().unwrap_or(&Value::Nothing), args.get(1).unwrap_or(&Value::Nothing));
+                Value::Nothing
+            },
+            "?!" => {
+                if args.first() != Some(&Value::Bool(true)) {
+                    let message = match args.get(1) {
+                        Some(msg) => format!("assertion failed: {}", msg),
+                        None => "assertion failed".to_string(),
+                    };
+                    self.fail(message);
+                }
+                Value::Nothing
+            },
+            "<<" => {
+                if let Some(Value::List(items)) = args.first() {
+                    let mut unique: Vec<Value> = Vec::new();
+                    for item in items.iter() {
+                        if self.error.is_some() { break; }
+                        if !unique.iter().any(|seen| self.equal(seen, item)) {
+                            unique.push(item.clone());
+                        }
+                    }
+                    return Value::List(Rc::new(unique));
+                }
+                Value::Nothing
+            },
+            _ => {
+                let fn_val = self.get_var(name);
+                self.call_user(name, &fn_val, args)
+            }
+        }
+    }
+
+    // A function the program defined, or else a registered one.
+    fn call_user(&mut self, name: &str, fn_val: &Value, args: Vec<Value>) -> Value {
+        if !matches!(fn_val, Value::Function(_)) && self.natives.contains_key(name) {
+            return self.call_native(name, &args);
+        }
+        self.call_value(name, fn_val, args)
+    }
+
+    // A call to a user function returned from a function doesn't need the
+    // frame it returns from any more, so it runs in that frame instead of a new
+    // one, and a recursive loop runs in constant stack.
+    fn return_call(&mut self, name: &str, fn_val: Value, args: Vec<Value>) -> Value {
+        match fn_val {
+            Value::Function(func) if !self.frames.is_empty() && self.error.is_none() => {
+                self.tail_call = Some(TailCall { name: name.to_string(), func, args });
+                Value::Nothing
+            }
+            fn_val => self.call_user(name, &fn_val, args),
+        }
+    }
+
+    // `name` is only used to label the call for the debugger.
+    fn call_value(&mut self, name: &str, fn_val: &Value, args: Vec<Value>) -> Value {
+        if let Value::Function(func) = fn_val {
+            let mut func = func.clone();
+            self.frames.push(Frame::call(self.function_layout(&func), &func.params, args));
+            self.enter_frame(name, &func);
+            if let Some(profiler) = &mut self.profiler { profiler.enter(name, func.file.as_deref()); }
+            let line = self.line;
+            let result = loop {
+                let result = if self.bytecode {
+                    self.run_chunk(&self.function_code(&func))
+                } else {
+                    self.run_block(&func.body)
+                };
+                let Some(next) = self.tail_call.take() else { break result };
+                let frame = self.frames.pop().unwrap_or_default();
+                self.frames.push(frame.reuse(self.function_layout(&next.func), &next.func.params, next.args));
+                self.leave_frame();
+                self.enter_frame(&next.name, &next.func);
+                if let Some(profiler) = &mut self.profiler {
+                    profiler.leave();
+                    profiler.enter(&next.name, next.func.file.as_deref());
+                }
+                func = next.func;
+            };
+            self.line = line;
+            if let Some(profiler) = &mut self.profiler { profiler.leave(); }
+            self.leave_frame();
+            self.frames.pop();
+
+            return result.unwrap_or(Value::Nothing);
+        }
+        Value::Nothing
+    }
+
+    // Sorts by the total order of `Value`, or through a function value:
+    // one parameter means "sort by key", two parameters means "comparator".
+    fn sort_values(&mut self, items: &mut Vec<Value>, by: Option<&Value>, descending: bool) {
+        let direction = |o: Ordering| if descending { o.reverse() } else { o };
+        match by {
+            Some(cmp @ Value::Function(func)) if func.params.len() >= 2 => {
+                *items = self.merge_sort(std::mem::take(items), cmp, descending);
+            }
+            Some(key_fn @ Value::Function(_)) => {
+                let mut keyed: Vec<(Value, Value)> = items.drain(..)
+                    .map(|item| (self.call_value("<key>", key_fn, vec![item.clone()]), item))
+                    .collect();
+                keyed.sort_by(|(a, _), (b, _)| direction(a.total_cmp(b)));
+                items.extend(keyed.into_iter().map(|(_, item)| item));
+            }
+            _ => items.sort_by(|a, b| direction(a.total_cmp(b))),
+        }
+    }
+
+    // A user comparator may not be a consistent order, so we merge by hand
+    // instead of handing it to `sort_by`, which is allowed to panic on that.
+    fn merge_sort(&mut self, mut items: Vec<Value>, cmp: &Value, descending: bool) -> Vec<Value> {
+        if items.len() <= 1 { return items; }
+        let right = items.split_off(items.len() / 2);
+        let left = self.merge_sort(items, cmp, descending);
+        let right = self.merge_sort(right, cmp, descending);
+
+        let mut merged = Vec::with_capacity(left.len() + right.len());
+        let mut left = left.into_iter().peekable();
+        let mut right = right.into_iter().peekable();
+        while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+            if self.right_first(cmp, l.clone(), r.clone(), descending) {
+                merged.push(right.next().unwrap());
+            } else {
+                merged.push(left.next().unwrap());
+            }
+        }
+        merged.extend(left);
+        merged.extend(right);
+        merged
+    }
+
+    // Whether `r` goes before `l`, which came first. A number orders them
+    // like `a - b` does, but `yes` only says that the first argument goes
+    // first, so on `no` the comparator is asked the other way round: equal
+    // items keep the order they came in.
+    fn right_first(&mut self, cmp: &Value, l: Value, r: Value, descending: bool) -> bool {
+        match self.call_value("<comparator>", cmp, vec![l.clone(), r.clone()]) {
+            Value::Number(n) => if descending { n < 0.0 } else { n > 0.0 },
+            result if descending => result == Value::Bool(true),
+            Value::Bool(true) => false,
+            _ => matches!(self.call_value("<comparator>", cmp, vec![r, l]), Value::Bool(true)),
+        }
+    }
+}
+
+fn binary(left: &Value, op: BinOp, right: &Value) -> Value {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => match op {
+            BinOp::Add => Value::Number(l + r),
+            BinOp::Sub => Value::Number(l - r),
+            BinOp::Mul => Value::Number(l * r),
+            BinOp::Div => Value::Number(l / r),
+            BinOp::Rem => Value::Number(l % r),
+            BinOp::Greater => Value::Bool(l > r),
+            BinOp::Less => Value::Bool(l < r),
+            BinOp::Equal => Value::Bool((l - r).abs() < f64::EPSILON),
+            BinOp::NotEqual => Value::Bool((l - r).abs() >= f64::EPSILON),
+            BinOp::GreaterEqual => Value::Bool(l >= r),
+            BinOp::LessEqual => Value::Bool(l <= r),
+        },
+        (Value::Text(l), Value::Text(r)) if op == BinOp::Add => Value::Text(Rc::new(format!("{}{}", l, r))),
+        (Value::Text(l), Value::Text(r)) if op == BinOp::Equal => Value::Bool(l == r),
+        (Value::Text(l), Value::Text(r)) if op == BinOp::NotEqual => Value::Bool(l != r),
+        (Value::Text(l), Value::Number(r)) if op == BinOp::Add => Value::Text(Rc::new(format!("{}{}", l, r))),
+        (Value::Number(l), Value::Text(r)) if op == BinOp::Add => Value::Text(Rc::new(format!("{}{}", l, r))),
+        (Value::Bool(l), Value::Bool(r)) if op == BinOp::Equal => Value::Bool(l == r),
+        (Value::Bool(l), Value::Bool(r)) if op == BinOp::NotEqual => Value::Bool(l != r),
+        (Value::List(l), Value::List(r)) if op == BinOp::Add => {
+            let mut new_list = l.to_vec();
+            new_list.extend(r.iter().cloned());
+            Value::List(Rc::new(new_list))
+        },
+        _ => Value::Nothing,
+    }
+}
+
+// `binary` for `x += ...`: adds to text or a list in place when `x` was the
+// only one holding it.
+fn append(left: Value, op: BinOp, right: &Value) -> Value {
+    match (left, right) {
+        (Value::Text(mut l), Value::Text(r)) if op == BinOp::Add => {
+            Rc::make_mut(&mut l).push_str(r);
+            Value::Text(l)
+        }
+        (Value::Text(mut l), Value::Number(r)) if op == BinOp::Add => {
+            Rc::make_mut(&mut l).push_str(&r.to_string());
+            Value::Text(l)
+        }
+        (Value::List(mut l), Value::List(r)) if op == BinOp::Add => {
+            Rc::make_mut(&mut l).extend(r.iter().cloned());
+            Value::List(l)
+        }
+        (left, right) => binary(&left, op, right),
+    }
+}
+
+// Builtins that never run Lazy code, so the variable a `*` call writes back
+// to can let go of its value while they work on it.
+fn in_place(name: &str, argc: usize) -> bool {
+    BUILTINS.iter().any(|b| b.symbol == name) && !(matches!(name, "++" | "--") && argc > 1)
+}
+
+// `list[i]`, counting from the end for negative `i`.
+fn index_value(list: Value, index: Value) -> Value {
+    if let (Value::List(items), Value::Number(idx)) = (list, index) {
+        let i = idx as i64;
+        let actual_idx = if i < 0 {
+            (items.len() as i64 + i) as usize
+        } else {
+            i as usize
+        };
+        if actual_idx < items.len() { return items[actual_idx].clone(); }
+    }
+    Value::Nothing
+}
+
+// --- EMBEDDING API ---
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interpreter {
+    /// Adds a directory to search for `<-` imports that aren't found next to
+    /// the importing file.
+    pub fn add_search_path(&mut self, dir: impl Into<PathBuf>) {
+        self.search_path.push(dir.into());
+    }
+
+    /// Runs parsed statements. Returns the value of a top-level `->`, if any.
+    /// Nothing runs if the program uses a name that is never defined.
+    pub fn run(&mut self, stmts: &[Stmt]) -> Result<Option<Value>, Error> {
+        self.check_names(stmts)?;
+        self.run_checked(stmts).map_err(|err| self.program_error(err))
+    }
+
+    // What stopped the program: a runtime error, or the syntax errors of a
+    // module it imported.
+    fn program_error(&mut self, err: RuntimeError) -> Error {
+        let errors = std::mem::take(&mut self.syntax_errors);
+        if errors.is_empty() { Error::Runtime(err) } else { Error::Parse(errors) }
+    }
+
+    fn check_names(&self, stmts: &[Stmt]) -> Result<(), Error> {
+        let errors = self.undefined_names(stmts);
+        if errors.is_empty() { Ok(()) } else { Err(Error::Parse(errors)) }
+    }
+
+    fn run_checked(&mut self, stmts: &[Stmt]) -> Result<Option<Value>, RuntimeError> {
+        self.start_budget();
+        self.syntax_errors.clear();
+        let result = self.run_program(stmts);
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(result),
+        }
+    }
+
+    /// Runs a program like a script, except that a bare expression on the
+    /// last line is returned instead of printed.
+    pub fn eval_str(&mut self, code: &str) -> Result<Value, Error> {
+        let mut stmts = parse(code)?;
+        self.check_names(&stmts)?;
+        let last = stmts.iter().rposition(|s| !matches!(s.kind, Statement::Comment(_)));
+        let last_expr = match last {
+            Some(i) if matches!(stmts[i].kind, Statement::Print(_)) => Some(stmts.remove(i)),
+            _ => None,
+        };
+        if let Some(val) = self.run_checked(&stmts).map_err(|err| self.program_error(err))? {
+            return Ok(val);
+        }
+        let Some(Stmt { line, kind: Statement::Print(expr), .. }) = last_expr else { return Ok(Value::Nothing) };
+        self.line = line;
+        let val = self.eval_expr(&expr);
+        match self.error.take() {
+            Some(err) => Err(err.into()),
+            None => Ok(val),
+        }
+    }
+
+    /// Runs a `.lazy` file. Its imports are looked up next to it first.
+    pub fn run_file(&mut self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let stmts = parse(&fs::read_to_string(path)?)?;
+        let file = fs::canonicalize(path)?;
+        self.loading.push(file);
+        let result = self.run(&stmts);
+        self.loading.pop();
+        result?;
+        Ok(())
+    }
+
+    /// Calls a function defined by the program, or a built-in symbol.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, RuntimeError> {
+        self.start_budget();
+        let val = self.call_function(name, args, false);
+        match self.error.take() {
+            Some(err) => Err(err),
+            None => Ok(val),
+        }
+    }
+
+    /// Sends the program's output and `+?` prompts to `console` and reads
+    /// answers from it, instead of the terminal.
+    ///
+    /// ```
+    /// use lazy::console::{BufferConsole, ScriptedConsole};
+    /// use lazy::Interpreter;
+    ///
+    /// let output = BufferConsole::new();
+    /// let mut interp = Interpreter::new();
+    /// interp.set_console(ScriptedConsole::new(["Ada"], output.clone()));
+    /// let program = lazy::parse("+? name : \"Name: \"\n\"Hi \" + name").unwrap();
+    /// interp.run(&program).unwrap();
+    /// assert_eq!(output.contents(), "Name: Hi Ada\n");
+    /// ```
+    pub fn set_console(&mut self, console: impl Console + 'static) {
+        self.console = Box::new(console);
+    }
+
+    /// Times every function call and line from now on, see `profile`.
+    pub fn enable_profiler(&mut self) {
+        self.profiler = Some(Box::new(Profiler::new()));
+    }
+
+    pub fn take_profiler(&mut self) -> Option<Profiler> {
+        self.profiler.take().map(|p| *p)
+    }
+
+    /// Compiles programs to bytecode and runs them on the VM instead of
+    /// walking the syntax tree. Both give the same results; the VM is faster.
+    pub fn use_bytecode(&mut self, on: bool) {
+        self.bytecode = on;
+    }
+
+    /// Whether programs go through `optimize::optimize` before they run. On
+    /// by default; the output is the same either way.
+    pub fn set_optimize(&mut self, on: bool) {
+        self.optimize = on;
+    }
+}
+
+// A Rust function registered with `Interpreter::register`.
+type NativeFn = Box<dyn FnMut(&[Value]) -> Result<Value, String>>;
+
+struct Native {
+    min_args: usize,
+    max_args: usize,
+    func: NativeFn,
+}
+
+impl Interpreter {
+    /// Makes a Rust function callable from Lazy as `name(...)`, taking between
+    /// `min_args` and `max_args` arguments. An `Err` stops the program with
+    /// that message. A function the program defines itself with the same
+    /// name takes precedence.
+    ///
+    /// ```
+    /// use lazy::{Interpreter, Value};
+    ///
+    /// let mut interp = Interpreter::new();
+    /// interp.register("grade", 1, 1, |args| match &args[0] {
+    ///     Value::Number(n) if *n >= 50.0 => Ok(Value::from("pass")),
+    ///     Value::Number(_) => Ok(Value::from("fail")),
+    ///     other => Err(format!("expected a number, got {}", other.type_name())),
+    /// });
+    /// assert_eq!(interp.eval_str("grade(72)").unwrap(), Value::from("pass"));
+    /// assert!(interp.eval_str("grade(\"A\")").is_err());
+    /// ```
+    pub fn register<F>(&mut self, name: &str, min_args: usize, max_args: usize, func: F)
+    where
+        F: FnMut(&[Value]) -> Result<Value, String> + 'static,
+    {
+        self.natives.insert(name.to_string(), Native { min_args, max_args, func: Box::new(func) });
+    }
+
+    fn call_native(&mut self, name: &str, args: &[Value]) -> Value {
+        let Some(native) = self.natives.get_mut(name) else { return Value::Nothing };
+        let result = if args.len() < native.min_args || args.len() > native.max_args {
+            let expected = match (native.min_args, native.max_args) {
+                (min, max) if min == max => min.to_string(),
+                (min, usize::MAX) => format!("at least {}", min),
+                (min, max) => format!("{} to {}", min, max),
+            };
+            Err(format!("takes {} argument(s) but got {}", expected, args.len()))
+        } else {
+            (native.func)(args)
+        };
+        result.unwrap_or_else(|message| {
+            self.fail(format!("{}: {}", name, message));
+            Value::Nothing
+        })
+    }
+}
+
+// --- ERRORS ---
+
+#[derive(Debug)]
+pub enum Error {
+    Parse(Vec<ParseError>),
+    Runtime(RuntimeError),
+    Io(io::Error),
+}
+
+impl std_fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std_fmt::Formatter) -> std_fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std_fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std_fmt::Formatter) -> std_fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std_fmt::Display for Error {
+    fn fmt(&self, f: &mut std_fmt::Formatter) -> std_fmt::Result {
+        match self {
+            Error::Parse(errors) => {
+                let lines: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Error::Runtime(err) => write!(f, "{}", err),
+            Error::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+impl std::error::Error for RuntimeError {}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<Vec<ParseError>> for Error {
+    fn from(errors: Vec<ParseError>) -> Self {
+        Error::Parse(errors)
+    }
+}
+
+impl From<RuntimeError> for Error {
+    fn from(err: RuntimeError) -> Self {
+        Error::Runtime(err)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+// --- PARSER ---
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+// A source line with its code and its `//` comment as separate entries.
+type SourceLine<'a> = (usize, &'a str);
+
+/// Parses a whole program. Fails with every syntax error found.
+pub fn parse(code: &str) -> Result<Vec<Stmt>, Vec<ParseError>> {
+    let (stmts, errors) = parse_program(code);
+    if errors.is_empty() { Ok(stmts) } else { Err(errors) }
+}
+
+/// Parses a whole program, keeping every statement that parsed even when
+/// there are syntax errors. Tools like the formatter and linter use this.
+pub fn parse_program(code: &str) -> (Vec<Stmt>, Vec<ParseError>) {
+    let mut lines: Vec<SourceLine> = Vec::new();
+    for (i, raw) in code.lines().enumerate() {
+        let (code_part, comment) = split_comment(raw);
+        let code_part = code_part.trim();
+        if !code_part.is_empty() { lines.push((i + 1, code_part)); }
+        if let Some(comment) = comment { lines.push((i + 1, comment.trim_end())); }
+    }
+
+    let mut idx = 0;
+    let mut errors = Vec::new();
+    let mut statements = parse_lines(&lines, &mut idx, &mut errors);
+    while idx < lines.len() {
+        errors.push(ParseError { line: lines[idx].0, message: "Unexpected '}' without an open block".to_string() });
+        idx += 1;
+        statements.extend(parse_lines(&lines, &mut idx, &mut errors));
+    }
+    (statements, errors)
+}
+
+// Splits off a `//` comment that is not inside a string.
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut in_quotes = false;
+    for (i, c) in line.char_indices() {
+        if c == '"' { in_quotes = !in_quotes; }
+        if !in_quotes && line[i..].starts_with("//") {
+            return (&line[..i], Some(&line[i..]));
+        }
+    }
+    (line, None)
+}
+
+// Parses statements up to (but not including) the `}` that closes the current block.
+fn parse_lines(lines: &[SourceLine], current: &mut usize, errors: &mut Vec<ParseError>) -> Vec<Stmt> {
+    let mut statements = Vec::new();
+    while *current < lines.len() {
+        let (line_no, line) = lines[*current];
+        let stmt = |end_line: usize, kind: Statement| Stmt { line: line_no, end_line, kind };
+
+        if line == "}" {
+            return statements;
+        }
+
+        if let Some(text) = line.strip_prefix("//") {
+            statements.push(stmt(line_no, Statement::Comment(text.to_string())));
+            *current += 1;
+            continue;
+        }
+
+        if line.starts_with("??") {
+            errors.push(ParseError { line: line_no, message: "Unexpected '??' without matching '?' (Orphaned Else)".to_string() });
+            *current += 1;
+            let _ = parse_block(lines, current, errors);
+            continue;
+        }
+
+        if let Some(subject) = line.strip_prefix("?> ") {
+            let subject = parse_expr(subject.trim().trim_end_matches('{').trim());
+            *current += 1;
+            let (arms, end_line) = pattern::parse_arms(lines, current, errors);
+            statements.push(stmt(end_line, Statement::Match(subject, arms)));
+            continue;
+        }
+
+        if line.contains("~>") {
+            let parts: Vec<&str> = line.split("~>").collect();
+            let sig = parts[0].trim();
+            if let Some(paren_idx) = sig.find('(') {
+                let name = sig[..paren_idx].trim().to_string();
+                let params_str = sig[paren_idx + 1..].trim_end_matches(')').trim();
+                let params = parse_params(params_str, line_no, errors);
+
+                let expr_str = parts[1].trim();
+                let expr = parse_expr(expr_str);
+
+                statements.push(stmt(line_no, Statement::QuickFunctionDef(name, params, expr)));
+                *current += 1;
+                continue;
+            }
+        }
+
+        if line.contains("=>") && !line.starts_with('"') {
+            let parts: Vec<&str> = line.split("=>").collect();
+            let sig = parts[0].trim();
+            if let Some(paren_idx) = sig.find('(') {
+                let name = sig[..paren_idx].trim().to_string();
+                let params_str = sig[paren_idx + 1..].trim_end_matches(')').trim();
+                let params = parse_params(params_str, line_no, errors);
+
+                *current += 1;
+                let (body, end_line) = parse_block(lines, current, errors);
+                statements.push(stmt(end_line, Statement::FunctionDef(name, params, body)));
+                continue;
+            }
+        }
+
+        if let Some(cond_str) = line.strip_prefix("@ ") {
+            let cond_str = cond_str.trim().trim_end_matches('{').trim();
+            let cond = parse_expr(cond_str);
+            *current += 1;
+            let (body, end_line) = parse_block(lines, current, errors);
+            statements.push(stmt(end_line, Statement::While(cond, body)));
+            continue;
+        }
+
+        if let Some(content) = line.strip_prefix(">> ") {
+            let content = content.trim();
+            let var_end = match content.strip_prefix('[') {
+                Some(names) => names.find(']').map(|end| end + 2),
+                None => content.find(char::is_whitespace),
+            };
+            if let Some(var_end) = var_end {
+                let vars = match Vars::parse(&content[..var_end]) {
+                    Ok(vars) => vars,
+                    Err(message) => {
+                        errors.push(ParseError { line: line_no, message });
+                        Vars::One(Name::from(&content[..var_end]))
+                    }
+                };
+                let mut rest = content[var_end..].trim();
+
+                if rest.starts_with("->") {
+                    rest = rest[2..].trim();
+                }
+
+                let list_expr_str = rest.trim_end_matches('{').trim();
+                let list_expr = parse_expr(list_expr_str);
+
+                *current += 1;
+                let (body, end_line) = parse_block(lines, current, errors);
+                statements.push(stmt(end_line, Statement::For(vars, list_expr, body)));
+                continue;
+            }
+        }
+
+        if line.starts_with("? ") && !line.contains(':') {
+            let cond_str = line[2..].trim().trim_end_matches('{').trim();
+            let cond = parse_expr(cond_str);
+            *current += 1;
+            let (then_block, mut end_line) = parse_block(lines, current, errors);
+
+            let mut else_ifs = Vec::new();
+            let mut else_block = Vec::new();
+
+            loop {
+                // Comments between `}` and `??` move into the next block.
+                let mut next = *current;
+                while next < lines.len() && lines[next].1.starts_with("//") { next += 1; }
+                if next >= lines.len() { break; }
+
+                let next_line = lines[next].1;
+                let next_clean = next_line.trim().trim_end_matches('{').trim();
+                let elif_cond_str = next_line.strip_prefix("?? ");
+                if next_clean != "??" && elif_cond_str.is_none() { break; }
+
+                let comments: Vec<Stmt> = lines[*current..next].iter()
+                    .map(|(n, c)| Stmt { line: *n, end_line: *n, kind: Statement::Comment(c[2..].to_string()) })
+                    .collect();
+                *current = next + 1;
+                let (mut block, block_end) = parse_block(lines, current, errors);
+                block.splice(0..0, comments);
+                end_line = block_end;
+
+                if next_clean == "??" {
+                    else_block = block;
+                    break;
+                }
+                let elif_cond_str = elif_cond_str.unwrap_or("").trim().trim_end_matches('{').trim();
+                else_ifs.push((parse_expr(elif_cond_str), block));
+            }
+
+            statements.push(stmt(end_line, Statement::If(cond, then_block, else_ifs, else_block)));
+            continue;
+        }
+
+        if let Some(kind) = parse_simple_statement(line, line_no, errors) {
+            statements.push(stmt(line_no, kind));
+        }
+        *current += 1;
+    }
+    statements
+}
+
+// Parses a block body and consumes its closing `}`, returning the line it was on.
+fn parse_block(lines: &[SourceLine], current: &mut usize, errors: &mut Vec<ParseError>) -> (Vec<Stmt>, usize) {
+    let body = parse_lines(lines, current, errors);
+    if *current < lines.len() {
+        let end_line = lines[*current].0;
+        *current += 1;
+        (body, end_line)
+    } else {
+        (body, lines.last().map(|l| l.0).unwrap_or(0))
+    }
+}
+
+fn parse_simple_statement(line: &str, line_no: usize, errors: &mut Vec<ParseError>) -> Option<Statement> {
+    let line = line.trim();
+
+    if let Some(content) = line.strip_prefix("+? ") {
+        let content = content.trim();
+        if let Some(colon_idx) = content.find(':') {
+            let vars_part = content[..colon_idx].trim();
+            let prompt_part = content[colon_idx + 1..].trim();
+            let vars: Vec<String> = vars_part.split_whitespace().map(|s| s.to_string()).collect();
+            let prompt = if prompt_part.starts_with('"') && prompt_part.ends_with('"') {
+                prompt_part[1..prompt_part.len()-1].to_string()
+            } else {
+                prompt_part.to_string()
+            };
+            let is_iter = prompt.contains("{?}");
+            return Some(Statement::Input(vars, Some(prompt), is_iter));
+        } else {
+            let vars: Vec<String> = content.split_whitespace().map(|s| s.to_string()).collect();
+            return Some(Statement::Input(vars, None, false));
+        }
+    }
+
+    if let Some(content) = line.strip_prefix("<-") {
+        let content = content.trim();
+        let (path, rest) = match content.strip_prefix('"').and_then(|c| c.find('"').map(|end| (c, end))) {
+            Some((c, end)) => (c[..end].to_string(), c[end + 1..].trim()),
+            None => (content.to_string(), ""),
+        };
+        let names: Vec<(String, String)> = rest.strip_prefix(':').unwrap_or("")
+            .split_whitespace()
+            .map(|n| (n.to_string(), n.to_string()))
+            .collect();
+        return Some(Statement::Import(path, names));
+    }
+
+    if let Some(content) = line.strip_prefix("->") {
+        let content = content.trim();
+        return Some(Statement::Return(parse_expr(content)));
+    }
+
+    if line.ends_with("++") && !line.contains('(') {
+        return Some(Statement::IncDec(Name::from(line[..line.len()-2].trim()), "++".to_string()));
+    }
+    if line.ends_with("--") && !line.contains('(') {
+        return Some(Statement::IncDec(Name::from(line[..line.len()-2].trim()), "--".to_string()));
+    }
+
+    for op in &["+=", "-=", "*=", "/="] {
+        if let Some(idx) = line.find(op) {
+            let var = Name::from(line[..idx].trim());
+            let expr = parse_expr(line[idx+2..].trim());
+            return Some(Statement::AugAssign(var, op[..1].to_string(), expr));
+        }
+    }
+
+    if let Some(eq_idx) = find_assign_op(line) {
+        let vars = match Vars::parse(&line[..eq_idx]) {
+            Ok(vars) => vars,
+            Err(message) => {
+                errors.push(ParseError { line: line_no, message });
+                return None;
+            }
+        };
+        let expr = parse_expr(line[eq_idx+1..].trim());
+        return Some(Statement::Assign(vars, expr));
+    }
+
+    if !line.starts_with("=>") && !line.starts_with("}") {
+        let expr = parse_expr(line);
+        match expr {
+            Expr::FunctionCall(name, args, true) => return Some(Statement::FunctionCall(name, args, true)),
+            _ => return Some(Statement::Print(expr)),
+        }
+    }
+
+    None
+}
+
+// --- HELPER FUNCTIONS ---
+
+// `a [b c] d`: names, and lists of names the argument is unpacked into.
+fn parse_params(params_str: &str, line_no: usize, errors: &mut Vec<ParseError>) -> Vec<Vars> {
+    split_args_outside_parens(params_str).into_iter()
+        .map(|param| match param.starts_with('[') {
+            true => Vars::parse(&param).unwrap_or_else(|message| {
+                errors.push(ParseError { line: line_no, message });
+                Vars::Unpack(Vec::new(), None)
+            }),
+            false => Vars::One(Name::from(param)),
+        })
+        .collect()
+}
+
+fn find_assign_op(s: &str) -> Option<usize> {
+    let chars: Vec<(usize, char)> = s.char_indices().collect();
+    let mut i = 0;
+    let mut in_quotes = false;
+
+    while i < chars.len() {
+        let (byte_idx, c) = chars[i];
+        if c == '"' { in_quotes = !in_quotes; }
+
+        if !in_quotes && c == '=' {
+            let prev = if i > 0 { chars[i-1].1 } else { ' ' };
+            let next = if i+1 < chars.len() { chars[i+1].1 } else { ' ' };
+            if prev != '>' && prev != '<' && prev != '!' && prev != '='
+                && prev != '+' && prev != '-' && prev != '*' && prev != '/' && prev != '~'
+                && next != '=' {
+                return Some(byte_idx);
+            }
+        }
+        i += 1;
+    }
+    None
+}
+
+fn parse_expr(s: &str) -> Expr {
+    let s = s.trim();
+
+    if s == "+??" { return Expr::Input; }
+
+    if s.starts_with('!') && !s.starts_with("!=") {
+        let operand = parse_expr(&s[1..]);
+        return Expr::FunctionCall("!".to_string(), vec![operand], false);
+    }
+
+    let logical_ops = ["==", "!=", ">=", "<=", ">", "<"];
+    for op in &logical_ops {
+        if let Some(idx) = find_op_outside_parens(s, op, false) {
+            let left = parse_expr(&s[..idx]);
+            let right = parse_expr(&s[idx + op.len()..]);
+            return Expr::BinaryOp(Box::new(left), op.to_string(), Box::new(right));
+        }
+    }
+
+    let math_ops = ["+", "-", "*", "/", "%"];
+    for op in &math_ops {
+        if let Some(idx) = find_op_outside_parens(s, op, true) {
+            let left = parse_expr(&s[..idx]);
+            let right = parse_expr(&s[idx + op.len()..]);
+            return Expr::BinaryOp(Box::new(left), op.to_string(), Box::new(right));
+        }
+    }
+
+    if s.starts_with('[') && s.ends_with(']') {
+        let content = s[1..s.len()-1].trim();
+        if content.is_empty() { return Expr::List(vec![]); }
+        let items: Vec<Expr> = split_args_outside_parens(content).into_iter().map(|i| parse_expr(&i)).collect();
+        return Expr::List(items);
+    }
+
+    if s.ends_with(']') && let Some(idx) = find_matching_open(s, '[', ']') {
+        let list = parse_expr(&s[..idx]);
+        let index = parse_expr(&s[idx+1..s.len()-1]);
+        return Expr::Index(Box::new(list), Box::new(index));
+    }
+
+    if s.ends_with(")*") {
+        if let Some(idx) = find_matching_open(&s[..s.len()-1], '(', ')') {
+            let name = s[..idx].trim().to_string();
+            let args_str = s[idx+1..s.len()-2].trim();
+            let args = parse_function_args(args_str);
+            return Expr::FunctionCall(name, args, true);
+        }
+    } else if s.ends_with(')') && let Some(idx) = find_matching_open(s, '(', ')') {
+        let name = s[..idx].trim().to_string();
+
+        if name.is_empty() {
+            return parse_expr(&s[idx+1..s.len()-1]);
+        }
+
+        let args_str = s[idx+1..s.len()-1].trim();
+        let args = parse_function_args(args_str);
+        return Expr::FunctionCall(name, args, false);
+    }
+
+    if s.starts_with('"') && s.ends_with('"') && s.len() >= 2 {
+        return Expr::Text(s[1..s.len()-1].to_string());
+    }
+
+    if let Ok(n) = s.parse::<f64>() { return Expr::Number(n); }
+
+    if s == "yes" || s == "true" { return Expr::Bool(true); }
+    if s == "no" || s == "false" { return Expr::Bool(false); }
+
+    Expr::Variable(Name::from(s))
+}
+
+fn parse_function_args(args_str: &str) -> Vec<Expr> {
+    if args_str.is_empty() { return vec![]; }
+    let arrow_parts = split_by_arrow(args_str);
+    if arrow_parts.len() > 1 {
+        return arrow_parts.iter().map(|p| parse_expr(p.trim())).collect();
+    }
+    let space_parts = split_args_outside_parens(arrow_parts[0]);
+    space_parts.iter().map(|p| parse_expr(p.trim())).collect()
+}
+
+fn split_by_arrow(s: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let chars: Vec<(usize, char)> = s.char_indices().collect();
+    let mut current_byte_start = 0;
+    let mut i = 0;
+    let mut in_quotes = false;
+    let mut paren_depth = 0;
+
+    while i < chars.len() {
+        let (byte_idx, c) = chars[i];
+        if c == '"' { in_quotes = !in_quotes; }
+
+        if !in_quotes {
+            if c == '(' { paren_depth += 1; }
+            else if c == ')' { paren_depth -= 1; }
+
+            if paren_depth == 0 && i + 1 < chars.len() && c == '-' && chars[i+1].1 == '>' {
+                result.push(&s[current_byte_start..byte_idx]);
+                i += 2;
+                if i < chars.len() { current_byte_start = chars[i].0; }
+                else { current_byte_start = s.len(); }
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    if current_byte_start < s.len() { result.push(&s[current_byte_start..]); }
+    else if result.is_empty() { result.push(s); }
+    result
+}
+
+fn find_op_outside_parens(s: &str, op: &str, reverse: bool) -> Option<usize> {
+    let s_chars: Vec<(usize, char)> = s.char_indices().collect();
+    let op_chars: Vec<char> = op.chars().collect();
+    let len = s_chars.len();
+    let op_len = op_chars.len();
+
+    if len < op_len { return None; }
+
+    let check_at = |i: usize| -> bool {
+        if i + op_len > len { return false; }
+
+        if i == 0 || i + op_len == len { return false; }
+
+        if op == "<" {
+            if i + 1 < len && s_chars[i+1].1 == '>' { return false; }
+            if i > 0 && s_chars[i-1].1 == '>' { return false; }
+            if i + 1 < len && s_chars[i+1].1 == '<' { return false; }
+            if i > 0 && s_chars[i-1].1 == '<' { return false; }
+        }
+        if op == ">" {
+            if i > 0 && s_chars[i-1].1 == '<' { return false; }
+            if i + 1 < len && s_chars[i+1].1 == '<' { return false; }
+            if i + 1 < len && s_chars[i+1].1 == '>' { return false; }
+            if i > 0 && s_chars[i-1].1 == '>' { return false; }
+        }
+        if op == "+" {
+            if i + 1 < len && s_chars[i+1].1 == '+' { return false; }
+            if i > 0 && s_chars[i-1].1 == '+' { return false; }
+        }
+        if op == "-" {
+            if i + 1 < len && s_chars[i+1].1 == '-' { return false; }
+            if i > 0 && s_chars[i-1].1 == '-' { return false; }
+            if i + 1 < len && s_chars[i+1].1 == '>' { return false; }
+        }
+
+        for k in 0..op_len {
+            if s_chars[i+k].1 != op_chars[k] { return false; }
+        }
+        true
+    };
+
+    let mut balance = 0;
+    let mut in_quotes = false;
+
+    if reverse {
+        let mut i = len;
+        while i > 0 {
+            i -= 1;
+            let (_, c) = s_chars[i];
+            if c == '"' { in_quotes = !in_quotes; }
+            if !in_quotes {
+                if c == ')' || c == ']' { balance += 1; }
+                else if c == '(' || c == '[' { balance -= 1; }
+                if balance == 0 && check_at(i) { return Some(s_chars[i].0); }
+            }
+        }
+    } else {
+        let mut i = 0;
+        while i < len {
+            let (_, c) = s_chars[i];
+            if c == '"' { in_quotes = !in_quotes; }
+            if !in_quotes {
+                if c == '(' || c == '[' { balance += 1; }
+                else if c == ')' || c == ']' { balance -= 1; }
+                if balance == 0 && check_at(i) { return Some(s_chars[i].0); }
+            }
+            i += 1;
+        }
+    }
+    None
+}
+
+fn find_matching_open(s: &str, open: char, close: char) -> Option<usize> {
+    let chars: Vec<(usize, char)> = s.char_indices().collect();
+    if chars.len() < 2 { return None; }
+    let mut balance = 1;
+    let mut i = chars.len() - 2;
+
+    loop {
+        let (byte_idx, c) = chars[i];
+        if c == close { balance += 1; }
+        if c == open {
+            balance -= 1;
+            if balance == 0 { return Some(byte_idx); }
+        }
+        if i == 0 { break; }
+        i -= 1;
+    }
+    None
+}
+
+fn split_args_outside_parens(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut cur = String::new();
+    let mut balance = 0;
+    let mut in_quotes = false;
+    for c in s.chars() {
+        if c == '"' {
+            in_quotes = !in_quotes;
+            cur.push(c);
+        } else if in_quotes {
+            cur.push(c);
+        } else {
+            if c == '(' || c == '[' { balance += 1; }
+            else if c == ')' || c == ']' { balance -= 1; }
+
+            if balance == 0 && c.is_whitespace() {
+                if !cur.is_empty() { args.push(cur.clone()); cur.clear(); }
+            } else {
+                cur.push(c);
+            }
+        }
+    }
+    if !cur.is_empty() { args.push(cur); }
+
+    let mut merged = Vec::new();
+    let mut buffer = String::new();
+
+    for part in args {
+        let is_math_op = ["+", "-", "*", "/", "%", "==", "!=", ">", "<", ">=", "<=", "!"].contains(&part.as_str());
+        let prev_ends_op = buffer.ends_with(|c: char| "+-*/%=!><".contains(c));
+
+        if is_math_op || prev_ends_op {
+            buffer.push_str(&part);
+        } else {
+            if !buffer.is_empty() {
+                merged.push(buffer.clone());
+            }
+            buffer = part;
+        }
+    }
+    if !buffer.is_empty() { merged.push(buffer); }
+
+    merged
+}
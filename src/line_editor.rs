@@ -0,0 +1,285 @@
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+// --- LINE EDITOR ---
+//
+// A small readline replacement for the REPL: cursor movement, history and a
+// history file. The terminal is switched to raw mode with `stty` only while a
+// line is being edited, so `+?` input inside programs keeps working normally.
+// When stdin isn't a terminal (or `stty` is missing) we fall back to plain
+// `read_line`.
+
+const HISTORY_LIMIT: usize = 1000;
+
+pub enum ReadLine {
+    Line(String),
+    Interrupted,
+    Eof,
+}
+
+pub struct LineEditor {
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+}
+
+struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    fn enter() -> Option<RawMode> {
+        let saved = stty(&["-g"])?;
+        stty(&["-icanon", "-echo", "-isig", "-ixon", "min", "1"])?;
+        Some(RawMode { saved: saved.trim().to_string() })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        stty(&[self.saved.as_str()]);
+    }
+}
+
+fn stty(args: &[&str]) -> Option<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() { return None; }
+    Some(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+enum Key {
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    KillLine,
+    Interrupt,
+    // Ctrl-D: the end of input on an empty line, delete on any other.
+    Eof,
+    // Stdin is at its end or can't be read: no more keys will come.
+    Closed,
+    Ignored,
+}
+
+fn read_byte(stdin: &mut io::StdinLock) -> Option<u8> {
+    let mut buf = [0u8; 1];
+    loop {
+        match stdin.read(&mut buf) {
+            Ok(1) => return Some(buf[0]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            _ => return None,
+        }
+    }
+}
+
+fn read_key(stdin: &mut io::StdinLock) -> Key {
+    let Some(byte) = read_byte(stdin) else { return Key::Closed };
+    match byte {
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x01 => Key::Home,
+        0x05 => Key::End,
+        0x02 => Key::Left,
+        0x06 => Key::Right,
+        0x10 => Key::Up,
+        0x0e => Key::Down,
+        0x15 => Key::KillLine,
+        0x03 => Key::Interrupt,
+        0x04 => Key::Eof,
+        0x1b => match read_byte(stdin) {
+            Some(b'[') | Some(b'O') => match read_byte(stdin) {
+                Some(b'A') => Key::Up,
+                Some(b'B') => Key::Down,
+                Some(b'C') => Key::Right,
+                Some(b'D') => Key::Left,
+                Some(b'H') => Key::Home,
+                Some(b'F') => Key::End,
+                Some(d @ b'0'..=b'9') => {
+                    // `ESC [ n ~` style keys: 1/7 home, 4/8 end, 3 delete.
+                    let mut code = vec![d];
+                    while let Some(b) = read_byte(stdin) {
+                        if b == b'~' { break; }
+                        code.push(b);
+                    }
+                    match code.as_slice() {
+                        b"1" | b"7" => Key::Home,
+                        b"4" | b"8" => Key::End,
+                        b"3" => Key::Delete,
+                        _ => Key::Ignored,
+                    }
+                }
+                _ => Key::Ignored,
+            },
+            _ => Key::Ignored,
+        },
+        b if b < 0x20 => Key::Ignored,
+        b => {
+            // Collect the rest of a UTF-8 sequence so Unicode names can be typed.
+            let len = match b {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![b];
+            while bytes.len() < len {
+                match read_byte(stdin) {
+                    Some(next) => bytes.push(next),
+                    None => break,
+                }
+            }
+            match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()) {
+                Some(c) => Key::Char(c),
+                None => Key::Ignored,
+            }
+        }
+    }
+}
+
+// East Asian wide characters take two terminal columns.
+fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115f | 0x2e80..=0xa4cf | 0xac00..=0xd7a3 | 0xf900..=0xfaff
+        | 0xfe30..=0xfe4f | 0xff00..=0xff60 | 0xffe0..=0xffe6 | 0x1f300..=0x1faff
+        | 0x20000..=0x3fffd => 2,
+        _ => 1,
+    }
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        let history_file = env::var_os("HOME")
+            .or_else(|| env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(".lazy_history"));
+        let history = history_file.as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().map(|l| l.to_string()).collect())
+            .unwrap_or_default();
+        Self { history, history_file }
+    }
+
+    pub fn read_line(&mut self, prompt: &str) -> ReadLine {
+        print!("{}", prompt);
+        io::stdout().flush().unwrap();
+
+        let raw = if io::stdin().is_terminal() { RawMode::enter() } else { None };
+        let result = match raw {
+            Some(_) => self.edit(prompt),
+            None => {
+                let mut input = String::new();
+                match io::stdin().read_line(&mut input) {
+                    Ok(0) | Err(_) => ReadLine::Eof,
+                    Ok(_) => ReadLine::Line(input.trim_end_matches(['\r', '\n']).to_string()),
+                }
+            }
+        };
+        drop(raw);
+
+        if let ReadLine::Line(line) = &result {
+            self.add_history(line);
+        }
+        result
+    }
+
+    fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map(|l| l.as_str()) == Some(line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        if self.history.len() > HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+        if let Some(path) = &self.history_file {
+            let _ = fs::write(path, self.history.join("\n") + "\n");
+        }
+    }
+
+    fn edit(&mut self, prompt: &str) -> ReadLine {
+        let mut stdin = io::stdin().lock();
+        let mut line: Vec<char> = Vec::new();
+        let mut cursor = 0;
+        let mut history_idx = self.history.len();
+        let mut draft: Vec<char> = Vec::new();
+
+        loop {
+            match read_key(&mut stdin) {
+                Key::Char(c) => {
+                    line.insert(cursor, c);
+                    cursor += 1;
+                }
+                Key::Enter => {
+                    println!();
+                    return ReadLine::Line(line.into_iter().collect());
+                }
+                Key::Backspace if cursor > 0 => {
+                    cursor -= 1;
+                    line.remove(cursor);
+                }
+                Key::Delete | Key::Eof if cursor < line.len() => {
+                    line.remove(cursor);
+                }
+                Key::Left if cursor > 0 => cursor -= 1,
+                Key::Right if cursor < line.len() => cursor += 1,
+                Key::Home => cursor = 0,
+                Key::End => cursor = line.len(),
+                Key::Up if history_idx > 0 => {
+                    if history_idx == self.history.len() { draft = line.clone(); }
+                    history_idx -= 1;
+                    line = self.history[history_idx].chars().collect();
+                    cursor = line.len();
+                }
+                Key::Down if history_idx < self.history.len() => {
+                    history_idx += 1;
+                    line = if history_idx == self.history.len() {
+                        draft.clone()
+                    } else {
+                        self.history[history_idx].chars().collect()
+                    };
+                    cursor = line.len();
+                }
+                Key::KillLine => {
+                    line.drain(..cursor);
+                    cursor = 0;
+                }
+                Key::Interrupt => {
+                    println!("^C");
+                    return ReadLine::Interrupted;
+                }
+                Key::Eof if line.is_empty() => {
+                    println!();
+                    return ReadLine::Eof;
+                }
+                // Whatever was typed can't be finished, so it isn't run.
+                Key::Closed => {
+                    println!();
+                    return ReadLine::Eof;
+                }
+                _ => continue,
+            }
+            redraw(prompt, &line, cursor);
+        }
+    }
+}
+
+fn redraw(prompt: &str, line: &[char], cursor: usize) {
+    let text: String = line.iter().collect();
+    let back: usize = line[cursor..].iter().map(|c| char_width(*c)).sum();
+    print!("\r{}{}\x1b[K", prompt, text);
+    if back > 0 {
+        print!("\x1b[{}D", back);
+    }
+    io::stdout().flush().unwrap();
+}
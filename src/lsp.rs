@@ -0,0 +1,467 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::check::{check_source, Severity};
+use crate::json::Json;
+use crate::pattern::{self, Vars};
+use crate::{parse_program, Error, Statement, Stmt, BUILTINS};
+
+// --- LANGUAGE SERVER ---
+//
+// `lazy lsp` speaks the Language Server Protocol over stdin/stdout so editors
+// get diagnostics, hover docs for built-in symbols, go-to-definition,
+// document symbols and completion. Documents are always synced in full and
+// re-parsed on every request; Lazy files are small.
+
+const SYMBOL_CHARS: &str = "#$~!?=^<>+-&|";
+
+/// Answers the editor on stdin/stdout until it sends `exit`. Fails if it
+/// exits without asking to `shutdown` first, or goes away without exiting.
+pub fn serve(search_path: Vec<PathBuf>) -> Result<(), Error> {
+    let mut server = Server { documents: HashMap::new(), search_path, shutdown: false, exited: false };
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    while !server.exited {
+        let Some(body) = read_message(&mut input) else {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the client went away without `exit`").into());
+        };
+        match Json::parse(&body) {
+            Ok(message) => server.handle(&message),
+            Err(e) => send(&Json::object(vec![
+                ("jsonrpc", Json::str("2.0")),
+                ("id", Json::Null),
+                ("error", Json::object(vec![("code", Json::Number(-32700.0)), ("message", Json::str(&e))])),
+            ])),
+        }
+    }
+    if !server.shutdown {
+        return Err(io::Error::other("the client sent `exit` before `shutdown`").into());
+    }
+    Ok(())
+}
+
+fn read_message(input: &mut impl BufRead) -> Option<String> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).ok()? == 0 { return None; }
+        let header = header.trim_end();
+        if header.is_empty() { break; }
+        if let Some((name, value)) = header.split_once(':')
+            && name.eq_ignore_ascii_case("Content-Length") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0; length?];
+    input.read_exact(&mut body).ok()?;
+    String::from_utf8(body).ok()
+}
+
+fn send(message: &Json) {
+    let body = message.to_string();
+    let mut out = io::stdout().lock();
+    let _ = write!(out, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let _ = out.flush();
+}
+
+fn notify(method: &str, params: Json) {
+    send(&Json::object(vec![("jsonrpc", Json::str("2.0")), ("method", Json::str(method)), ("params", params)]));
+}
+
+struct Server {
+    documents: HashMap<String, String>,
+    search_path: Vec<PathBuf>,
+    shutdown: bool,
+    exited: bool,
+}
+
+impl Server {
+    fn handle(&mut self, message: &Json) {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params").unwrap_or(&Json::Null);
+        let uri = params.at(&["textDocument", "uri"]).and_then(Json::as_str).unwrap_or("").to_string();
+
+        let result = match method {
+            "initialize" => Some(Json::object(vec![
+                ("capabilities", Json::object(vec![
+                    ("textDocumentSync", Json::Number(1.0)),
+                    ("hoverProvider", Json::Bool(true)),
+                    ("definitionProvider", Json::Bool(true)),
+                    ("documentSymbolProvider", Json::Bool(true)),
+                    ("completionProvider", Json::object(vec![])),
+                ])),
+                ("serverInfo", Json::object(vec![
+                    ("name", Json::str("lazy")),
+                    ("version", Json::str(env!("CARGO_PKG_VERSION"))),
+                ])),
+            ])),
+            "shutdown" => {
+                self.shutdown = true;
+                Some(Json::Null)
+            }
+            "exit" => {
+                self.exited = true;
+                None
+            }
+            "textDocument/didOpen" => {
+                let text = params.at(&["textDocument", "text"]).and_then(Json::as_str).unwrap_or("");
+                self.documents.insert(uri.clone(), text.to_string());
+                self.publish_diagnostics(&uri);
+                None
+            }
+            "textDocument/didChange" => {
+                // Full sync: the last change holds the whole document.
+                if let Some(Json::Array(changes)) = params.get("contentChanges")
+                    && let Some(text) = changes.last().and_then(|c| c.get("text")).and_then(Json::as_str) {
+                    self.documents.insert(uri.clone(), text.to_string());
+                    self.publish_diagnostics(&uri);
+                }
+                None
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                notify("textDocument/publishDiagnostics", Json::object(vec![
+                    ("uri", Json::str(&uri)),
+                    ("diagnostics", Json::Array(Vec::new())),
+                ]));
+                None
+            }
+            "textDocument/hover" => Some(self.hover(&uri, position(params))),
+            "textDocument/definition" => Some(self.definition(&uri, position(params))),
+            "textDocument/documentSymbol" => Some(self.document_symbols(&uri)),
+            "textDocument/completion" => Some(self.completion(&uri, position(params))),
+            _ => None,
+        };
+
+        // Notifications have no id and never get a reply.
+        let Some(id) = message.get("id") else { return };
+        let reply = match result {
+            Some(result) => ("result", result),
+            None => ("error", Json::object(vec![
+                ("code", Json::Number(-32601.0)),
+                ("message", Json::str(&format!("unknown method {}", method))),
+            ])),
+        };
+        send(&Json::object(vec![("jsonrpc", Json::str("2.0")), ("id", id.clone()), reply]));
+    }
+
+    fn text(&self, uri: &str) -> &str {
+        self.documents.get(uri).map(|s| s.as_str()).unwrap_or("")
+    }
+
+    fn publish_diagnostics(&self, uri: &str) {
+        let text = self.text(uri);
+        let diagnostics = check_source(text).into_iter().map(|diag| {
+            let line = text.lines().nth(diag.line.saturating_sub(1)).unwrap_or("");
+            let indent = line.len() - line.trim_start().len();
+            Json::object(vec![
+                ("range", range((diag.line.saturating_sub(1), utf16_len(&line[..indent])),
+                    (diag.line.saturating_sub(1), utf16_len(line.trim_end())))),
+                ("severity", Json::Number(match diag.severity {
+                    Severity::Error => 1.0,
+                    Severity::Warning => 2.0,
+                })),
+                ("source", Json::str("lazy")),
+                ("message", Json::str(&diag.message)),
+            ])
+        }).collect();
+        notify("textDocument/publishDiagnostics", Json::object(vec![
+            ("uri", Json::str(uri)),
+            ("diagnostics", Json::Array(diagnostics)),
+        ]));
+    }
+
+    fn hover(&self, uri: &str, (line, col): (usize, usize)) -> Json {
+        let text = self.text(uri);
+        let Some(source) = text.lines().nth(line) else { return Json::Null };
+        let chars: Vec<char> = source.chars().collect();
+        let col = char_index(source, col);
+
+        let contents = if let Some(builtin) = builtin_at(&chars, col) {
+            format!("```lazy\n{}\n```\n{}", builtin.usage, builtin.help)
+        } else if let Some(name) = word_at(&chars, col) {
+            let (stmts, _) = parse_program(text);
+            match find_function(&stmts, &name) {
+                Some((params, _)) => format!("```lazy\n{}({})\n```", name, pattern::show_params(params)),
+                None => return Json::Null,
+            }
+        } else {
+            return Json::Null;
+        };
+        Json::object(vec![("contents", Json::object(vec![
+            ("kind", Json::str("markdown")),
+            ("value", Json::str(&contents)),
+        ]))])
+    }
+
+    fn definition(&self, uri: &str, (line, col): (usize, usize)) -> Json {
+        let text = self.text(uri);
+        let Some(source) = text.lines().nth(line) else { return Json::Null };
+        let chars: Vec<char> = source.chars().collect();
+        let Some(name) = word_at(&chars, char_index(source, col)) else { return Json::Null };
+
+        let (stmts, _) = parse_program(text);
+        if let Some((_, def_line)) = find_function(&stmts, &name) {
+            return location(uri, text, def_line);
+        }
+
+        // Imported functions live in another file.
+        for (path, names) in imports(&stmts) {
+            let stem = Path::new(&path).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+            let target = match name.split_once('.') {
+                Some((ns, target)) if ns == stem => target.to_string(),
+                _ if names.iter().any(|(_, bind_as)| *bind_as == name) => name.clone(),
+                _ => continue,
+            };
+            let Some(file) = self.resolve(uri, &path) else { continue };
+            let Ok(module_text) = fs::read_to_string(&file) else { continue };
+            let (module_stmts, _) = parse_program(&module_text);
+            if let Some((_, def_line)) = find_function(&module_stmts, &target) {
+                return location(&path_to_uri(&file), &module_text, def_line);
+            }
+        }
+        Json::Null
+    }
+
+    fn resolve(&self, uri: &str, path: &str) -> Option<PathBuf> {
+        let base = uri_to_path(uri).and_then(|p| p.parent().map(Path::to_path_buf)).unwrap_or_default();
+        std::iter::once(base)
+            .chain(self.search_path.iter().cloned())
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.is_file())
+    }
+
+    fn document_symbols(&self, uri: &str) -> Json {
+        let text = self.text(uri);
+        let (stmts, _) = parse_program(text);
+        let mut symbols = Vec::new();
+        collect_symbols(&stmts, text, &mut symbols);
+        Json::Array(symbols)
+    }
+
+    fn completion(&self, uri: &str, (line, _): (usize, usize)) -> Json {
+        let (stmts, _) = parse_program(self.text(uri));
+        let mut names: Vec<(String, Option<Vec<Vars>>)> = Vec::new();
+        visible_names(&stmts, line + 1, &mut names);
+        for (path, _) in imports(&stmts) {
+            if let Some(file) = self.resolve(uri, &path)
+                && let Ok(module_text) = fs::read_to_string(&file) {
+                let stem = file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                let (module_stmts, _) = parse_program(&module_text);
+                let mut module_names = Vec::new();
+                visible_names(&module_stmts, 0, &mut module_names);
+                names.extend(module_names.into_iter().map(|(name, params)| (format!("{}.{}", stem, name), params)));
+            }
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        let items = names.into_iter()
+            .filter(|(name, _)| seen.insert(name.clone()))
+            .map(|(name, params)| match params {
+                Some(params) => Json::object(vec![
+                    ("label", Json::str(&name)),
+                    ("kind", Json::Number(3.0)),
+                    ("detail", Json::str(&format!("{}({})", name, pattern::show_params(&params)))),
+                ]),
+                None => Json::object(vec![("label", Json::str(&name)), ("kind", Json::Number(6.0))]),
+            })
+            .collect();
+        Json::Array(items)
+    }
+}
+
+fn position(params: &Json) -> (usize, usize) {
+    let get = |key| params.at(&["position", key]).and_then(Json::as_f64).unwrap_or(0.0) as usize;
+    (get("line"), get("character"))
+}
+
+// Positions are 0-based `(line, character)` pairs.
+fn range(start: (usize, usize), end: (usize, usize)) -> Json {
+    let pos = |(line, character): (usize, usize)| Json::object(vec![
+        ("line", Json::Number(line as f64)),
+        ("character", Json::Number(character as f64)),
+    ]);
+    Json::object(vec![("start", pos(start)), ("end", pos(end))])
+}
+
+// The range of a function's name on its definition line.
+fn name_range(text: &str, line: usize, name: &str) -> Json {
+    let source = text.lines().nth(line.saturating_sub(1)).unwrap_or("");
+    let indent = source.len() - source.trim_start().len();
+    let start = utf16_len(&source[..indent]);
+    range((line.saturating_sub(1), start), (line.saturating_sub(1), start + utf16_len(name)))
+}
+
+fn location(uri: &str, text: &str, line: usize) -> Json {
+    let source = text.lines().nth(line.saturating_sub(1)).unwrap_or("");
+    let name: String = source.trim_start().chars().take_while(|c| is_word_char(*c)).collect();
+    Json::object(vec![("uri", Json::str(uri)), ("range", name_range(text, line, &name))])
+}
+
+// LSP counts columns in UTF-16 code units.
+fn utf16_len(s: &str) -> usize {
+    s.chars().map(char::len_utf16).sum()
+}
+
+fn char_index(line: &str, utf16_col: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in line.chars().enumerate() {
+        if units >= utf16_col { return i; }
+        units += c.len_utf16();
+    }
+    line.chars().count()
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn word_at(chars: &[char], col: usize) -> Option<String> {
+    let mut start = col.min(chars.len());
+    while start > 0 && is_word_char(chars[start - 1]) { start -= 1; }
+    let mut end = col.min(chars.len());
+    while end < chars.len() && is_word_char(chars[end]) { end += 1; }
+    let word: String = chars[start..end].iter().collect();
+    (!word.is_empty() && !word.starts_with(|c: char| c.is_ascii_digit())).then_some(word)
+}
+
+// The built-in symbol under the cursor: the longest one that covers it and is
+// followed by `(`, so `x = #(xs)` finds `#` but `a >= b` finds nothing.
+fn builtin_at(chars: &[char], col: usize) -> Option<&'static crate::Builtin> {
+    let is_symbol = |c: char| SYMBOL_CHARS.contains(c) || c == 'v';
+    let mut start = col.min(chars.len());
+    while start > 0 && is_symbol(chars[start - 1]) { start -= 1; }
+    let mut end = col.min(chars.len());
+    while end < chars.len() && is_symbol(chars[end]) { end += 1; }
+    // `nav(x)` is a call to `nav`, not to `v`.
+    if chars.get(end) != Some(&'(') || (start > 0 && is_word_char(chars[start - 1])) { return None; }
+    let run: String = chars[start..end].iter().collect();
+    (0..run.len()).map(|i| &run[i..]).find_map(|symbol| {
+        let covers = start + run[..run.len() - symbol.len()].chars().count() <= col;
+        BUILTINS.iter().find(|b| b.symbol == symbol && covers)
+    })
+}
+
+fn find_function<'a>(stmts: &'a [Stmt], name: &str) -> Option<(&'a [Vars], usize)> {
+    stmts.iter().find_map(|stmt| match &stmt.kind {
+        Statement::FunctionDef(fn_name, params, _) | Statement::QuickFunctionDef(fn_name, params, _)
+            if fn_name == name => Some((params.as_slice(), stmt.line)),
+        _ => stmt.blocks().find_map(|block| find_function(block, name)),
+    })
+}
+
+fn imports(stmts: &[Stmt]) -> Vec<(String, Vec<(String, String)>)> {
+    let mut found = Vec::new();
+    for stmt in stmts {
+        if let Statement::Import(path, names) = &stmt.kind {
+            found.push((path.clone(), names.clone()));
+        }
+        for block in stmt.blocks() {
+            found.extend(imports(block));
+        }
+    }
+    found
+}
+
+fn collect_symbols(stmts: &[Stmt], text: &str, symbols: &mut Vec<Json>) {
+    for stmt in stmts {
+        let (name, params) = match &stmt.kind {
+            Statement::FunctionDef(name, params, _) | Statement::QuickFunctionDef(name, params, _) => (name, params),
+            _ => {
+                for block in stmt.blocks() {
+                    collect_symbols(block, text, symbols);
+                }
+                continue;
+            }
+        };
+        let mut children = Vec::new();
+        if let Statement::FunctionDef(_, _, body) = &stmt.kind {
+            collect_symbols(body, text, &mut children);
+        }
+        let last_line = text.lines().nth(stmt.end_line.saturating_sub(1)).unwrap_or("");
+        symbols.push(Json::object(vec![
+            ("name", Json::str(name)),
+            ("detail", Json::str(&format!("({})", pattern::show_params(params)))),
+            ("kind", Json::Number(12.0)),
+            ("range", range((stmt.line.saturating_sub(1), 0), (stmt.end_line.saturating_sub(1), utf16_len(last_line)))),
+            ("selectionRange", name_range(text, stmt.line, name)),
+            ("children", Json::Array(children)),
+        ]));
+    }
+}
+
+// Names visible on `line` (1-based): everything assigned outside functions,
+// plus the parameters and locals of each function the line is inside.
+// Functions come with their parameters.
+fn visible_names(stmts: &[Stmt], line: usize, names: &mut Vec<(String, Option<Vec<Vars>>)>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            Statement::Assign(vars, _) | Statement::For(vars, _, _) => {
+                names.extend(vars.names().into_iter().map(|n| (n.to_string(), None)));
+            }
+            Statement::AugAssign(name, _, _) | Statement::IncDec(name, _) => names.push((name.to_string(), None)),
+            Statement::Input(vars, _, _) => names.extend(vars.iter().map(|v| (v.clone(), None))),
+            Statement::Import(_, bound) => names.extend(bound.iter().map(|(_, bind_as)| (bind_as.clone(), None))),
+            _ => {}
+        }
+        match &stmt.kind {
+            Statement::FunctionDef(name, params, body) => {
+                names.push((name.clone(), Some(params.clone())));
+                if (stmt.line..=stmt.end_line).contains(&line) {
+                    names.extend(pattern::param_names(params).into_iter().map(|p| (p, None)));
+                    visible_names(body, line, names);
+                }
+            }
+            Statement::QuickFunctionDef(name, params, _) => {
+                names.push((name.clone(), Some(params.clone())));
+                if stmt.line == line {
+                    names.extend(pattern::param_names(params).into_iter().map(|p| (p, None)));
+                }
+            }
+            // What an arm's pattern binds is only there inside the arm.
+            Statement::Match(_, arms) => {
+                for arm in arms {
+                    if (arm.line..=arm.end_line).contains(&line) {
+                        names.extend(arm.pattern.names().into_iter().map(|n| (n.to_string(), None)));
+                    }
+                    visible_names(&arm.body, line, names);
+                }
+            }
+            _ => {
+                for block in stmt.blocks() {
+                    visible_names(block, line, names);
+                }
+            }
+        }
+    }
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::new();
+    let mut chars = path.bytes();
+    while let Some(b) = chars.next() {
+        if b == b'%' {
+            let hex: String = chars.by_ref().take(2).map(char::from).collect();
+            bytes.push(u8::from_str_radix(&hex, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = "file://".to_string();
+    for b in path.to_string_lossy().bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    uri
+}
@@ -0,0 +1,241 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::build::normalize;
+use crate::pattern;
+use crate::{parse_program, ChildMut, Expr, Interpreter, Name, ParseError, Statement, Stmt, Value};
+
+// --- MODULES ---
+//
+// `<- "shapes.lazy"` runs another file once and exposes everything it defines
+// at the top level as `shapes.name`. `<- "shapes.lazy" : area pi` additionally
+// binds `area` and `pi` directly.
+//
+// Lazy looks names up dynamically through the scope chain, so a module's
+// functions would normally see the importer's variables instead of their own.
+// To keep modules self-contained, every reference to a module-level name inside
+// the module is rewritten to its qualified form (`pi` -> `shapes.pi`) before
+// the module runs. The qualified names then live in the global scope.
+
+pub struct Module {
+    pub namespace: String,
+    pub names: Vec<String>,
+}
+
+impl Interpreter {
+    pub(crate) fn import(&mut self, path: &str, names: &[(String, String)]) {
+        let Some(file) = self.resolve_module(path) else {
+            self.fail(format!("cannot find module \"{}\"", path));
+            return;
+        };
+
+        if let Some(idx) = self.loading.iter().position(|p| *p == file) {
+            let chain: Vec<String> = self.loading[idx..].iter()
+                .chain(std::iter::once(&file))
+                .map(|p| display_path(p))
+                .collect();
+            self.fail(format!("import cycle: {}", chain.join(" -> ")));
+            return;
+        }
+
+        if !self.modules.contains_key(&file) && !self.load_module(&file) {
+            return;
+        }
+
+        let module = &self.modules[&file];
+        let namespace = module.namespace.clone();
+        if let Some((name, _)) = names.iter().find(|(name, _)| !module.names.contains(name)) {
+            self.fail(format!("module \"{}\" has no \"{}\"", path, name));
+            return;
+        }
+        let mut bindings = Vec::new();
+        for (name, bind_as) in names {
+            let qualified = format!("{}.{}", namespace, name);
+            let val = self.global(&qualified).cloned().unwrap_or(Value::Nothing);
+            bindings.push((bind_as.clone(), val));
+        }
+        for (bind_as, val) in bindings {
+            self.set_var(&bind_as, val);
+        }
+    }
+
+    fn load_module(&mut self, file: &Path) -> bool {
+        let read = match self.bundle.get(file) {
+            Some(code) => Ok(code.clone()),
+            None => fs::read_to_string(file),
+        };
+        let code = match read {
+            Ok(code) => code,
+            Err(e) => {
+                self.fail(format!("cannot read module \"{}\": {}", display_path(file), e));
+                return false;
+            }
+        };
+        let namespace = file.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+        if let Some((other, _)) = self.modules.iter().find(|(_, m)| m.namespace == namespace) {
+            let message = format!("module \"{}\" clashes with \"{}\" (both are named \"{}\")",
+                display_path(file), display_path(other), namespace);
+            self.fail(message);
+            return false;
+        }
+
+        let (mut stmts, errors) = parse_program(&code);
+        if !errors.is_empty() {
+            let name = display_path(file);
+            self.syntax_errors.extend(errors.into_iter().map(|err| ParseError {
+                line: err.line,
+                message: format!("{} (in module \"{}\")", err.message, name),
+            }));
+            self.fail(format!("module \"{}\" has syntax errors", name));
+            return false;
+        }
+        let globals = module_globals(&stmts);
+        qualify_block(&mut stmts, &namespace, &globals, &HashSet::new());
+
+        // Module code always runs at the top level, even when the import sits inside a function.
+        let saved = std::mem::take(&mut self.frames);
+        self.enter_module(file, &code);
+        if let Some(profiler) = &mut self.profiler { profiler.enter_module(file); }
+        self.loading.push(file.to_path_buf());
+        self.run_program(&stmts);
+        self.loading.pop();
+        if let Some(profiler) = &mut self.profiler { profiler.leave_module(); }
+        self.leave_module();
+        self.frames = saved;
+
+        let mut names: Vec<String> = globals.into_iter().collect();
+        names.sort();
+        self.modules.insert(file.to_path_buf(), Module { namespace, names });
+        true
+    }
+
+    // Relative to the importing file first, then each directory of the search
+    // path. A bundled program's imports were found that way by `lazy build`.
+    fn resolve_module(&self, path: &str) -> Option<PathBuf> {
+        let base = self.loading.last()
+            .and_then(|f| f.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        if !self.bundle.is_empty() {
+            return [base.join(path), PathBuf::from(path)].into_iter()
+                .map(|candidate| normalize(&candidate))
+                .find(|candidate| self.bundle.contains_key(candidate));
+        }
+        std::iter::once(base)
+            .chain(self.search_path.iter().cloned())
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.is_file())
+            .and_then(|candidate| candidate.canonicalize().ok())
+    }
+}
+
+fn display_path(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+// Everything a module defines outside of function bodies. Blocks don't open
+// scopes in Lazy, so names assigned inside a top-level `?` or `@` count too.
+fn module_globals(stmts: &[Stmt]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    for stmt in stmts {
+        match &stmt.kind {
+            Statement::Assign(vars, _) | Statement::For(vars, _, _) => names.extend(vars.names().into_iter().map(String::from)),
+            Statement::AugAssign(name, _, _) | Statement::IncDec(name, _) => {
+                names.insert(name.to_string());
+            }
+            // Its body is the function's own.
+            Statement::FunctionDef(name, _, _) | Statement::QuickFunctionDef(name, _, _) => {
+                names.insert(name.clone());
+                continue;
+            }
+            Statement::Input(vars, _, _) => names.extend(vars.iter().cloned()),
+            Statement::Import(_, bindings) => names.extend(bindings.iter().map(|(_, b)| b.clone())),
+            _ => {}
+        }
+        // What an arm binds is gone after it, so only its body counts.
+        for block in stmt.blocks() {
+            names.extend(module_globals(block));
+        }
+    }
+    names
+}
+
+fn qualify_name(name: &mut String, ns: &str, globals: &HashSet<String>, params: &HashSet<String>) {
+    if globals.contains(name.as_str()) && !params.contains(name.as_str()) {
+        *name = format!("{}.{}", ns, name);
+    }
+}
+
+fn qualify_var(name: &mut Name, ns: &str, globals: &HashSet<String>, params: &HashSet<String>) {
+    if globals.contains(name.as_str()) && !params.contains(name.as_str()) {
+        name.rename(format!("{}.{}", ns, name));
+    }
+}
+
+fn qualify_block(stmts: &mut [Stmt], ns: &str, globals: &HashSet<String>, params: &HashSet<String>) {
+    for stmt in stmts {
+        match &mut stmt.kind {
+            Statement::Assign(vars, _) | Statement::For(vars, _, _) => {
+                for name in vars.names_mut() {
+                    qualify_var(name, ns, globals, params);
+                }
+            }
+            Statement::AugAssign(name, _, _) | Statement::IncDec(name, _) => qualify_var(name, ns, globals, params),
+            Statement::FunctionCall(name, _, _) => qualify_name(name, ns, globals, params),
+            Statement::Input(vars, _, _) => {
+                for var in vars {
+                    qualify_name(var, ns, globals, params);
+                }
+            }
+            Statement::Import(_, bindings) => {
+                for (_, bind_as) in bindings {
+                    qualify_name(bind_as, ns, globals, params);
+                }
+            }
+            // The names an arm or a function binds are its own.
+            Statement::Match(subject, arms) => {
+                qualify_expr(subject, ns, globals, params);
+                for arm in arms {
+                    let inner: HashSet<String> = params.iter().cloned()
+                        .chain(arm.pattern.names().into_iter().map(String::from))
+                        .collect();
+                    if let Some(guard) = &mut arm.guard {
+                        qualify_expr(guard, ns, globals, &inner);
+                    }
+                    qualify_block(&mut arm.body, ns, globals, &inner);
+                }
+                continue;
+            }
+            Statement::FunctionDef(name, fn_params, body) => {
+                qualify_name(name, ns, globals, params);
+                let inner: HashSet<String> = params.iter().cloned().chain(pattern::param_names(fn_params)).collect();
+                qualify_block(body, ns, globals, &inner);
+                continue;
+            }
+            Statement::QuickFunctionDef(name, fn_params, expr) => {
+                qualify_name(name, ns, globals, params);
+                let inner: HashSet<String> = params.iter().cloned().chain(pattern::param_names(fn_params)).collect();
+                qualify_expr(expr, ns, globals, &inner);
+                continue;
+            }
+            _ => {}
+        }
+        for child in stmt.children_mut() {
+            match child {
+                ChildMut::Expr(expr, _) => qualify_expr(expr, ns, globals, params),
+                ChildMut::Block(block) => qualify_block(block, ns, globals, params),
+            }
+        }
+    }
+}
+
+fn qualify_expr(expr: &mut Expr, ns: &str, globals: &HashSet<String>, params: &HashSet<String>) {
+    match expr {
+        Expr::Variable(name) => qualify_var(name, ns, globals, params),
+        Expr::FunctionCall(name, _, _) => qualify_name(name, ns, globals, params),
+        _ => {}
+    }
+    for child in expr.children_mut() {
+        qualify_expr(child, ns, globals, params);
+    }
+}
@@ -0,0 +1,105 @@
+use std::mem;
+
+use crate::{binary, BinOp, ChildMut, Expr, Statement, Stmt, Value};
+
+// --- OPTIMIZER ---
+//
+// Runs over a program before it starts, unless `--no-optimize` says not to.
+// Only rewrites that can't change what a program does:
+// - arithmetic, comparisons and `+` on literals are worked out once
+//   (`60 * 60 * 24` becomes `86400`), through the same `binary` the
+//   interpreter uses, so the results are identical
+// - a `?` chain whose conditions are literals keeps only the branch that
+//   would run; blocks are not scopes, so its statements can take its place
+// - statements after a `->` in the same block are dropped
+
+/// ```
+/// use lazy::{optimize::optimize, parse, pattern::Vars, Expr, Statement};
+///
+/// let program = optimize(parse("day = 60 * 60 * 24\n? no {\n  \"never\"\n}").unwrap());
+/// assert_eq!(program.len(), 1);
+/// assert_eq!(program[0].kind, Statement::Assign(Vars::One("day".into()), Expr::Number(86400.0)));
+/// ```
+pub fn optimize(stmts: Vec<Stmt>) -> Vec<Stmt> {
+    let mut out = Vec::with_capacity(stmts.len());
+    for stmt in stmts {
+        optimize_stmt(stmt, &mut out);
+        if matches!(out.last(), Some(Stmt { kind: Statement::Return(_), .. })) { break; }
+    }
+    out
+}
+
+// Pushes what `stmt` becomes: itself, nothing, or the statements of the one
+// branch that runs.
+fn optimize_stmt(stmt: Stmt, out: &mut Vec<Stmt>) {
+    let (cond, then_block, else_ifs, else_block) = match stmt.kind {
+        Statement::If(cond, then_block, else_ifs, else_block) => (cond, then_block, else_ifs, else_block),
+        kind => {
+            let mut stmt = Stmt { kind, ..stmt };
+            for child in stmt.children_mut() {
+                match child {
+                    ChildMut::Expr(expr, _) => *expr = fold(mem::replace(expr, Expr::Input)),
+                    ChildMut::Block(block) => *block = optimize(mem::take(block)),
+                }
+            }
+            out.push(stmt);
+            return;
+        }
+    };
+    let branches = std::iter::once((cond, then_block)).chain(else_ifs);
+    let mut kept: Vec<(Expr, Vec<Stmt>)> = Vec::new();
+    let mut else_block = Some(else_block);
+    for (cond, block) in branches {
+        let cond = fold(cond);
+        match constant(&cond) {
+            // This branch always runs when it is reached: it becomes the else.
+            Some(Value::Bool(true)) => {
+                else_block = Some(block);
+                break;
+            }
+            // Never runs.
+            Some(_) => {}
+            None => kept.push((cond, block)),
+        }
+    }
+    let else_block = optimize(else_block.unwrap_or_default());
+    if kept.is_empty() {
+        out.extend(else_block);
+        return;
+    }
+    let mut kept = kept.into_iter().map(|(cond, block)| (cond, optimize(block)));
+    let (cond, then_block) = kept.next().unwrap();
+    let kind = Statement::If(cond, then_block, kept.collect(), else_block);
+    out.push(Stmt { kind, ..stmt });
+}
+
+fn fold(mut expr: Expr) -> Expr {
+    for child in expr.children_mut() {
+        *child = fold(mem::replace(child, Expr::Input));
+    }
+    if let Expr::BinaryOp(left, op, right) = &expr
+        && let (Some(l), Some(r), Some(bin)) = (constant(left), constant(right), BinOp::from_symbol(op))
+        && let Some(folded) = literal(binary(&l, bin, &r)) {
+        return folded;
+    }
+    expr
+}
+
+fn constant(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Number(n) => Some(Value::Number(*n)),
+        Expr::Text(s) => Some(Value::from(s.as_str())),
+        Expr::Bool(b) => Some(Value::Bool(*b)),
+        _ => None,
+    }
+}
+
+// Nothing has no literal, so `"a" - 1` stays as it is.
+fn literal(val: Value) -> Option<Expr> {
+    match val {
+        Value::Number(n) => Some(Expr::Number(n)),
+        Value::Text(s) => Some(Expr::Text(s.to_string())),
+        Value::Bool(b) => Some(Expr::Bool(b)),
+        _ => None,
+    }
+}
@@ -0,0 +1,406 @@
+use std::fmt;
+
+use crate::{parse_block, parse_expr, Expr, Interpreter, Name, ParseError, SourceLine, Statement, Stmt, Value};
+
+// --- PATTERNS ---
+//
+// `?> value {` tries its arms in order and runs the first one whose pattern
+// matches the value and whose `? guard`, if it has one, is yes:
+//
+//     ?> point {
+//         [0 0] {
+//             "origin"
+//         }
+//         [x y] ? x == y {
+//             "diagonal at " + x
+//         }
+//         [x ...rest] {
+//             "starts at " + x
+//         }
+//         _ {
+//             "not a point"
+//         }
+//     }
+//
+// The names a pattern binds only exist while its arm runs: they shadow any
+// variable of the same name in the current scope, which gets its value back
+// after the arm. A value that no arm matches stops the program.
+//
+// `a b = pair`, `>> [name score] rows {` and a parameter written `[x y]`
+// unpack a list the same way without the checks: each name gets the item in
+// its place, or nothing past the end, and `...rest` the list of the items
+// left over. Anything that isn't a list unpacks like an empty one.
+
+const TYPES: [&str; 6] = ["number", "text", "bool", "nothing", "list", "function"];
+
+/// One arm of a `?>` match: `pattern ? guard {`, its body, and `}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arm {
+    pub line: usize,
+    pub end_line: usize,
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `_` matches anything.
+    Any,
+    /// `name` matches anything and binds it to `name`.
+    Bind(String),
+    /// `3`, `"text"` and `yes` match values equal to them, as `==` compares.
+    Number(f64),
+    Text(String),
+    Bool(bool),
+    /// `:number` matches values of that type, and `n:number` binds them.
+    Type(Option<String>, String),
+    /// `[a b]` matches a list with an item for each pattern. With a rest,
+    /// `[a b ...rest]`, it matches longer lists too, and the rest pattern gets
+    /// the list of the items after them; `...` alone is `..._`.
+    List(Vec<Pattern>, Option<Box<Pattern>>),
+}
+
+impl Pattern {
+    /// Parses the pattern of an arm.
+    ///
+    /// ```
+    /// use lazy::pattern::Pattern;
+    ///
+    /// let pattern = Pattern::parse("[first ...rest]").unwrap();
+    /// assert_eq!(pattern.names(), vec!["first", "rest"]);
+    /// assert!(Pattern::parse("n:string").is_err());
+    /// ```
+    pub fn parse(s: &str) -> Result<Pattern, String> {
+        let s = s.trim();
+        if s == "_" { return Ok(Pattern::Any); }
+        if let Some(content) = s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            let mut items = Vec::new();
+            let mut rest = None;
+            for item in crate::split_args_outside_parens(content) {
+                if rest.is_some() {
+                    return Err(format!("`{}` comes after the rest of the list in `{}`", item, s));
+                }
+                match item.strip_prefix("...") {
+                    Some("") => rest = Some(Box::new(Pattern::Any)),
+                    Some(pattern) => rest = Some(Box::new(Pattern::parse(pattern)?)),
+                    None => items.push(Pattern::parse(&item)?),
+                }
+            }
+            return Ok(Pattern::List(items, rest));
+        }
+        if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+            return Ok(Pattern::Text(s[1..s.len() - 1].to_string()));
+        }
+        if let Ok(n) = s.parse::<f64>() { return Ok(Pattern::Number(n)); }
+        if s == "yes" || s == "true" { return Ok(Pattern::Bool(true)); }
+        if s == "no" || s == "false" { return Ok(Pattern::Bool(false)); }
+        if let Some((name, type_name)) = s.split_once(':') {
+            if !TYPES.contains(&type_name) {
+                return Err(format!("`{}` is not a type, expected one of {}", type_name, TYPES.join(" ")));
+            }
+            let name = match name {
+                "" => None,
+                name if is_name(name) => Some(name.to_string()),
+                name => return Err(format!("`{}` can't be bound to a name", name)),
+            };
+            return Ok(Pattern::Type(name, type_name.to_string()));
+        }
+        if is_name(s) { Ok(Pattern::Bind(s.to_string())) } else { Err(format!("`{}` is not a pattern", s)) }
+    }
+
+    /// The names the pattern binds, in order.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Pattern::Bind(name) | Pattern::Type(Some(name), _) => vec![name],
+            Pattern::List(items, rest) => items.iter().chain(rest.as_deref()).flat_map(Pattern::names).collect(),
+            _ => vec![],
+        }
+    }
+
+    // Adds what the pattern binds to `binds` when `val` matches.
+    pub(crate) fn matches(&self, val: &Value, binds: &mut Vec<(String, Value)>) -> bool {
+        match (self, val) {
+            (Pattern::Any, _) => true,
+            (Pattern::Bind(name), _) => {
+                binds.push((name.clone(), val.clone()));
+                true
+            }
+            (Pattern::Number(n), Value::Number(m)) => n == m || (n - m).abs() < f64::EPSILON,
+            (Pattern::Text(s), Value::Text(t)) => s == t.as_str(),
+            (Pattern::Bool(b), Value::Bool(c)) => b == c,
+            (Pattern::Type(name, type_name), _) if val.type_name() == type_name => {
+                binds.extend(name.iter().map(|name| (name.clone(), val.clone())));
+                true
+            }
+            (Pattern::List(patterns, rest), Value::List(items)) => {
+                let fits = match rest {
+                    Some(_) => items.len() >= patterns.len(),
+                    None => items.len() == patterns.len(),
+                };
+                fits && patterns.iter().zip(items.iter()).all(|(p, item)| p.matches(item, binds))
+                    && rest.as_ref().is_none_or(|rest| rest.matches(&Value::from(items[patterns.len()..].to_vec()), binds))
+            }
+            _ => false,
+        }
+    }
+
+    // Whether this pattern matches every value `other` matches, so an arm
+    // with `other` after an arm with this one never runs.
+    pub(crate) fn covers(&self, other: &Pattern) -> bool {
+        match (self, other) {
+            (Pattern::Any | Pattern::Bind(_), _) => true,
+            (Pattern::Type(_, t), Pattern::Type(_, u)) => t == u,
+            (Pattern::Type(_, t), Pattern::Number(_)) => t == "number",
+            (Pattern::Type(_, t), Pattern::Text(_)) => t == "text",
+            (Pattern::Type(_, t), Pattern::Bool(_)) => t == "bool",
+            (Pattern::Type(_, t), Pattern::List(..)) => t == "list",
+            (Pattern::List(xs, None), Pattern::List(ys, None)) => {
+                xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| x.covers(y))
+            }
+            (Pattern::List(xs, Some(rest)), Pattern::List(ys, other_rest)) => {
+                // The rest list could be anything when `other` has a rest.
+                let rest_covered = match other_rest {
+                    Some(other_rest) => xs.len() == ys.len() && rest.covers(other_rest),
+                    None => rest.covers(&Pattern::Type(None, "list".to_string())),
+                };
+                xs.len() <= ys.len() && xs.iter().zip(ys).all(|(x, y)| x.covers(y)) && rest_covered
+            }
+            (x, y) => x == y,
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pattern::Any => write!(f, "_"),
+            Pattern::Bind(name) => write!(f, "{}", name),
+            Pattern::Number(n) => write!(f, "{}", n),
+            Pattern::Text(s) => write!(f, "\"{}\"", s),
+            Pattern::Bool(b) => write!(f, "{}", if *b { "yes" } else { "no" }),
+            Pattern::Type(name, type_name) => write!(f, "{}:{}", name.as_deref().unwrap_or(""), type_name),
+            Pattern::List(items, rest) => {
+                let mut parts: Vec<String> = items.iter().map(|p| p.to_string()).collect();
+                match rest.as_deref() {
+                    Some(Pattern::Any) => parts.push("...".to_string()),
+                    Some(rest) => parts.push(format!("...{}", rest)),
+                    None => {}
+                }
+                write!(f, "[{}]", parts.join(" "))
+            }
+        }
+    }
+}
+
+/// What `=` and `>>` give a value to: one variable, or several that a list is
+/// unpacked into.
+///
+/// ```
+/// use lazy::pattern::Vars;
+///
+/// let vars = Vars::parse("first ...rest").unwrap();
+/// assert_eq!(vars, Vars::Unpack(vec!["first".into()], Some("rest".into())));
+/// assert_eq!(vars.to_string(), "[first ...rest]");
+/// assert_eq!(Vars::parse("[a b]").unwrap().names(), vec!["a", "b"]);
+/// assert!(Vars::parse("a 2").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Vars {
+    One(Name),
+    Unpack(Vec<Name>, Option<Name>),
+}
+
+impl Vars {
+    /// `name`, or names separated by spaces, optionally in brackets.
+    pub fn parse(s: &str) -> Result<Vars, String> {
+        let s = s.trim();
+        let inner = match s.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            Some(inner) => inner,
+            None if s.contains(char::is_whitespace) || s.starts_with("...") => s,
+            None => return Ok(Vars::One(Name::from(s))),
+        };
+        let mut names = Vec::new();
+        let mut rest = None;
+        for item in inner.split_whitespace() {
+            if rest.is_some() {
+                return Err(format!("`{}` comes after the rest of the list in `{}`", item, s));
+            }
+            let name = item.strip_prefix("...").unwrap_or(item);
+            if !is_name(name) {
+                return Err(format!("can't unpack into `{}` in `{}`, it isn't a name", item, s));
+            }
+            if item.starts_with("...") { rest = Some(Name::from(name)) } else { names.push(Name::from(name)) }
+        }
+        if names.is_empty() && rest.is_none() {
+            return Err(format!("no names to unpack into in `{}`", s));
+        }
+        Ok(Vars::Unpack(names, rest))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Vars::One(name) => vec![name],
+            Vars::Unpack(names, rest) => names.iter().chain(rest).map(Name::as_str).collect(),
+        }
+    }
+
+    pub(crate) fn names_mut(&mut self) -> Vec<&mut Name> {
+        match self {
+            Vars::One(name) => vec![name],
+            Vars::Unpack(names, rest) => names.iter_mut().chain(rest).collect(),
+        }
+    }
+
+    // Each name with its part of `val`.
+    pub(crate) fn unpack(&self, val: Value) -> Vec<(&Name, Value)> {
+        let (names, rest) = match self {
+            Vars::One(name) => return vec![(name, val)],
+            Vars::Unpack(names, rest) => (names, rest),
+        };
+        let items = match &val {
+            Value::List(items) => items.as_slice(),
+            _ => &[],
+        };
+        let mut out: Vec<(&Name, Value)> = names.iter()
+            .enumerate()
+            .map(|(i, name)| (name, items.get(i).cloned().unwrap_or(Value::Nothing)))
+            .collect();
+        if let Some(rest) = rest {
+            out.push((rest, Value::from(items.get(names.len()..).unwrap_or(&[]).to_vec())));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Vars {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Vars::One(name) => write!(f, "{}", name),
+            Vars::Unpack(names, rest) => {
+                let rest = rest.iter().map(|rest| format!("...{}", rest));
+                write!(f, "[{}]", names.iter().map(Name::to_string).chain(rest).collect::<Vec<_>>().join(" "))
+            }
+        }
+    }
+}
+
+/// Parameters as they are written, `a [b c]`.
+pub fn show_params(params: &[Vars]) -> String {
+    params.iter().map(Vars::to_string).collect::<Vec<_>>().join(" ")
+}
+
+// The names parameters give values to, in the order a call binds them.
+pub(crate) fn param_names(params: &[Vars]) -> Vec<String> {
+    params.iter().flat_map(Vars::names).map(String::from).collect()
+}
+
+fn is_name(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// The arms of a `?>` up to its closing `}`, which it consumes, returning the
+// line it was on. Comments between arms go at the start of the next arm.
+pub(crate) fn parse_arms(lines: &[SourceLine], current: &mut usize, errors: &mut Vec<ParseError>) -> (Vec<Arm>, usize) {
+    let mut arms: Vec<Arm> = Vec::new();
+    let mut comments = Vec::new();
+    while *current < lines.len() {
+        let (line_no, line) = lines[*current];
+        *current += 1;
+        if line == "}" {
+            if let Some(arm) = arms.last_mut() { arm.body.append(&mut comments); }
+            return (arms, line_no);
+        }
+        if let Some(text) = line.strip_prefix("//") {
+            comments.push(Stmt { line: line_no, end_line: line_no, kind: Statement::Comment(text.to_string()) });
+            continue;
+        }
+        let Some(header) = line.strip_suffix('{') else {
+            errors.push(ParseError { line: line_no, message: format!("expected an arm like `pattern {{` in `?>`, found `{}`", line) });
+            continue;
+        };
+        let (pattern, guard) = split_guard(header.trim());
+        let (mut body, end_line) = parse_block(lines, current, errors);
+        body.splice(0..0, comments.drain(..));
+        match Pattern::parse(pattern) {
+            Ok(pattern) => arms.push(Arm { line: line_no, end_line, pattern, guard: guard.map(parse_expr), body }),
+            Err(message) => errors.push(ParseError { line: line_no, message }),
+        }
+    }
+    (arms, lines.last().map(|l| l.0).unwrap_or(0))
+}
+
+// `pattern ? guard`, at the first `?` outside quotes.
+fn split_guard(header: &str) -> (&str, Option<&str>) {
+    let mut in_quotes = false;
+    for (i, c) in header.char_indices() {
+        if c == '"' { in_quotes = !in_quotes; }
+        if c == '?' && !in_quotes {
+            return (header[..i].trim(), Some(header[i + 1..].trim()));
+        }
+    }
+    (header, None)
+}
+
+impl Interpreter {
+    // Gives the names a pattern bound their values in the current scope, and
+    // returns what they had there before, for `unbind`.
+    pub(crate) fn bind(&mut self, binds: Vec<(String, Value)>) -> Vec<(usize, Option<Value>)> {
+        binds.into_iter().map(|(name, val)| {
+            let slot = self.symbol(&name);
+            let before = self.own(slot);
+            self.define(slot, val);
+            (slot, before)
+        }).collect()
+    }
+
+    pub(crate) fn unbind(&mut self, saved: Vec<(usize, Option<Value>)>) {
+        for (slot, before) in saved.into_iter().rev() {
+            match before {
+                Some(val) => self.define(slot, val),
+                None => self.forget(slot),
+            }
+        }
+    }
+
+    // `=` and `>>`.
+    pub(crate) fn assign_vars(&mut self, vars: &Vars, val: Value) {
+        for (name, val) in vars.unpack(val) {
+            self.store(self.slot(name), val);
+        }
+    }
+
+    pub(crate) fn no_match(&mut self, val: &Value) {
+        self.fail(format!("no pattern matches {}", val.repr()));
+    }
+
+    // `?> value {` on the tree-walker.
+    pub(crate) fn run_match(&mut self, subject: &Expr, arms: &[Arm]) -> Option<Value> {
+        let val = self.eval_expr(subject);
+        for arm in arms {
+            let mut binds = Vec::new();
+            if self.error.is_some() || !arm.pattern.matches(&val, &mut binds) { continue; }
+            let saved = self.bind(binds);
+            if let Some(guard) = &arm.guard && self.eval_expr(guard) != Value::Bool(true) {
+                self.unbind(saved);
+                continue;
+            }
+            let result = self.run_block(&arm.body);
+            self.unbind(saved);
+            return result;
+        }
+        self.no_match(&val);
+        None
+    }
+}
+
+// Arms whose pattern an earlier arm without a guard already covers, for the
+// linter.
+pub(crate) fn unreachable(arms: &[Arm]) -> Vec<&Arm> {
+    arms.iter().enumerate()
+        .filter(|(i, arm)| arms[..*i].iter().any(|earlier| earlier.guard.is_none() && earlier.pattern.covers(&arm.pattern)))
+        .map(|(_, arm)| arm)
+        .collect()
+}
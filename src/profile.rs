@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// --- PROFILER ---
+//
+// `--profile` times every call to a user function and counts how often each
+// line runs. Inclusive time is the whole call, exclusive time leaves out the
+// functions it called. `--folded-stacks file` also writes one line per call
+// stack with its exclusive time in microseconds, the input format of
+// flamegraph tools.
+//
+// Lines are counted per file: a module's line 3 isn't the program's line 3.
+// `None` is the program's own file.
+
+const TOP_LINES: usize = 20;
+
+struct Call {
+    name: String,
+    start: Instant,
+    // Time spent in functions this call made.
+    children: Duration,
+}
+
+#[derive(Default)]
+struct FunctionStats {
+    calls: u64,
+    inclusive: Duration,
+    exclusive: Duration,
+}
+
+pub struct Profiler {
+    stack: Vec<Call>,
+    functions: HashMap<String, FunctionStats>,
+    lines: HashMap<(Option<PathBuf>, usize), u64>,
+    folded: HashMap<String, Duration>,
+    // The file the running code is in: the current function's, or the
+    // module being loaded.
+    files: Vec<Option<PathBuf>>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let main = Call { name: "<main>".to_string(), start: Instant::now(), children: Duration::ZERO };
+        Profiler { stack: vec![main], functions: HashMap::new(), lines: HashMap::new(), folded: HashMap::new(), files: vec![None] }
+    }
+
+    pub(crate) fn hit(&mut self, line: usize) {
+        let file = self.files.last().cloned().flatten();
+        *self.lines.entry((file, line)).or_insert(0) += 1;
+    }
+
+    // `file` is where the function was defined.
+    pub(crate) fn enter(&mut self, name: &str, file: Option<&Path>) {
+        self.stack.push(Call { name: name.to_string(), start: Instant::now(), children: Duration::ZERO });
+        self.files.push(file.map(Path::to_path_buf));
+    }
+
+    pub(crate) fn leave(&mut self) {
+        // The `<main>` frame is only closed by `finish`.
+        if self.stack.len() > 1 {
+            self.close();
+            self.files.pop();
+        }
+    }
+
+    // A module's top level runs while it is imported.
+    pub(crate) fn enter_module(&mut self, file: &Path) {
+        self.files.push(Some(file.to_path_buf()));
+    }
+
+    pub(crate) fn leave_module(&mut self) {
+        if self.files.len() > 1 { self.files.pop(); }
+    }
+
+    fn close(&mut self) {
+        let path: Vec<&str> = self.stack.iter().map(|c| c.name.as_str()).collect();
+        let path = path.join(";");
+        let Some(call) = self.stack.pop() else { return };
+        let elapsed = call.start.elapsed();
+        let exclusive = elapsed.saturating_sub(call.children);
+
+        let stats = self.functions.entry(call.name.clone()).or_default();
+        stats.calls += 1;
+        stats.exclusive += exclusive;
+        // A recursive call is already inside the outer call's inclusive time.
+        if !self.stack.iter().any(|c| c.name == call.name) {
+            stats.inclusive += elapsed;
+        }
+        *self.folded.entry(path).or_default() += exclusive;
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children += elapsed;
+        }
+    }
+
+    // Closes any calls still open, then prints the report to stderr so it
+    // doesn't mix with the program's own output. `program` is the file that
+    // was run; each module's lines follow under its own name.
+    pub fn finish(mut self, program: &Path, folded_file: Option<&Path>) {
+        while !self.stack.is_empty() { self.close(); }
+        let total = self.functions.get("<main>").map(|s| s.inclusive).unwrap_or_default();
+
+        eprintln!();
+        eprintln!("--- profile: {} total ---", ms(total));
+        eprintln!("{:<24} {:>8} {:>12} {:>12}", "function", "calls", "inclusive", "exclusive");
+        let mut functions: Vec<(&String, &FunctionStats)> = self.functions.iter().collect();
+        functions.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then(a.0.cmp(b.0)));
+        for (name, stats) in functions {
+            eprintln!("{:<24} {:>8} {:>12} {:>12}", name, stats.calls, ms(stats.inclusive), ms(stats.exclusive));
+        }
+
+        // Functions the program defines know its file by its full path.
+        let program_path = fs::canonicalize(program).ok();
+        let mut files: HashMap<Option<PathBuf>, HashMap<usize, u64>> = HashMap::new();
+        for ((file, line), hits) in self.lines {
+            let file = file.filter(|f| Some(f) != program_path.as_ref());
+            *files.entry(file).or_default().entry(line).or_default() += hits;
+        }
+        let mut files: Vec<(Option<PathBuf>, HashMap<usize, u64>)> = files.into_iter().collect();
+        files.sort_by(|a, b| a.0.cmp(&b.0));
+        for (file, lines) in files {
+            let mut lines: Vec<(usize, u64)> = lines.into_iter().collect();
+            eprintln!();
+            if let Some(file) = &file {
+                eprintln!("{}", file.file_name().unwrap_or_default().to_string_lossy());
+            }
+            eprintln!("{:>6} {:>10}  code", "line", "hits");
+            lines.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            let source = fs::read_to_string(file.as_deref().unwrap_or(program)).unwrap_or_default();
+            let code: Vec<&str> = source.lines().collect();
+            for (line, hits) in lines.into_iter().take(TOP_LINES) {
+                let text = code.get(line.wrapping_sub(1)).map(|l| l.trim()).unwrap_or("");
+                eprintln!("{:>6} {:>10}  {}", line, hits, text);
+            }
+        }
+
+        if let Some(file) = folded_file {
+            let mut stacks: Vec<(&String, &Duration)> = self.folded.iter().collect();
+            stacks.sort();
+            let out: String = stacks.iter()
+                .map(|(path, time)| format!("{} {}\n", path, time.as_micros()))
+                .collect();
+            if let Err(e) = fs::write(file, out) {
+                eprintln!("Error: {}: {}", file.display(), e);
+            }
+        }
+    }
+}
+
+fn ms(d: Duration) -> String {
+    format!("{:.3} ms", d.as_secs_f64() * 1000.0)
+}
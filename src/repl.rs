@@ -0,0 +1,259 @@
+use std::fs;
+use std::io::{self, IsTerminal};
+
+use crate::dump;
+use crate::line_editor::{LineEditor, ReadLine};
+use crate::pattern;
+use crate::{parse, parse_expr, parse_program, Error, Expr, Interpreter, Statement, Stmt, Value, BUILTINS};
+
+// --- REPL ---
+//
+// Every complete statement runs as soon as it is entered. A line that leaves a
+// `{`, `(` or `[` open switches to the `...>` prompt until the block is closed.
+// A finished `?` block also waits one more line, because a `??` may follow.
+// Lines starting with `:` are REPL commands, see `COMMANDS`.
+
+const PROMPT: &str = "lazy> ";
+const CONTINUE_PROMPT: &str = "...> ";
+
+const COMMANDS: &[(&str, &str)] = &[
+    (":vars", "List variables and functions"),
+    (":ast <code>", "Show how code is parsed, as S-expressions"),
+    (":ast=json <code>", "Show how code is parsed, as JSON"),
+    (":type <expr>", "Show the type of an expression's value"),
+    (":load <file>", "Run a file in this session"),
+    (":save <file>", "Save the code that ran in this session to a file"),
+    (":reset", "Forget all variables and functions"),
+    (":help [symbol]", "Show commands and built-in symbols"),
+];
+
+struct Session {
+    interp: Interpreter,
+    // The statements that ran without an error, for `:save`.
+    input: String,
+}
+
+pub fn run(interp: Interpreter) {
+    println!("Lazy Lang REPL - Type 'exit' to quit, ':help' for commands");
+    let mut session = Session { interp, input: String::new() };
+    let mut editor = LineEditor::new();
+    let mut buf = String::new();
+
+    loop {
+        let prompt = if buf.is_empty() { PROMPT } else { CONTINUE_PROMPT };
+        let line = match editor.read_line(prompt) {
+            ReadLine::Line(line) => line,
+            ReadLine::Interrupted => {
+                buf.clear();
+                continue;
+            }
+            ReadLine::Eof => break,
+        };
+
+        // The buffer is a complete `?` chain waiting to see if a `??` follows.
+        let (braces, parens) = open_brackets(&buf);
+        if !buf.is_empty() && braces <= 0 && parens <= 0 && !line.trim_start().starts_with("??") {
+            session.run(&buf);
+            buf.clear();
+        }
+
+        if buf.is_empty() && line.trim() == "exit" { break; }
+        if buf.is_empty() && line.trim_start().starts_with(':') {
+            session.command(line.trim());
+            continue;
+        }
+
+        if buf.is_empty() && line.trim().is_empty() { continue; }
+        // The parser works line by line, so an open `(` or `[` joins the next line onto this one.
+        if open_brackets(&buf).1 > 0 {
+            buf.pop();
+            buf.push(' ');
+        }
+        buf.push_str(&line);
+        buf.push('\n');
+
+        let (braces, parens) = open_brackets(&buf);
+        if braces > 0 || parens > 0 || ends_with_if(&buf) { continue; }
+        session.run(&buf);
+        buf.clear();
+    }
+
+    if !buf.is_empty() {
+        session.run(&buf);
+    }
+}
+
+impl Session {
+    fn run(&mut self, code: &str) {
+        if let Err(e) = run_input(&mut self.interp, code, &mut self.input) {
+            report_error(e);
+        }
+    }
+
+    fn command(&mut self, line: &str) {
+        let (cmd, arg) = match line.split_once(char::is_whitespace) {
+            Some((cmd, arg)) => (cmd, arg.trim()),
+            None => (line, ""),
+        };
+        match cmd {
+            ":vars" => self.list_vars(),
+            ":ast" => {
+                let (stmts, errors) = parse_program(arg);
+                print!("{}", dump::sexpr(&stmts));
+                for err in errors {
+                    eprintln!("Error: {}", err.message);
+                }
+            }
+            ":ast=json" => {
+                let (stmts, errors) = parse_program(arg);
+                println!("{}", dump::json(&stmts, &errors).pretty());
+            }
+            ":type" => {
+                let ret = Stmt { kind: Statement::Return(parse_expr(arg)), line: 1, end_line: 1 };
+                match self.interp.run(&[ret]) {
+                    Ok(val) => println!("{}", val.unwrap_or(Value::Nothing).type_name()),
+                    Err(err) => report_error(err),
+                }
+            }
+            ":load" => {
+                if let Err(err) = self.interp.run_file(arg) { report_error(err); }
+            }
+            ":save" => match fs::write(arg, &self.input) {
+                Ok(()) => println!("Saved to {}", arg),
+                Err(e) => eprintln!("Error: {}", e),
+            },
+            ":reset" => {
+                self.interp.reset();
+                self.input.clear();
+            }
+            ":help" => help(arg),
+            _ => eprintln!("Error: unknown command {} (try :help)", cmd),
+        }
+    }
+
+    fn list_vars(&self) {
+        let mut vars: Vec<(String, &Value)> = Vec::new();
+        for scope in self.interp.scope_vars() {
+            for (name, val) in scope {
+                vars.retain(|(n, _)| *n != name);
+                vars.push((name, val));
+            }
+        }
+        vars.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, val) in vars {
+            match val {
+                Value::Function(func) => println!("{}({})", name, pattern::show_params(&func.params)),
+                Value::Text(t) => println!("{} = \"{}\"", name, t),
+                Value::Nothing => println!("{} = nothing", name),
+                _ => println!("{} = {}", name, val),
+            }
+        }
+    }
+}
+
+fn help(symbol: &str) {
+    if !symbol.is_empty() {
+        match BUILTINS.iter().find(|b| b.symbol == symbol) {
+            Some(builtin) => println!("{:<26} {}", builtin.usage, builtin.help),
+            None => eprintln!("Error: {} is not a built-in symbol", symbol),
+        }
+        return;
+    }
+    println!("Commands:");
+    for (cmd, help) in COMMANDS {
+        println!("  {:<16} {}", cmd, help);
+    }
+    println!("Built-in symbols:");
+    for builtin in BUILTINS {
+        println!("  {:<26} {}", builtin.usage, builtin.help);
+    }
+}
+
+// Runs what was entered the way `Interpreter::run` runs a program, one
+// top-level statement at a time so that each bare expression's value, and
+// the variable each `*` call changes, can be shown as it comes. The lines of
+// each statement that runs are added to `ran`.
+fn run_input(interp: &mut Interpreter, code: &str, ran: &mut String) -> Result<(), Error> {
+    let stmts = parse(code)?;
+    interp.check_names(&stmts)?;
+    let lines: Vec<&str> = code.lines().collect();
+    // Lines of `code` already in `ran`; a comment can share one with the statement before it.
+    let mut kept = 0;
+    for stmt in stmts {
+        let (first, last) = (stmt.line.max(kept + 1), stmt.end_line.min(lines.len()));
+        match stmt.kind {
+            Statement::Print(expr) => {
+                let ret = Stmt { kind: Statement::Return(expr), ..stmt };
+                let val = interp.run_checked(&[ret])?;
+                show(&val.unwrap_or(Value::Nothing));
+            }
+            Statement::FunctionCall(_, ref args, true) => {
+                let changed = match args.first() {
+                    Some(Expr::Variable(name)) => Some(name.clone()),
+                    _ => None,
+                };
+                interp.run_checked(&[stmt])?;
+                if let Some(name) = changed {
+                    show(&interp.get_var(&name));
+                }
+            }
+            _ => {
+                interp.run_checked(&[stmt])?;
+            }
+        }
+        for line in lines.get(first - 1..last).unwrap_or_default() {
+            ran.push_str(line);
+            ran.push('\n');
+        }
+        kept = kept.max(last);
+    }
+    Ok(())
+}
+
+// An error stops the rest of the input, but the session goes on.
+fn report_error(err: Error) {
+    match err {
+        Error::Parse(errors) => {
+            for err in errors {
+                eprintln!("Error: {}", err);
+            }
+        }
+        err => eprintln!("Error: {}", err),
+    }
+}
+
+// Scripts print nothing for `Nothing`; the REPL says so, so typos stand out.
+fn show(val: &Value) {
+    match val {
+        Value::Nothing if io::stdout().is_terminal() => println!("\x1b[2mnothing\x1b[0m"),
+        Value::Nothing => println!("nothing"),
+        _ => println!("{}", val),
+    }
+}
+
+fn ends_with_if(code: &str) -> bool {
+    let (stmts, _) = parse_program(code);
+    matches!(stmts.iter().rfind(|s| !matches!(s.kind, Statement::Comment(_))), Some(Stmt { kind: Statement::If(..), .. }))
+}
+
+// How many `{` and how many `(`/`[` are still open, ignoring strings and comments.
+fn open_brackets(code: &str) -> (i32, i32) {
+    let mut braces = 0;
+    let mut parens = 0;
+    for line in code.lines() {
+        let mut in_quotes = false;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => in_quotes = !in_quotes,
+                '/' if !in_quotes && chars.peek() == Some(&'/') => break,
+                '{' if !in_quotes => braces += 1,
+                '}' if !in_quotes => braces -= 1,
+                '(' | '[' if !in_quotes => parens += 1,
+                ')' | ']' if !in_quotes => parens -= 1,
+                _ => {}
+            }
+        }
+    }
+    (braces, parens)
+}
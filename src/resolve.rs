@@ -0,0 +1,663 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::pattern::{param_names, Vars};
+use crate::{Child, ChildMut, Expr, Function, Interpreter, Name, ParseError, Statement, Stmt, Value, BUILTINS};
+
+// --- RESOLVER ---
+//
+// Before a program runs, `resolve` gives every variable it reads or assigns
+// an address: a depth and a slot there. There are two depths. Code at the
+// top level keeps its variables in the globals, where the slot is the name's
+// symbol, the number this interpreter gave it. A function keeps them in the
+// frame of each call, which has a slot for every name its body uses, its
+// parameters first; that layout is worked out from the body, the same way by
+// the resolver and by each call. Reading or assigning a variable then indexes
+// the globals or the running call's frame directly.
+//
+// Lazy scopes names dynamically: a function sees its caller's variables, and
+// assigning a name its caller has changes the caller's. So a slot the running
+// call hasn't filled falls back to the callers' frames, innermost first, and
+// then the globals, looking the name up by symbol in each. Only names some
+// function can give a value to are ever looked for in frames, so reading a
+// global from a function goes straight to the globals.
+//
+// Code `run` never saw, like a debugger expression or the body of a
+// `Function::new`, has no addresses and looks every name up that way. Before
+// a program runs, `undefined_names` reports every name that is read but never
+// given a value anywhere.
+
+// One per interpreter, and gone with it. Compiled code and frame layouts hold
+// symbols, so they keep the table they were made with to tell whether they
+// still fit.
+#[derive(Debug, Default)]
+pub(crate) struct Symbols {
+    ids: RefCell<HashMap<String, usize>>,
+    names: RefCell<Vec<String>>,
+    // By symbol: whether any call's frame can hold it.
+    in_frames: RefCell<Vec<bool>>,
+}
+
+impl Symbols {
+    pub(crate) fn symbol(&self, name: &str) -> usize {
+        if let Some(&id) = self.ids.borrow().get(name) {
+            return id;
+        }
+        let mut names = self.names.borrow_mut();
+        let id = names.len();
+        names.push(name.to_string());
+        self.ids.borrow_mut().insert(name.to_string(), id);
+        id
+    }
+
+    fn find(&self, name: &str) -> Option<usize> {
+        self.ids.borrow().get(name).copied()
+    }
+
+    fn name(&self, id: usize) -> String {
+        self.names.borrow()[id].clone()
+    }
+
+    fn can_be_in_frames(&self, symbol: usize) {
+        let mut in_frames = self.in_frames.borrow_mut();
+        if in_frames.len() <= symbol {
+            in_frames.resize(symbol + 1, false);
+        }
+        in_frames[symbol] = true;
+    }
+
+    fn is_in_frames(&self, symbol: usize) -> bool {
+        self.in_frames.borrow().get(symbol).copied().unwrap_or(false)
+    }
+}
+
+// Where a function's calls keep their variables, for one interpreter's
+// symbols.
+#[derive(Debug, Default)]
+pub(crate) struct Layout {
+    symbols: Rc<Symbols>,
+    // The symbol in each slot.
+    names: Vec<usize>,
+    // The slot of each symbol.
+    slots: HashMap<usize, usize>,
+}
+
+impl Layout {
+    fn new(symbols: &Rc<Symbols>, params: &[Vars], body: &[Stmt]) -> Layout {
+        let (names, bound) = frame_names(params, body);
+        let names: Vec<usize> = names.iter().map(|name| symbols.symbol(name)).collect();
+        for &symbol in &names[..bound] {
+            symbols.can_be_in_frames(symbol);
+        }
+        let slots = names.iter().enumerate().rev().map(|(slot, &symbol)| (symbol, slot)).collect();
+        Layout { symbols: symbols.clone(), names, slots }
+    }
+}
+
+// The names a function's frame has a slot for: its parameters, then the names
+// its body gives a value to, then the ones it only reads. Returns them with
+// how many can be given a value. A function defined inside has a frame of its
+// own, so only its name counts.
+pub(crate) fn frame_names(params: &[Vars], body: &[Stmt]) -> (Vec<String>, usize) {
+    let mut names = FrameNames::default();
+    for name in param_names(params) {
+        names.add(&name);
+    }
+    names.bound(body);
+    let bound = names.names.len();
+    names.read(body);
+    (names.names, bound)
+}
+
+#[derive(Default)]
+struct FrameNames {
+    names: Vec<String>,
+    seen: HashSet<String>,
+}
+
+impl FrameNames {
+    fn add(&mut self, name: &str) {
+        if self.seen.insert(name.to_string()) {
+            self.names.push(name.to_string());
+        }
+    }
+
+    fn bound(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match &stmt.kind {
+                Statement::Assign(vars, _) | Statement::For(vars, _, _) => {
+                    for name in vars.names() {
+                        self.add(name);
+                    }
+                }
+                Statement::AugAssign(name, _, _) | Statement::IncDec(name, _) => self.add(name),
+                Statement::Input(vars, _, _) => {
+                    for name in vars {
+                        self.add(name);
+                    }
+                }
+                Statement::Import(_, names) => {
+                    for (_, bind_as) in names {
+                        self.add(bind_as);
+                    }
+                }
+                Statement::Match(_, arms) => {
+                    for arm in arms {
+                        for name in arm.pattern.names() {
+                            self.add(name);
+                        }
+                    }
+                }
+                Statement::FunctionDef(name, _, _) | Statement::QuickFunctionDef(name, _, _) => {
+                    self.add(name);
+                    continue;
+                }
+                _ => {}
+            }
+            for block in stmt.blocks() {
+                self.bound(block);
+            }
+        }
+    }
+
+    fn read(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            if matches!(stmt.kind, Statement::FunctionDef(..) | Statement::QuickFunctionDef(..)) { continue; }
+            for child in stmt.children() {
+                match child {
+                    Child::Expr(expr, _) => self.read_expr(expr),
+                    Child::Block(block) => self.read(block),
+                }
+            }
+        }
+    }
+
+    fn read_expr(&mut self, expr: &Expr) {
+        if let Expr::Variable(name) = expr {
+            self.add(name);
+        }
+        for child in expr.children() {
+            self.read_expr(child);
+        }
+    }
+}
+
+// Where names are kept in the code being resolved.
+enum Scope<'a> {
+    Globals(&'a Symbols),
+    Frame(HashMap<String, usize>),
+}
+
+impl Scope<'_> {
+    fn function(params: &[Vars], body: &[Stmt]) -> Scope<'static> {
+        let (names, _) = frame_names(params, body);
+        Scope::Frame(names.into_iter().enumerate().rev().map(|(slot, name)| (name, slot)).collect())
+    }
+
+    fn address(&self, name: &Name) -> Address {
+        match self {
+            Scope::Globals(symbols) => Address::Global(symbols.symbol(name)),
+            Scope::Frame(slots) => slots.get(name.as_str()).map_or(Address::Unresolved, |&slot| Address::Local(slot)),
+        }
+    }
+
+    fn resolve(&self, name: &mut Name) {
+        name.set_address(self.address(name));
+    }
+}
+
+// Gives every variable of a program, and of the functions it defines, its
+// address.
+pub(crate) fn resolve(stmts: &mut [Stmt], symbols: &Symbols) {
+    resolve_block(stmts, &Scope::Globals(symbols));
+}
+
+fn resolve_function(params: &mut [Vars], body: &mut [Stmt]) {
+    let scope = Scope::function(params, body);
+    for param in params {
+        for name in param.names_mut() {
+            scope.resolve(name);
+        }
+    }
+    resolve_block(body, &scope);
+}
+
+fn resolve_block(stmts: &mut [Stmt], scope: &Scope) {
+    for stmt in stmts {
+        match &mut stmt.kind {
+            Statement::Assign(vars, _) | Statement::For(vars, _, _) => {
+                for name in vars.names_mut() {
+                    scope.resolve(name);
+                }
+            }
+            Statement::AugAssign(name, _, _) | Statement::IncDec(name, _) => scope.resolve(name),
+            Statement::FunctionDef(_, params, body) => {
+                resolve_function(params, body);
+                continue;
+            }
+            Statement::QuickFunctionDef(_, params, expr) => {
+                // Its body is `-> expr`, which has the same names.
+                let body = [Stmt { line: stmt.line, end_line: stmt.line, kind: Statement::Return(expr.clone()) }];
+                let scope = Scope::function(params, &body);
+                for param in params.iter_mut() {
+                    for name in param.names_mut() {
+                        scope.resolve(name);
+                    }
+                }
+                resolve_expr(expr, &scope);
+                continue;
+            }
+            _ => {}
+        }
+        for child in stmt.children_mut() {
+            match child {
+                ChildMut::Expr(expr, _) => resolve_expr(expr, scope),
+                ChildMut::Block(block) => resolve_block(block, scope),
+            }
+        }
+    }
+}
+
+fn resolve_expr(expr: &mut Expr, scope: &Scope) {
+    if let Expr::Variable(name) = expr {
+        scope.resolve(name);
+    }
+    for child in expr.children_mut() {
+        resolve_expr(child, scope);
+    }
+}
+
+// Where the resolver found a variable: the depth, the running call's frame
+// or the globals, and the slot there.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) enum Address {
+    // Not resolved: looked up by name, like code `run` never saw.
+    #[default]
+    Unresolved,
+    // Slot of the running call's frame.
+    Local(usize),
+    // Slot of the globals, which is the name's symbol.
+    Global(usize),
+}
+
+// An address as running code uses it: an unresolved name becomes its symbol.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Slot {
+    Local(usize),
+    Global(usize),
+    Symbol(usize),
+}
+
+// The variables of one function call, by slot of its function's layout.
+#[derive(Default)]
+pub(crate) struct Frame {
+    layout: Rc<Layout>,
+    vars: Vec<Option<Value>>,
+    // Variables the layout has no slot for, given a value by name: from the
+    // debugger, or kept from the call a tail call took the place of.
+    extra: Vec<(usize, Value)>,
+}
+
+impl Frame {
+    fn new(layout: Rc<Layout>) -> Frame {
+        let vars = vec![None; layout.names.len()];
+        Frame { layout, vars, extra: Vec::new() }
+    }
+
+    fn get(&self, symbol: usize) -> Option<&Value> {
+        match self.layout.slots.get(&symbol) {
+            Some(&slot) => self.vars[slot].as_ref(),
+            None => self.extra.iter().find(|(s, _)| *s == symbol).map(|(_, v)| v),
+        }
+    }
+
+    fn get_mut(&mut self, symbol: usize) -> Option<&mut Value> {
+        match self.layout.slots.get(&symbol) {
+            Some(&slot) => self.vars[slot].as_mut(),
+            None => self.extra.iter_mut().find(|(s, _)| *s == symbol).map(|(_, v)| v),
+        }
+    }
+
+    fn insert(&mut self, symbol: usize, val: Value) {
+        self.layout.symbols.can_be_in_frames(symbol);
+        if let Some(&slot) = self.layout.slots.get(&symbol) {
+            self.vars[slot] = Some(val);
+            return;
+        }
+        match self.extra.iter_mut().find(|(s, _)| *s == symbol) {
+            Some((_, var)) => *var = val,
+            None => self.extra.push((symbol, val)),
+        }
+    }
+
+    fn remove(&mut self, symbol: usize) {
+        match self.layout.slots.get(&symbol) {
+            Some(&slot) => self.vars[slot] = None,
+            None => self.extra.retain(|(s, _)| *s != symbol),
+        }
+    }
+
+    // Every variable the call has, by symbol.
+    fn bindings(&self) -> impl Iterator<Item = (usize, &Value)> {
+        let slots = self.vars.iter().enumerate().filter_map(|(slot, val)| Some((self.layout.names[slot], val.as_ref()?)));
+        slots.chain(self.extra.iter().map(|(symbol, val)| (*symbol, val)))
+    }
+
+    // A call's parameters, with the names of an `[a b]` one in their place.
+    pub(crate) fn call(layout: Rc<Layout>, params: &[Vars], args: Vec<Value>) -> Frame {
+        let mut frame = Frame::new(layout);
+        for (param, arg) in params.iter().zip(args) {
+            for (name, val) in param.unpack(arg) {
+                match name.address() {
+                    Address::Local(slot) => frame.vars[slot] = Some(val),
+                    _ => {
+                        let symbol = frame.layout.symbols.symbol(name);
+                        frame.insert(symbol, val);
+                    }
+                }
+            }
+        }
+        frame
+    }
+
+    // The frame for a tail call made from this one. The callee would have
+    // seen this call's variables under its own, so they stay.
+    pub(crate) fn reuse(self, layout: Rc<Layout>, params: &[Vars], args: Vec<Value>) -> Frame {
+        let mut frame = Frame::call(layout, params, args);
+        for (symbol, val) in self.bindings() {
+            if frame.get(symbol).is_none() {
+                frame.insert(symbol, val.clone());
+            }
+        }
+        frame
+    }
+}
+
+impl Interpreter {
+    pub(crate) fn symbol(&self, name: &str) -> usize {
+        self.symbols.symbol(name)
+    }
+
+    // Resolves the program `run` is about to run.
+    pub(crate) fn resolve(&self, stmts: &mut [Stmt]) {
+        resolve(stmts, &self.symbols);
+    }
+
+    // Where the function's calls keep their variables, for this interpreter's
+    // symbols.
+    pub(crate) fn function_layout(&self, func: &Function) -> Rc<Layout> {
+        let mut layout = func.layout.borrow_mut();
+        match &*layout {
+            Some(layout) if Rc::ptr_eq(&layout.symbols, &self.symbols) => layout.clone(),
+            _ => layout.insert(Rc::new(Layout::new(&self.symbols, &func.params, &func.body))).clone(),
+        }
+    }
+
+    // Where the running code keeps `name`.
+    pub(crate) fn slot(&self, name: &Name) -> Slot {
+        match name.address() {
+            Address::Local(slot) => Slot::Local(slot),
+            Address::Global(symbol) => Slot::Global(symbol),
+            Address::Unresolved => Slot::Symbol(self.symbol(name)),
+        }
+    }
+
+    pub(crate) fn lookup(&self, symbol: usize) -> Value {
+        self.lookup_from(self.frames.len(), symbol)
+    }
+
+    // The variable in the innermost of the first `depth` frames that has it,
+    // or the global.
+    fn lookup_from(&self, depth: usize, symbol: usize) -> Value {
+        if self.symbols.is_in_frames(symbol) {
+            for frame in self.frames[..depth].iter().rev() {
+                if let Some(val) = frame.get(symbol) {
+                    return val.clone();
+                }
+            }
+        }
+        self.globals.get(symbol).cloned().flatten().unwrap_or(Value::Nothing)
+    }
+
+    // Changes the innermost variable with this name, or creates it in the
+    // current call when there is none.
+    pub(crate) fn assign(&mut self, symbol: usize, val: Value) {
+        if let Some(var) = self.find_mut(self.frames.len(), symbol) {
+            *var = val;
+            return;
+        }
+        self.define(symbol, val);
+    }
+
+    // The variable in the innermost of the first `depth` frames that has it,
+    // or the global, if there is one.
+    fn find_mut(&mut self, depth: usize, symbol: usize) -> Option<&mut Value> {
+        if self.symbols.is_in_frames(symbol)
+            && let Some(i) = self.frames[..depth].iter().rposition(|f| f.get(symbol).is_some()) {
+            return self.frames[i].get_mut(symbol);
+        }
+        self.globals.get_mut(symbol)?.as_mut()
+    }
+
+    // Creates the variable in the current call, even if an outer one exists.
+    pub(crate) fn define(&mut self, symbol: usize, val: Value) {
+        match self.frames.last_mut() {
+            Some(frame) => frame.insert(symbol, val),
+            None => self.set_global(symbol, val),
+        }
+    }
+
+    fn set_global(&mut self, symbol: usize, val: Value) {
+        if self.globals.len() <= symbol {
+            self.globals.resize(symbol + 1, None);
+        }
+        self.globals[symbol] = Some(val);
+    }
+
+    // The variable as the current call has it, without looking further out.
+    pub(crate) fn own(&self, symbol: usize) -> Option<Value> {
+        match self.frames.last() {
+            Some(frame) => frame.get(symbol).cloned(),
+            None => self.globals.get(symbol).cloned().flatten(),
+        }
+    }
+
+    // Removes the variable from the current call, undoing `define`.
+    pub(crate) fn forget(&mut self, symbol: usize) {
+        match self.frames.last_mut() {
+            Some(frame) => frame.remove(symbol),
+            None => {
+                if let Some(var) = self.globals.get_mut(symbol) {
+                    *var = None;
+                }
+            }
+        }
+    }
+
+    pub(crate) fn load(&self, slot: Slot) -> Value {
+        match slot {
+            Slot::Local(slot) => {
+                let Some(frame) = self.frames.last() else { return Value::Nothing };
+                match &frame.vars[slot] {
+                    Some(val) => val.clone(),
+                    None => self.lookup_from(self.frames.len() - 1, frame.layout.names[slot]),
+                }
+            }
+            Slot::Global(symbol) if self.frames.is_empty() => {
+                self.globals.get(symbol).cloned().flatten().unwrap_or(Value::Nothing)
+            }
+            Slot::Global(symbol) | Slot::Symbol(symbol) => self.lookup(symbol),
+        }
+    }
+
+    pub(crate) fn store(&mut self, slot: Slot, val: Value) {
+        match slot {
+            Slot::Local(slot) => {
+                let depth = self.frames.len().saturating_sub(1);
+                let Some(frame) = self.frames.last_mut() else { return };
+                if frame.vars[slot].is_none() {
+                    let symbol = frame.layout.names[slot];
+                    if let Some(var) = self.find_mut(depth, symbol) {
+                        *var = val;
+                        return;
+                    }
+                }
+                if let Some(frame) = self.frames.last_mut() {
+                    frame.vars[slot] = Some(val);
+                }
+            }
+            Slot::Global(symbol) if self.frames.is_empty() => self.set_global(symbol, val),
+            Slot::Global(symbol) | Slot::Symbol(symbol) => self.assign(symbol, val),
+        }
+    }
+
+    fn var_mut(&mut self, slot: Slot) -> Option<&mut Value> {
+        let symbol = match slot {
+            Slot::Local(slot) => {
+                let depth = self.frames.len().checked_sub(1)?;
+                let frame = &self.frames[depth];
+                if frame.vars[slot].is_some() {
+                    return self.frames[depth].vars[slot].as_mut();
+                }
+                return self.find_mut(depth, frame.layout.names[slot]);
+            }
+            Slot::Global(symbol) | Slot::Symbol(symbol) => symbol,
+        };
+        self.find_mut(self.frames.len(), symbol)
+    }
+
+    // Empties the variable if it still shares `val`'s text or list, so `val`
+    // can be changed in place. The caller stores the new value right after.
+    pub(crate) fn release(&mut self, slot: Slot, val: &Value) {
+        if let Some(var) = self.var_mut(slot) && shares(var, val) {
+            *var = Value::Nothing;
+        }
+    }
+
+    pub(crate) fn global(&self, name: &str) -> Option<&Value> {
+        self.symbols.find(name).and_then(|slot| self.globals.get(slot)?.as_ref())
+    }
+
+    // Every scope's variables by name, globals first.
+    pub(crate) fn scope_vars(&self) -> Vec<Vec<(String, &Value)>> {
+        let globals = self.globals.iter().enumerate()
+            .filter_map(|(slot, val)| val.as_ref().map(|v| (self.symbols.name(slot), v)))
+            .collect();
+        let frames = self.frames.iter()
+            .map(|frame| frame.bindings().map(|(symbol, v)| (self.symbols.name(symbol), v)).collect());
+        std::iter::once(globals).chain(frames).collect()
+    }
+
+    // Names the program reads or calls that nothing ever assigns: not the
+    // program, not an earlier run, not a registered function. Each name is
+    // reported once, at its first use.
+    pub(crate) fn undefined_names(&self, stmts: &[Stmt]) -> Vec<ParseError> {
+        let mut names = Names::default();
+        names.collect(stmts);
+        let mut errors = Vec::new();
+        let mut reported = HashSet::new();
+        names.check(stmts, &mut |name: &str, line: usize, call: bool| {
+            if self.is_defined(name, &names) || !reported.insert(name.to_string()) { return; }
+            let message = if call {
+                format!("call to undefined function `{}`", name)
+            } else {
+                format!("undefined variable `{}`", name)
+            };
+            errors.push(ParseError { line, message });
+        });
+        errors
+    }
+
+    fn is_defined(&self, name: &str, names: &Names) -> bool {
+        names.defined.contains(name)
+            || self.natives.contains_key(name)
+            || self.global(name).is_some()
+            || name.split_once('.').is_some_and(|(ns, _)| {
+                names.namespaces.contains(ns) || self.modules.values().any(|m| m.namespace == ns)
+            })
+    }
+}
+
+#[derive(Default)]
+struct Names {
+    defined: HashSet<String>,
+    namespaces: HashSet<String>,
+}
+
+impl Names {
+    // Everything that is ever given a value, wherever it is.
+    fn collect(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match &stmt.kind {
+                Statement::Assign(vars, _) | Statement::For(vars, _, _) => {
+                    self.defined.extend(vars.names().into_iter().map(String::from));
+                }
+                Statement::AugAssign(name, _, _) | Statement::IncDec(name, _) => {
+                    self.defined.insert(name.to_string());
+                }
+                Statement::Input(vars, _, _) => self.defined.extend(vars.iter().cloned()),
+                Statement::Match(_, arms) => {
+                    for arm in arms {
+                        self.defined.extend(arm.pattern.names().into_iter().map(String::from));
+                    }
+                }
+                Statement::FunctionDef(name, params, _) | Statement::QuickFunctionDef(name, params, _) => {
+                    self.defined.insert(name.to_string());
+                    self.defined.extend(param_names(params));
+                }
+                Statement::Import(path, names) => {
+                    if let Some(stem) = Path::new(path).file_stem() {
+                        self.namespaces.insert(stem.to_string_lossy().to_string());
+                    }
+                    self.defined.extend(names.iter().map(|(_, bind_as)| bind_as.clone()));
+                }
+                _ => {}
+            }
+            for block in stmt.blocks() {
+                self.collect(block);
+            }
+        }
+    }
+
+    // Calls `found(name, line, is_call)` for every name that is read or called.
+    fn check(&self, stmts: &[Stmt], found: &mut impl FnMut(&str, usize, bool)) {
+        for stmt in stmts {
+            for child in stmt.children() {
+                match child {
+                    Child::Expr(expr, line) => expr_names(expr, line, found),
+                    Child::Block(block) => self.check(block, found),
+                }
+            }
+            if let Statement::FunctionCall(name, _, _) = &stmt.kind {
+                call_name(name, stmt.line, found);
+            }
+        }
+    }
+}
+
+fn expr_names(expr: &Expr, line: usize, found: &mut impl FnMut(&str, usize, bool)) {
+    for child in expr.children() {
+        expr_names(child, line, found);
+    }
+    match expr {
+        Expr::Variable(name) => found(name, line, false),
+        Expr::FunctionCall(name, _, _) => call_name(name, line, found),
+        _ => {}
+    }
+}
+
+fn call_name(name: &str, line: usize, found: &mut impl FnMut(&str, usize, bool)) {
+    if !BUILTINS.iter().any(|b| b.symbol == name) {
+        found(name, line, true);
+    }
+}
+
+fn shares(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Text(x), Value::Text(y)) => Rc::ptr_eq(x, y),
+        (Value::List(x), Value::List(y)) => Rc::ptr_eq(x, y),
+        _ => false,
+    }
+}
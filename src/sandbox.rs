@@ -0,0 +1,195 @@
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::{Interpreter, RuntimeError, Value};
+
+// --- SANDBOX ---
+//
+// Limits for running programs nobody has read, like student submissions: how
+// many statements may run, how long a list or text may get, how long a run may
+// take, and whether `+?` may wait for input. Running out of one stops the
+// program with an error that says which, see `RuntimeError::limit`. The
+// statement count and the clock start again with every `run`, `eval_str` or
+// `call`.
+//
+// A list can hold the same list many times, so `a = [a a]` done twenty times
+// is a small list that prints as millions of numbers. Builtins that go through
+// every item inside a value, like `$`, `&`, `==`, `<<` and printing, count
+// each item they look at with `visit`, which keeps an eye on the clock, and
+// text they build is checked against the size limit as it grows.
+
+/// No limits by default: set the fields to impose them.
+///
+/// ```
+/// use lazy::sandbox::{Limit, Limits};
+/// use lazy::{Error, Interpreter};
+///
+/// let mut interp = Interpreter::new();
+/// interp.set_limits(Limits { max_statements: Some(1000), ..Limits::default() });
+/// match interp.eval_str("n = 0\n@ yes {\n  n += 1\n}") {
+///     Err(Error::Runtime(err)) => assert_eq!(err.limit, Some(Limit::Statements)),
+///     other => panic!("expected a runtime error, got {:?}", other),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Limits {
+    /// How many statements may run, counting each time a loop body's
+    /// statements run again, and one more for each time round a loop, so an
+    /// empty loop runs out too.
+    pub max_statements: Option<u64>,
+    /// How many items any one list, or bytes any one text, may have. Each
+    /// value is checked on its own as it is made; a text made from a list,
+    /// like `$(list)`, is stopped as soon as it is too long, however many
+    /// lists it shows.
+    pub max_value_size: Option<usize>,
+    /// How long a run may take. Checked every few statements, and every few
+    /// items a builtin like `$` or `<<` looks at, so a `+?` waiting for an
+    /// answer can take longer.
+    pub timeout: Option<Duration>,
+    /// Whether `+?` may read input. Without it, the first `+?` is an error.
+    pub allow_input: bool,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self { max_statements: None, max_value_size: None, timeout: None, allow_input: true }
+    }
+}
+
+/// Which limit stopped a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Statements,
+    Size,
+    Time,
+    Input,
+}
+
+impl Limit {
+    /// Exit status of the `lazy` command when a program runs out of this
+    /// limit. Other errors exit with 1.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Limit::Statements => 3,
+            Limit::Size => 4,
+            Limit::Time => 5,
+            Limit::Input => 6,
+        }
+    }
+}
+
+// How often `tick` looks at the clock.
+const CLOCK_EVERY: u64 = 1024;
+
+impl Interpreter {
+    /// Stops programs that go past `limits`, see `sandbox`.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub(crate) fn start_budget(&mut self) {
+        self.steps = 0;
+        self.visits = 0;
+        self.deadline = self.limits.timeout.map(|timeout| Instant::now() + timeout);
+    }
+
+    // Counts one statement; false once a limit has stopped the program.
+    pub(crate) fn tick(&mut self) -> bool {
+        self.steps += 1;
+        if let Some(max) = self.limits.max_statements && self.steps > max {
+            return self.exceed(Limit::Statements, format!("ran more than {} statements", max));
+        }
+        !self.steps.is_multiple_of(CLOCK_EVERY) || self.in_time()
+    }
+
+    // Counts one item a builtin looked at; false once a limit has stopped the
+    // program.
+    #[inline]
+    pub(crate) fn visit(&mut self) -> bool {
+        self.visits += 1;
+        self.error.is_none() && (!self.visits.is_multiple_of(CLOCK_EVERY) || self.in_time())
+    }
+
+    fn in_time(&mut self) -> bool {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => {
+                let timeout = self.limits.timeout.unwrap_or_default();
+                self.exceed(Limit::Time, format!("ran longer than {:?}", timeout))
+            }
+            _ => true,
+        }
+    }
+
+    // `val` as `$` shows it, or quoted like an item of a list, written item by
+    // item and stopped by the limits on the way.
+    pub(crate) fn render(&mut self, val: &Value, quoted: bool) -> String {
+        let mut out = String::new();
+        self.render_into(&mut out, val, quoted);
+        out
+    }
+
+    fn render_into(&mut self, out: &mut String, val: &Value, quoted: bool) -> bool {
+        if !self.visit() { return false; }
+        match val {
+            Value::List(items) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { out.push(' '); }
+                    if !self.render_into(out, item, true) { return false; }
+                }
+                out.push(']');
+            }
+            Value::Text(t) if quoted => {
+                out.push('"');
+                out.push_str(t);
+                out.push('"');
+            }
+            _ => out.push_str(&val.to_string()),
+        }
+        match self.limits.max_value_size {
+            Some(max) if out.len() > max => {
+                self.exceed(Limit::Size, format!("made a text longer than {} bytes", max))
+            }
+            _ => true,
+        }
+    }
+
+    // Whether the values are equal, counting each item compared with `visit`.
+    // Lists that are one and the same are equal without looking.
+    pub(crate) fn equal(&mut self, a: &Value, b: &Value) -> bool {
+        if !self.visit() { return false; }
+        match (a, b) {
+            (Value::List(xs), Value::List(ys)) => {
+                Rc::ptr_eq(xs, ys) || xs.len() == ys.len() && xs.iter().zip(ys.iter()).all(|(x, y)| self.equal(x, y))
+            }
+            (Value::Number(x), Value::Number(y)) => x == y,
+            _ => a == b,
+        }
+    }
+
+    // Passes `val` on, after stopping the program if it is too big.
+    pub(crate) fn sized(&mut self, val: Value) -> Value {
+        let size = match &val {
+            Value::Text(s) => s.len(),
+            Value::List(items) => items.len(),
+            _ => 0,
+        };
+        if let Some(max) = self.limits.max_value_size && size > max {
+            let what = if matches!(val, Value::Text(_)) { "text" } else { "list" };
+            self.exceed(Limit::Size, format!("made a {} of size {}, more than {}", what, size, max));
+        }
+        val
+    }
+
+    // Before `+?` reads anything.
+    pub(crate) fn input_allowed(&mut self) -> bool {
+        self.limits.allow_input || self.exceed(Limit::Input, "`+?` input is disabled".to_string())
+    }
+
+    fn exceed(&mut self, limit: Limit, message: String) -> bool {
+        if self.error.is_none() {
+            self.error = Some(RuntimeError { line: self.line, message, limit: Some(limit) });
+        }
+        false
+    }
+}
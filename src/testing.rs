@@ -0,0 +1,177 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::console::{BufferConsole, StdConsole};
+use crate::{parse_program, Error, Interpreter, Statement, Stmt, Value};
+
+// --- TESTING ---
+//
+// `==` and `?!` stop the program with an error when a check fails. `lazy test`
+// finds every `*_test.lazy` file, and runs each function in it whose name
+// starts with `test_` in a fresh interpreter, so one failing test can't
+// leave anything behind for the next.
+
+const TEST_PREFIX: &str = "test_";
+const MAX_DIFFERENCES: usize = 10;
+
+impl Interpreter {
+    pub(crate) fn assert_equal(&mut self, actual: &Value, expected: &Value) {
+        if self.same(actual, expected) || self.error.is_some() { return; }
+        let mut message = format!(
+            "assertion failed: values are not equal\n  expected: {}\n  actual:   {}",
+            self.repr(expected), self.repr(actual));
+        let mut diffs = Vec::new();
+        self.differences(expected, actual, String::new(), &mut diffs);
+        for diff in diffs.iter().take(MAX_DIFFERENCES) {
+            message.push_str("\n  ");
+            message.push_str(diff);
+        }
+        if diffs.len() > MAX_DIFFERENCES {
+            message.push_str(&format!("\n  ...and {} more", diffs.len() - MAX_DIFFERENCES));
+        }
+        self.fail(message);
+    }
+
+    // Equality as `==` sees it: numbers within rounding error, lists item by
+    // item.
+    fn same(&mut self, a: &Value, b: &Value) -> bool {
+        if !self.visit() { return false; }
+        match (a, b) {
+            (Value::Number(x), Value::Number(y)) => (x - y).abs() < f64::EPSILON || x == y,
+            (Value::List(xs), Value::List(ys)) => {
+                Rc::ptr_eq(xs, ys) || xs.len() == ys.len() && xs.iter().zip(ys.iter()).all(|(x, y)| self.same(x, y))
+            }
+            _ => a == b,
+        }
+    }
+
+    // `Value::repr`, written with the limits on.
+    fn repr(&mut self, val: &Value) -> String {
+        match val {
+            Value::Text(_) | Value::List(_) => self.render(val, true),
+            _ => val.repr(),
+        }
+    }
+
+    // Where two values differ, as lines like `[2]: expected 3, got 4`. Values
+    // that aren't lists or text get no extra lines, the expected/actual pair
+    // says it all.
+    fn differences(&mut self, expected: &Value, actual: &Value, path: String, out: &mut Vec<String>) {
+        match (expected, actual) {
+            (Value::List(xs), Value::List(ys)) => {
+                if xs.len() != ys.len() {
+                    out.push(format!("{}: expected {} item(s), got {}", display_path(&path), xs.len(), ys.len()));
+                }
+                for i in 0..xs.len().max(ys.len()) {
+                    if !self.visit() { return; }
+                    let item_path = format!("{}[{}]", path, i);
+                    match (xs.get(i), ys.get(i)) {
+                        (Some(x), Some(y)) if !self.same(x, y) => {
+                            if matches!((x, y), (Value::List(_), Value::List(_))) {
+                                self.differences(x, y, item_path, out);
+                            } else {
+                                let (x, y) = (self.repr(x), self.repr(y));
+                                out.push(format!("{}: expected {}, got {}", item_path, x, y));
+                            }
+                        }
+                        (Some(x), None) => {
+                            let x = self.repr(x);
+                            out.push(format!("{}: expected {}, missing", item_path, x));
+                        }
+                        (None, Some(y)) => {
+                            let y = self.repr(y);
+                            out.push(format!("{}: unexpected {}", item_path, y));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            (Value::Text(x), Value::Text(y)) if path.is_empty() => {
+                let at = x.chars().zip(y.chars()).take_while(|(a, b)| a == b).count();
+                // Point at the character under the `actual:` line above.
+                out.push(format!("          {}^ first difference at character {}", " ".repeat(at + 1), at + 1));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn display_path(path: &str) -> &str {
+    if path.is_empty() { "list" } else { path }
+}
+
+/// A `test_` function and, if it failed, why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub name: String,
+    pub failure: Option<String>,
+}
+
+/// The files `lazy test` runs: each of `roots` that is a file, and every
+/// `*_test.lazy` file under the ones that are directories, sorted.
+pub fn test_files(roots: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for root in roots {
+        if root.is_dir() {
+            find_test_files(root, &mut files);
+        } else {
+            files.push(root.clone());
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Runs each `test_` function of `file`, in file order. Fails if the file
+/// can't be read or parsed.
+pub fn run_tests(file: &Path, search_path: &[PathBuf]) -> Result<Vec<TestResult>, Error> {
+    let code = fs::read_to_string(file)?;
+    let (stmts, errors) = parse_program(&code);
+    if !errors.is_empty() {
+        return Err(Error::Parse(errors));
+    }
+    Ok(test_names(&stmts).into_iter().map(|name| {
+        let failure = run_test(file, &stmts, &name, search_path);
+        TestResult { name, failure }
+    }).collect())
+}
+
+fn find_test_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                find_test_files(&path, files);
+            }
+        } else if name.ends_with("_test.lazy") {
+            files.push(path);
+        }
+    }
+}
+
+// Top-level functions named `test_...` that take no parameters, in file order.
+fn test_names(stmts: &[Stmt]) -> Vec<String> {
+    stmts.iter().filter_map(|stmt| match &stmt.kind {
+        Statement::FunctionDef(name, params, _) | Statement::QuickFunctionDef(name, params, _)
+            if name.starts_with(TEST_PREFIX) && params.is_empty() => Some(name.clone()),
+        _ => None,
+    }).collect()
+}
+
+// Runs the file's top level, then the test. Returns the failure message, if
+// any. What the top level prints is dropped: it would be the same for every
+// test in the file.
+fn run_test(file: &Path, stmts: &[Stmt], name: &str, search_path: &[PathBuf]) -> Option<String> {
+    let mut interp = Interpreter::new();
+    interp.search_path = search_path.to_vec();
+    interp.loading.extend(fs::canonicalize(file));
+    interp.set_console(BufferConsole::new());
+    if let Err(err) = interp.run(stmts) {
+        return Some(err.to_string());
+    }
+    interp.set_console(StdConsole);
+    interp.call(name, Vec::new()).err().map(|err| err.to_string())
+}
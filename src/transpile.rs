@@ -0,0 +1,73 @@
+use crate::{parse, Error, Interpreter, ParseError, Statement, Stmt};
+
+mod c;
+mod js;
+
+// --- TRANSPILER ---
+//
+// `lazy transpile --target js program.lazy` turns a program into a
+// JavaScript file that runs on its own, in Node or a browser, and prints what
+// `lazy program.lazy` would. The output is the program, statement for
+// statement, after the runtime in `transpile/runtime.js`, which does what the
+// interpreter does for values, operators and builtins. `--target c` does the
+// same with `transpile/runtime.c`, for a file any C compiler builds.
+//
+// Programs that use `<-` can't be transpiled: they would need their modules
+// in the same file.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Js,
+    C,
+}
+
+impl Target {
+    pub fn from_name(name: &str) -> Option<Target> {
+        match name {
+            "js" => Some(Target::Js),
+            "c" => Some(Target::C),
+            _ => None,
+        }
+    }
+}
+
+/// Translates a program to `target`. `name` is the file it came from, for a
+/// comment at the top.
+///
+/// ```
+/// use lazy::transpile::{transpile, Target};
+///
+/// let js = transpile("total = 2 + 3\ntotal", "sum.lazy", Target::Js).unwrap();
+/// assert!(js.contains("set($, \"total\", add(2, 3));"));
+/// assert!(js.contains("print($.total);"));
+/// ```
+pub fn transpile(code: &str, name: &str, target: Target) -> Result<String, Error> {
+    let stmts = parse(code)?;
+    let mut errors = Interpreter::new().undefined_names(&stmts);
+    unsupported(&stmts, &mut errors);
+    if target == Target::C {
+        c::unsupported(&stmts, &mut errors);
+    }
+    if !errors.is_empty() {
+        errors.sort_by_key(|e| e.line);
+        return Err(Error::Parse(errors));
+    }
+    Ok(match target {
+        Target::Js => js::program(&stmts, name),
+        Target::C => c::program(&stmts, name),
+    })
+}
+
+fn unsupported(stmts: &[Stmt], errors: &mut Vec<ParseError>) {
+    for stmt in stmts {
+        if let Statement::Import(path, _) = &stmt.kind {
+            errors.push(ParseError {
+                line: stmt.line,
+                message: format!("can't transpile the import of \"{}\", only single-file programs", path),
+            });
+        }
+        for block in stmt.blocks() {
+            unsupported(block, errors);
+        }
+    }
+}
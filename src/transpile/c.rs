@@ -0,0 +1,693 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::pattern::{param_names, show_params, Pattern, Vars};
+use crate::{Child, Expr, ParseError, Statement, Stmt, BUILTINS};
+
+// --- C ---
+//
+// Each Lazy function becomes a C function without parameters, which finds
+// its arguments in the frame `invoke` pushed for it, and the program becomes
+// `program`. Variables are numbered, `S_name`, and looked up when they are
+// read, since a function sees whatever its caller has.
+//
+// C works out the arguments of a call in any order, so when one of them can
+// change a variable or read input, the ones before it are worked out into
+// temporaries first, and the statement comes after them.
+
+const RUNTIME: &str = include_str!("runtime.c");
+
+pub(crate) fn program(stmts: &[Stmt], name: &str) -> String {
+    let mut c = C::default();
+    c.symbols(stmts);
+    c.body = Body::default();
+    c.block(stmts);
+    let main = std::mem::take(&mut c.body.out);
+
+    let mut out = format!(
+        "/* {} as C, from `lazy transpile --target c`. Build it with `cc -O2 program.c -lm`. */\n\n{}\n",
+        name, RUNTIME,
+    );
+    out.push_str("enum {\n");
+    for ident in &c.idents {
+        out.push_str(&format!("    {},\n", ident));
+    }
+    out.push_str("    SYMBOLS\n};\n\n");
+    for decl in &c.decls {
+        out.push_str(decl);
+    }
+    for def in &c.defs {
+        out.push('\n');
+        out.push_str(def);
+    }
+    out.push_str(&format!(
+        "\nstatic Value program(void) {{\n{}    return none();\n}}\n\nint main(void) {{\n    return run(SYMBOLS, program);\n}}\n",
+        main,
+    ));
+    out
+}
+
+// A builtin with the wrong number of arguments is for the linter to find,
+// but the C builtins take exactly theirs.
+pub(crate) fn unsupported(stmts: &[Stmt], errors: &mut Vec<ParseError>) {
+    for stmt in stmts {
+        if let Statement::FunctionCall(name, args, _) = &stmt.kind {
+            arity(name, args.len(), stmt.line, errors);
+        }
+        for child in stmt.children() {
+            match child {
+                Child::Expr(expr, line) => expr_arity(expr, line, errors),
+                Child::Block(block) => unsupported(block, errors),
+            }
+        }
+    }
+}
+
+fn expr_arity(expr: &Expr, line: usize, errors: &mut Vec<ParseError>) {
+    if let Expr::FunctionCall(name, args, _) = expr {
+        arity(name, args.len(), line, errors);
+    }
+    expr.children().into_iter().for_each(|e| expr_arity(e, line, errors));
+}
+
+fn arity(name: &str, count: usize, line: usize, errors: &mut Vec<ParseError>) {
+    if let Some(b) = BUILTINS.iter().find(|b| b.symbol == name)
+        && (count < b.min_args || count > b.max_args)
+    {
+        errors.push(ParseError {
+            line,
+            message: format!("can't transpile `{}` with {} argument(s) to C: {}", name, count, b.usage),
+        });
+    }
+}
+
+#[derive(Default)]
+struct C {
+    // `S_name` for each variable, in the order they first appear.
+    symbols: HashMap<String, String>,
+    idents: Vec<String>,
+    // Names of the C functions and `Func`s made so far.
+    used: HashSet<String>,
+    // A prototype and `Func` for each Lazy function and the tables of the
+    // `?>` patterns and of what lists are unpacked into, then the definitions.
+    decls: Vec<String>,
+    patterns: usize,
+    unpacks: usize,
+    defs: Vec<String>,
+    body: Body,
+}
+
+// The C function being written.
+#[derive(Default)]
+struct Body {
+    out: String,
+    depth: usize,
+    temps: usize,
+    // Temporaries the next statement needs, declared before it.
+    pending: Vec<String>,
+    // What to do on `->` for the blocks around: let go of the list of a `>>`
+    // loop, give back what a `?>` arm bound.
+    cleanups: Vec<String>,
+    in_function: bool,
+    // Of the statement being written, for errors from `==` and `?!`.
+    line: usize,
+}
+
+impl C {
+    fn emit(&mut self, code: &str) {
+        self.body.out.push_str(&"    ".repeat(self.body.depth + 1));
+        self.body.out.push_str(code);
+        self.body.out.push('\n');
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+    }
+
+    fn nested(&mut self, stmts: &[Stmt]) {
+        self.body.depth += 1;
+        self.block(stmts);
+        self.body.depth -= 1;
+    }
+
+    fn temp(&mut self, code: String) -> String {
+        self.body.temps += 1;
+        let name = format!("t{}", self.body.temps);
+        self.body.pending.push(format!("Value {} = {};", name, code));
+        name
+    }
+
+    fn flush(&mut self) {
+        for code in std::mem::take(&mut self.body.pending) {
+            self.emit(&code);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.body.line = stmt.line;
+        match &stmt.kind {
+            Statement::Print(expr) => {
+                let code = format!("print({});", self.expr(expr));
+                self.flush();
+                self.emit(&code);
+            }
+            Statement::Assign(vars, expr) => {
+                let value = self.expr(expr);
+                let code = self.assign(vars, value);
+                self.flush();
+                self.emit(&code);
+            }
+            // Nothing stays nothing, without working out the right side.
+            Statement::AugAssign(name, op, expr) => {
+                let sym = self.sym(name);
+                self.emit(&format!("if (has({})) {{", sym));
+                self.body.depth += 1;
+                // The value is read before the right side is worked out.
+                let (left, right) = if impure(expr) {
+                    let left = self.temp(format!("get({})", sym));
+                    let right = self.expr(expr);
+                    (left, self.temp(right))
+                } else {
+                    let right = self.expr(expr);
+                    let right = if constant(expr) { right } else { self.temp(right) };
+                    (format!("take({})", sym), right)
+                };
+                self.flush();
+                let func = if op == "+" { "append" } else { operator(op) };
+                self.emit(&format!("set({}, {}({}, {}));", sym, func, left, right));
+                self.body.depth -= 1;
+                self.emit("}");
+            }
+            Statement::IncDec(name, op) => {
+                let sym = self.sym(name);
+                self.emit(&format!("set({}, {}(get({}), num(1)));", sym, operator(&op[..1]), sym));
+            }
+            Statement::If(cond, then_block, else_ifs, else_block) => {
+                let code = format!("if (truth({})) {{", self.expr(cond));
+                self.flush();
+                self.emit(&code);
+                self.nested(then_block);
+                // A condition that needs temporaries goes in an `else` block
+                // of its own.
+                let mut opened = 0;
+                for (cond, block) in else_ifs {
+                    let cond = self.expr(cond);
+                    if self.body.pending.is_empty() {
+                        self.emit(&format!("}} else if (truth({})) {{", cond));
+                    } else {
+                        self.emit("} else {");
+                        self.body.depth += 1;
+                        opened += 1;
+                        self.flush();
+                        self.emit(&format!("if (truth({})) {{", cond));
+                    }
+                    self.nested(block);
+                }
+                if !else_block.is_empty() {
+                    self.emit("} else {");
+                    self.nested(else_block);
+                }
+                self.emit("}");
+                for _ in 0..opened {
+                    self.body.depth -= 1;
+                    self.emit("}");
+                }
+            }
+            Statement::While(cond, body) => {
+                let cond = self.expr(cond);
+                if self.body.pending.is_empty() {
+                    self.emit(&format!("while (truth({})) {{", cond));
+                } else {
+                    self.emit("for (;;) {");
+                    self.body.depth += 1;
+                    self.flush();
+                    self.emit(&format!("if (!truth({})) break;", cond));
+                    self.body.depth -= 1;
+                }
+                self.nested(body);
+                self.emit("}");
+            }
+            Statement::For(vars, list, body) => {
+                let list = self.expr(list);
+                let items = self.temp(format!("each({})", list));
+                self.flush();
+                let i = format!("i{}", &items[1..]);
+                self.emit(&format!("for (size_t {i} = 0; {i} < length({items}); {i}++) {{"));
+                let code = self.assign(vars, format!("item({}, {})", items, i));
+                self.body.depth += 1;
+                self.emit(&code);
+                self.body.depth -= 1;
+                self.body.cleanups.push(format!("drop({});", items));
+                self.nested(body);
+                self.body.cleanups.pop();
+                self.emit("}");
+                self.emit(&format!("drop({});", items));
+            }
+            // Works out which arm runs first, then runs it, and gives back
+            // what its pattern bound after it.
+            Statement::Match(subject, arms) => {
+                let subject = self.expr(subject);
+                let subject = self.temp(subject);
+                self.flush();
+                let n = &subject[1..];
+                self.emit(&format!("size_t m{} = binding();", n));
+                self.emit(&format!("int arm{} = -1;", n));
+                for (i, arm) in arms.iter().enumerate() {
+                    let table = self.pattern_table(std::slice::from_ref(&arm.pattern));
+                    let Some(guard) = &arm.guard else {
+                        self.emit(&format!("if (arm{n} < 0 && match({subject}, {table})) arm{n} = {i};"));
+                        continue;
+                    };
+                    self.emit(&format!("if (arm{n} < 0 && match({subject}, {table})) {{"));
+                    self.body.depth += 1;
+                    let guard = self.expr(guard);
+                    self.flush();
+                    self.emit(&format!("if (truth({guard})) arm{n} = {i};"));
+                    self.emit(&format!("else unbind(m{n});"));
+                    self.body.depth -= 1;
+                    self.emit("}");
+                }
+                self.emit(&format!("if (arm{n} < 0) no_match({}, {subject});", stmt.line));
+                self.emit(&format!("drop({});", subject));
+                self.body.cleanups.push(format!("unbind(m{});", n));
+                for (i, arm) in arms.iter().enumerate() {
+                    let code = format!("{}if (arm{} == {}) {{", if i == 0 { "" } else { "} else " }, n, i);
+                    self.emit(&code);
+                    self.nested(&arm.body);
+                }
+                if !arms.is_empty() { self.emit("}"); }
+                self.body.cleanups.pop();
+                self.emit(&format!("unbind(m{});", n));
+            }
+            Statement::FunctionDef(name, params, body) => {
+                let func = self.function(name, params, |c| {
+                    c.block(body);
+                    if !matches!(body.last().map(|s| &s.kind), Some(Statement::Return(_))) {
+                        c.emit("return none();");
+                    }
+                });
+                let code = format!("set({}, func(&{}));", self.sym(name), func);
+                self.emit(&code);
+            }
+            Statement::QuickFunctionDef(name, params, expr) => {
+                let func = self.function(name, params, |c| c.ret(expr));
+                let code = format!("set({}, func(&{}));", self.sym(name), func);
+                self.emit(&code);
+            }
+            Statement::FunctionCall(name, args, mutates) => {
+                let code = match args.first() {
+                    Some(Expr::Variable(target)) if *mutates => self.mutating(name, target, args),
+                    _ => format!("drop({});", self.call(name, args)),
+                };
+                self.flush();
+                self.emit(&code);
+            }
+            Statement::Return(expr) => self.ret(expr),
+            Statement::Input(names, prompt, numbered) => {
+                let prompt = prompt.as_deref().unwrap_or("+? ");
+                for (i, name) in names.iter().enumerate() {
+                    let question = if *numbered { prompt.replace("{?}", &(i + 1).to_string()) } else { prompt.to_string() };
+                    let code = format!("set({}, answer({}));", self.sym(name), literal(&question));
+                    self.emit(&code);
+                }
+            }
+            // `transpile` turns these down before getting here.
+            Statement::Import(_, _) => {}
+            Statement::Comment(comment) => {
+                // A `\` at the end would carry the comment on to the next line.
+                self.emit(&format!("//{}", comment.trim_end_matches(['\\', ' '])));
+            }
+        }
+    }
+
+    // Writes a Lazy function as a C function, with `write` making the body,
+    // and returns the name of its `Func`.
+    fn function(&mut self, name: &str, params: &[Vars], write: impl FnOnce(&mut C)) -> String {
+        let base = ident(name);
+        let mut n = 1;
+        let mut body_name = format!("f_{}", base);
+        while !self.used.insert(body_name.clone()) {
+            n += 1;
+            body_name = format!("f_{}_{}", base, n);
+        }
+        let func_name = format!("F{}", &body_name[1..]);
+        let syms: Vec<String> = params.iter()
+            .map(|param| match param {
+                Vars::One(name) => self.sym(name),
+                Vars::Unpack(..) => "-1".to_string(),
+            })
+            .collect();
+        let names = literal(&show_params(params));
+        let decl = if params.iter().any(|param| matches!(param, Vars::Unpack(..))) {
+            let entries: Vec<String> = params.iter()
+                .map(|param| match param {
+                    Vars::One(_) => "{-1, NULL, -1}".to_string(),
+                    Vars::Unpack(..) => self.unpack_entry(param),
+                })
+                .collect();
+            format!(
+                "static Value {b}(void);\nstatic const int P{p}[] = {{{}}};\nstatic const Unpack U{p}[] = {{{}}};\nstatic const Func {f} = {{{}, P{p}, {}, {b}, U{p}}};\n",
+                syms.join(", "), entries.join(", "), params.len(), names, b = body_name, f = func_name, p = &body_name[1..],
+            )
+        } else if params.is_empty() {
+            format!(
+                "static Value {}(void);\nstatic const Func {} = {{0, NULL, {}, {}}};\n",
+                body_name, func_name, names, body_name,
+            )
+        } else {
+            format!(
+                "static Value {b}(void);\nstatic const int P{p}[] = {{{}}};\nstatic const Func {f} = {{{}, P{p}, {}, {b}}};\n",
+                syms.join(", "), params.len(), names, b = body_name, f = func_name, p = &body_name[1..],
+            )
+        };
+        self.decls.push(decl);
+
+        let outer = std::mem::replace(&mut self.body, Body { in_function: true, ..Body::default() });
+        write(self);
+        let inner = std::mem::replace(&mut self.body, outer);
+        self.defs.push(format!("static Value {}(void) {{\n{}}}\n", body_name, inner.out));
+        func_name
+    }
+
+    // `-> expr`: a call to a user function in a function is a tail call, made
+    // by `invoke` without a new frame.
+    fn ret(&mut self, expr: &Expr) {
+        let value = match expr {
+            Expr::FunctionCall(name, args, false) if self.body.in_function && !is_builtin(name) => {
+                let args = self.values(args);
+                format!("tail({}, {})", self.sym(name), arguments(&args))
+            }
+            _ => self.expr(expr),
+        };
+        if self.body.cleanups.is_empty() {
+            self.flush();
+            self.emit(&format!("return {};", value));
+            return;
+        }
+        let result = self.temp(value);
+        self.flush();
+        for cleanup in self.body.cleanups.clone().iter().rev() {
+            self.emit(cleanup);
+        }
+        self.emit(&format!("return {};", result));
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        match expr {
+            Expr::Number(n) => format!("num({})", number(*n)),
+            Expr::Text(s) => format!("str({}, {})", literal(s), s.len()),
+            Expr::Bool(b) => format!("boolean({})", *b as u8),
+            Expr::Variable(name) => format!("get({})", self.sym(name)),
+            Expr::List(items) if items.is_empty() => "list(0, NULL)".to_string(),
+            Expr::List(items) => {
+                let items = self.values(items);
+                format!("list({}, (Value[]){{{}}})", items.len(), items.join(", "))
+            }
+            Expr::Index(list, index) => {
+                let args = self.values(&[(**list).clone(), (**index).clone()]);
+                format!("at({}, {})", args[0], args[1])
+            }
+            Expr::BinaryOp(left, op, right) => {
+                let args = self.values(&[(**left).clone(), (**right).clone()]);
+                format!("{}({}, {})", operator(op), args[0], args[1])
+            }
+            Expr::FunctionCall(name, args, true) if matches!(args.first(), Some(Expr::Variable(_))) => {
+                let call = self.call(name, args);
+                let Some(Expr::Variable(target)) = args.first() else { unreachable!() };
+                format!("stored({}, {})", self.sym(target), call)
+            }
+            Expr::FunctionCall(name, args, _) => self.call(name, args),
+            Expr::Input => "input()".to_string(),
+        }
+    }
+
+    // The values of `exprs`, worked out left to right.
+    fn values(&mut self, exprs: &[Expr]) -> Vec<String> {
+        let ordered = exprs.iter().any(impure);
+        let last = exprs.len().saturating_sub(1);
+        let mut out = Vec::new();
+        for (i, expr) in exprs.iter().enumerate() {
+            let code = self.expr(expr);
+            out.push(if ordered && i < last && !constant(expr) { self.temp(code) } else { code });
+        }
+        out
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr]) -> String {
+        let args = self.values(args);
+        self.apply(name, &args)
+    }
+
+    fn apply(&mut self, name: &str, args: &[String]) -> String {
+        let line = self.body.line;
+        let a = |i: usize| args[i].clone();
+        match name {
+            "#" => format!("len({})", a(0)),
+            "$" => format!("to_text({})", a(0)),
+            "~" => format!("to_number({})", a(0)),
+            "!" => format!("not({})", a(0)),
+            "?=" => format!("random_below({})", a(0)),
+            "^" => format!("push({}, {})", a(0), a(1)),
+            "v" => format!("pop({})", a(0)),
+            "<>" => format!("reverse({})", a(0)),
+            "><" => format!("contains({}, {})", a(0), a(1)),
+            "<<" => format!("unique({})", a(0)),
+            "&" => format!("join({}, {})", a(0), a(1)),
+            "|" => format!("split({}, {})", a(0), a(1)),
+            "++" | "--" => {
+                let by = args.get(1).cloned().unwrap_or_else(|| "none()".to_string());
+                format!("sort({}, {}, {})", a(0), by, (name == "--") as u8)
+            }
+            "==" => format!("assert_equal({}, {}, {})", line, a(0), a(1)),
+            "?!" if args.len() < 2 => format!("check({}, {})", line, a(0)),
+            "?!" => format!("check_message({}, {}, {})", line, a(0), a(1)),
+            _ => format!("call({}, {})", self.sym(name), arguments(args)),
+        }
+    }
+
+    // `f*(x ...)` stores the result back into `x`. Builtins that never run
+    // Lazy code take `x`'s value from it while they work, so they can change
+    // it in place, once the other arguments are worked out.
+    fn mutating(&mut self, name: &str, target: &str, args: &[Expr]) -> String {
+        let sym = self.sym(target);
+        let in_place = is_builtin(name) && !(matches!(name, "++" | "--") && args.len() > 1);
+        if !in_place || args[1..].iter().any(impure) {
+            return format!("set({}, {});", sym, self.call(name, args));
+        }
+        let mut values = vec![format!("take({})", sym)];
+        for arg in &args[1..] {
+            let code = self.expr(arg);
+            values.push(if constant(arg) { code } else { self.temp(code) });
+        }
+        format!("set({}, {});", sym, self.apply(name, &values))
+    }
+
+    // `set` for one name, `unpack` for several.
+    fn assign(&mut self, vars: &Vars, value: String) -> String {
+        match vars {
+            Vars::One(name) => format!("set({}, {});", self.sym(name), value),
+            Vars::Unpack(..) => {
+                let entry = self.unpack_entry(vars);
+                self.unpacks += 1;
+                let name = format!("U{}", self.unpacks);
+                self.decls.push(format!("static const Unpack {} = {};\n", name, entry));
+                format!("unpack({}, &{}, 0);", value, name)
+            }
+        }
+    }
+
+    // `{count, names, rest}`, with the table of names it needs before it.
+    fn unpack_entry(&mut self, vars: &Vars) -> String {
+        let Vars::Unpack(names, rest) = vars else { return "{-1, NULL, -1}".to_string() };
+        let syms: Vec<String> = names.iter().map(|name| self.sym(name)).collect();
+        let rest = rest.as_ref().map_or("-1".to_string(), |rest| self.sym(rest));
+        if syms.is_empty() {
+            return format!("{{0, NULL, {}}}", rest);
+        }
+        self.unpacks += 1;
+        let table = format!("N{}", self.unpacks);
+        self.decls.push(format!("static const int {}[] = {{{}}};\n", table, syms.join(", ")));
+        format!("{{{}, {}, {}}}", syms.len(), table, rest)
+    }
+
+    // A table of patterns for `match`, with the tables their lists need
+    // before it. Returns its name.
+    fn pattern_table(&mut self, patterns: &[Pattern]) -> String {
+        let entries: Vec<String> = patterns.iter().map(|p| self.pattern_entry(p)).collect();
+        self.patterns += 1;
+        let name = format!("M{}", self.patterns);
+        self.decls.push(format!("static const Pattern {}[] = {{\n    {},\n}};\n", name, entries.join(",\n    ")));
+        name
+    }
+
+    fn pattern_entry(&mut self, pattern: &Pattern) -> String {
+        let bind = |c: &mut C, name: Option<&String>| name.map_or("-1".to_string(), |name| c.sym(name));
+        match pattern {
+            Pattern::Any => "{MATCH_ANY, -1}".to_string(),
+            Pattern::Bind(name) => format!("{{MATCH_ANY, {}}}", bind(self, Some(name))),
+            Pattern::Number(n) => format!("{{MATCH_IS, -1, NUMBER, {}}}", number(*n)),
+            Pattern::Bool(b) => format!("{{MATCH_IS, -1, BOOL, {}}}", *b as u8),
+            Pattern::Text(s) => format!("{{MATCH_IS, -1, TEXT, 0, {}, {}}}", literal(s), s.len()),
+            Pattern::Type(name, type_name) => {
+                let tag = match type_name.as_str() {
+                    "number" => "NUMBER",
+                    "text" => "TEXT",
+                    "bool" => "BOOL",
+                    "list" => "LIST",
+                    "function" => "FUNC",
+                    _ => "NOTHING",
+                };
+                format!("{{MATCH_TYPE, {}, {}}}", bind(self, name.as_ref()), tag)
+            }
+            Pattern::List(items, rest) => {
+                let table = if items.is_empty() { "NULL".to_string() } else { self.pattern_table(items) };
+                let rest = match rest {
+                    Some(rest) => self.pattern_table(std::slice::from_ref(rest)),
+                    None => "NULL".to_string(),
+                };
+                format!("{{MATCH_ITEMS, -1, LIST, 0, NULL, 0, {}, {}, {}}}", items.len(), table, rest)
+            }
+        }
+    }
+
+    fn sym(&mut self, name: &str) -> String {
+        self.symbols.get(name).cloned().unwrap_or_else(|| {
+            let base = format!("S_{}", ident(name));
+            let mut sym = base.clone();
+            while self.idents.contains(&sym) {
+                sym = format!("{}_{}", base, self.idents.len());
+            }
+            self.symbols.insert(name.to_string(), sym.clone());
+            self.idents.push(sym.clone());
+            sym
+        })
+    }
+
+    // Numbers every variable up front, in the order they appear.
+    fn symbols(&mut self, stmts: &[Stmt]) {
+        let mut names = Vec::new();
+        names_in(stmts, &mut names);
+        for name in names {
+            self.sym(&name);
+        }
+    }
+}
+
+fn names_in(stmts: &[Stmt], out: &mut Vec<String>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            Statement::Assign(vars, _) | Statement::For(vars, _, _) => out.extend(vars.names().into_iter().map(String::from)),
+            Statement::AugAssign(name, _, _) | Statement::IncDec(name, _) => out.push(name.to_string()),
+            Statement::Match(_, arms) => {
+                for arm in arms {
+                    out.extend(arm.pattern.names().into_iter().map(String::from));
+                }
+            }
+            Statement::FunctionDef(name, params, _) | Statement::QuickFunctionDef(name, params, _) => {
+                out.push(name.clone());
+                out.extend(param_names(params));
+            }
+            Statement::FunctionCall(name, _, _) if !is_builtin(name) => out.push(name.clone()),
+            Statement::Input(names, _, _) => out.extend(names.iter().cloned()),
+            _ => {}
+        }
+        for child in stmt.children() {
+            match child {
+                Child::Expr(expr, _) => expr_names(expr, out),
+                Child::Block(block) => names_in(block, out),
+            }
+        }
+    }
+}
+
+fn expr_names(expr: &Expr, out: &mut Vec<String>) {
+    match expr {
+        Expr::Variable(name) => out.push(name.to_string()),
+        Expr::FunctionCall(name, _, _) if !is_builtin(name) => out.push(name.clone()),
+        _ => {}
+    }
+    expr.children().into_iter().for_each(|e| expr_names(e, out));
+}
+
+fn is_builtin(name: &str) -> bool {
+    BUILTINS.iter().any(|b| b.symbol == name)
+}
+
+// Whether working out `expr` can change a variable, read input or stop the
+// program, so that what comes before it has to be worked out first.
+fn impure(expr: &Expr) -> bool {
+    match expr {
+        Expr::FunctionCall(name, args, mutates)
+            if *mutates
+                || !is_builtin(name)
+                || matches!(name.as_str(), "?=" | "==" | "?!")
+                || (matches!(name.as_str(), "++" | "--") && args.len() > 1) => true,
+        Expr::Input => true,
+        _ => expr.children().into_iter().any(impure),
+    }
+}
+
+fn constant(expr: &Expr) -> bool {
+    matches!(expr, Expr::Number(_) | Expr::Text(_) | Expr::Bool(_))
+}
+
+fn arguments(args: &[String]) -> String {
+    match args.len() {
+        0 => "0, NULL".to_string(),
+        n => format!("{}, (Value[]){{{}}}", n, args.join(", ")),
+    }
+}
+
+fn operator(op: &str) -> &'static str {
+    match op {
+        "+" => "add",
+        "-" => "sub",
+        "*" => "mul",
+        "/" => "divide",
+        "%" => "modulo",
+        ">" => "gt",
+        "<" => "lt",
+        ">=" => "ge",
+        "<=" => "le",
+        "==" => "eq",
+        "!=" => "ne",
+        _ => "no_op",
+    }
+}
+
+// A C name made from a Lazy one.
+fn ident(name: &str) -> String {
+    let ident: String = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+    if ident.is_empty() { "_".to_string() } else { ident }
+}
+
+// A C double. Big whole numbers need a point, or they would be integers too
+// big for any C type.
+fn number(n: f64) -> String {
+    if n.is_nan() { return "NAN".to_string(); }
+    if n.is_infinite() { return if n > 0.0 { "INFINITY" } else { "-INFINITY" }.to_string(); }
+    let s = n.to_string();
+    if !s.contains('.') && (n.abs() >= 1e15 || (n == 0.0 && n.is_sign_negative())) { s + ".0" } else { s }
+}
+
+// A C string literal. `??` is escaped so it can't start a trigraph.
+fn literal(s: &str) -> String {
+    let mut out = String::from("\"");
+    let mut previous = ' ';
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '?' if previous == '?' => out.push_str("\\?"),
+            c if c.is_ascii_control() => out.push_str(&format!("\\{:03o}", c as u8)),
+            c => out.push(c),
+        }
+        previous = c;
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,335 @@
+use crate::pattern::{Pattern, Vars};
+use crate::{Expr, Statement, Stmt, BUILTINS};
+
+// --- JAVASCRIPT ---
+//
+// Each statement becomes one JavaScript statement. Variables are properties
+// of `$`, the scope of the running call, and every operator and builtin is a
+// runtime function, since JavaScript's own `+` and `==` mean something else.
+
+const RUNTIME: &str = include_str!("runtime.js");
+
+const NAMES: &str = "Fn, run, print, set, unpack, call, tail, each, input, read, match, unbind,\n  \
+    add, sub, mul, div, rem, gt, lt, ge, le, eq, ne, at,\n  \
+    len, text, number, not, random, push, pop, reverse, sort, contains, unique, join, split,\n  \
+    assertEqual, assert,";
+
+pub(crate) fn program(stmts: &[Stmt], name: &str) -> String {
+    let mut js = Js { out: String::new(), depth: 0, line: 0, in_function: false };
+    js.block(stmts);
+    format!(
+        "// {} as JavaScript, from `lazy transpile --target js`.\n\n{}\nconst {{\n  {}\n}} = lazy;\n\nrun(($) => {{\n{}}});\n",
+        name, RUNTIME, NAMES, js.out,
+    )
+}
+
+struct Js {
+    out: String,
+    depth: usize,
+    // Of the statement being written, for errors from `==` and `?!`.
+    line: usize,
+    in_function: bool,
+}
+
+impl Js {
+    fn emit(&mut self, code: &str) {
+        self.out.push_str(&"  ".repeat(self.depth));
+        self.out.push_str(code);
+        self.out.push('\n');
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        self.depth += 1;
+        for stmt in stmts {
+            self.stmt(stmt);
+        }
+        self.depth -= 1;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        self.line = stmt.line;
+        match &stmt.kind {
+            Statement::Print(expr) => {
+                let code = format!("print({});", self.expr(expr));
+                self.emit(&code);
+            }
+            Statement::Assign(vars, expr) => {
+                let code = format!("{};", assign_vars(vars, &self.expr(expr)));
+                self.emit(&code);
+            }
+            // Nothing stays nothing, without working out the right side.
+            Statement::AugAssign(name, op, expr) => {
+                let value = operator(op, &var(name), &self.expr(expr));
+                self.emit(&format!("if ({} != null) {};", var(name), assign(name, &value)));
+            }
+            Statement::IncDec(name, op) => {
+                let value = operator(&op[..1], &var(name), "1");
+                self.emit(&format!("{};", assign(name, &value)));
+            }
+            Statement::If(cond, then_block, else_ifs, else_block) => {
+                let code = format!("if ({} === true) {{", self.expr(cond));
+                self.emit(&code);
+                self.block(then_block);
+                for (cond, block) in else_ifs {
+                    let code = format!("}} else if ({} === true) {{", self.expr(cond));
+                    self.emit(&code);
+                    self.block(block);
+                }
+                if !else_block.is_empty() {
+                    self.emit("} else {");
+                    self.block(else_block);
+                }
+                self.emit("}");
+            }
+            Statement::While(cond, body) => {
+                let code = format!("while ({} === true) {{", self.expr(cond));
+                self.emit(&code);
+                self.block(body);
+                self.emit("}");
+            }
+            Statement::For(vars, list, body) => {
+                let code = format!("for (const item of each({})) {{", self.expr(list));
+                self.emit(&code);
+                self.depth += 1;
+                self.emit(&format!("{};", assign_vars(vars, "item")));
+                self.depth -= 1;
+                self.block(body);
+                self.emit("}");
+            }
+            // The arm's names are given back however it ends, `->` included.
+            Statement::Match(subject, arms) => {
+                let subject = self.expr(subject);
+                let patterns: Vec<String> = arms.iter().map(|arm| pattern(&arm.pattern)).collect();
+                let guards: Vec<String> = arms.iter()
+                    .map(|arm| arm.guard.as_ref().map_or("null".to_string(), |guard| format!("() => {}", self.expr(guard))))
+                    .collect();
+                self.emit("{");
+                self.depth += 1;
+                self.emit(&format!(
+                    "const [arm, saved] = match($, {}, [{}], [{}], {});",
+                    subject, patterns.join(", "), guards.join(", "), stmt.line,
+                ));
+                self.emit("try {");
+                for (i, arm) in arms.iter().enumerate() {
+                    let code = format!("{}if (arm === {}) {{", if i == 0 { "" } else { "} else " }, i);
+                    self.emit(&code);
+                    self.block(&arm.body);
+                }
+                if !arms.is_empty() { self.emit("}"); }
+                self.emit("} finally {");
+                self.emit("  unbind($, saved);");
+                self.emit("}");
+                self.depth -= 1;
+                self.emit("}");
+            }
+            Statement::FunctionDef(name, params, body) => {
+                self.emit(&format!("{} = new Fn({}, ($) => {{", var(name), params_text(params)));
+                let outer = std::mem::replace(&mut self.in_function, true);
+                self.block(body);
+                self.in_function = outer;
+                self.emit(&format!("}}{});", unpacks(params)));
+            }
+            Statement::QuickFunctionDef(name, params, expr) => {
+                let outer = std::mem::replace(&mut self.in_function, true);
+                let body = self.returned(expr);
+                self.in_function = outer;
+                self.emit(&format!("{} = new Fn({}, ($) => {}{});", var(name), params_text(params), body, unpacks(params)));
+            }
+            Statement::FunctionCall(name, args, mutates) => {
+                let code = format!("{};", self.call(name, args, *mutates));
+                self.emit(&code);
+            }
+            Statement::Return(expr) => {
+                let code = format!("return {};", self.returned(expr));
+                self.emit(&code);
+            }
+            Statement::Input(names, prompt, numbered) => {
+                let mut args = vec!["$".to_string(), texts(names)];
+                match (prompt, numbered) {
+                    (Some(prompt), true) => args.extend([text(prompt), "true".to_string()]),
+                    (Some(prompt), false) => args.push(text(prompt)),
+                    (None, true) => args.extend(["undefined".to_string(), "true".to_string()]),
+                    (None, false) => {}
+                }
+                self.emit(&format!("read({});", args.join(", ")));
+            }
+            // `transpile` turns these down before getting here.
+            Statement::Import(_, _) => {}
+            Statement::Comment(comment) => self.emit(&format!("//{}", comment)),
+        }
+    }
+
+    // What a function returns: a call to a user function in a function is a
+    // tail call, made by `call` without a new frame.
+    fn returned(&mut self, expr: &Expr) -> String {
+        match expr {
+            Expr::FunctionCall(name, args, false) if self.in_function && !is_builtin(name) => {
+                format!("tail({}, [{}])", var(name), self.exprs(args))
+            }
+            _ => self.expr(expr),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> String {
+        match expr {
+            Expr::Number(n) => format!("{}", n),
+            Expr::Text(s) => text(s),
+            Expr::Bool(b) => b.to_string(),
+            Expr::Variable(name) => var(name),
+            Expr::List(items) => format!("[{}]", self.exprs(items)),
+            Expr::Index(list, index) => format!("at({}, {})", self.expr(list), self.expr(index)),
+            Expr::BinaryOp(left, op, right) => {
+                let (left, right) = (self.expr(left), self.expr(right));
+                operator(op, &left, &right)
+            }
+            Expr::FunctionCall(name, args, mutates) => self.call(name, args, *mutates),
+            Expr::Input => "input()".to_string(),
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> String {
+        exprs.iter().map(|e| self.expr(e)).collect::<Vec<_>>().join(", ")
+    }
+
+    // `f*(x)` stores the result back into `x`.
+    fn call(&mut self, name: &str, args: &[Expr], mutates: bool) -> String {
+        let list = self.exprs(args);
+        let with = |first: &str, rest: &[&str]| {
+            let mut all = vec![first.to_string()];
+            all.extend((!list.is_empty()).then(|| list.clone()));
+            all.extend(rest.iter().map(|s| s.to_string()));
+            all.join(", ")
+        };
+        let call = match name {
+            "#" => format!("len({})", list),
+            "$" => format!("text({})", list),
+            "~" => format!("number({})", list),
+            "!" => format!("not({})", list),
+            "?=" => format!("random({})", list),
+            "^" => format!("push({})", list),
+            "v" => format!("pop({})", list),
+            "<>" => format!("reverse({})", list),
+            "><" => format!("contains({})", list),
+            "<<" => format!("unique({})", list),
+            "&" => format!("join({})", list),
+            "|" => format!("split({})", list),
+            "++" => format!("sort({})", with("$", &[])),
+            "--" if args.len() < 2 => format!("sort({})", with("$", &["null", "true"])),
+            "--" => format!("sort({})", with("$", &["true"])),
+            "==" => format!("assertEqual({})", with(&self.line.to_string(), &[])),
+            "?!" => format!("assert({})", with(&self.line.to_string(), &[])),
+            _ => format!("call($, {}, [{}])", var(name), list),
+        };
+        match args.first() {
+            Some(Expr::Variable(target)) if mutates => assign(target, &call),
+            _ => call,
+        }
+    }
+}
+
+fn is_builtin(name: &str) -> bool {
+    BUILTINS.iter().any(|b| b.symbol == name)
+}
+
+fn operator(op: &str, left: &str, right: &str) -> String {
+    let func = match op {
+        "+" => "add",
+        "-" => "sub",
+        "*" => "mul",
+        "/" => "div",
+        "%" => "rem",
+        ">" => "gt",
+        "<" => "lt",
+        ">=" => "ge",
+        "<=" => "le",
+        "==" => "eq",
+        "!=" => "ne",
+        // Still works out both sides, for their `+?`s.
+        _ => return format!("({}, {}, null)", left, right),
+    };
+    format!("{}({}, {})", func, left, right)
+}
+
+// A `?>` arm's pattern as the runtime's `matches` reads it.
+fn pattern(p: &Pattern) -> String {
+    match p {
+        Pattern::Any => "{}".to_string(),
+        Pattern::Bind(name) => format!("{{ bind: {} }}", text(name)),
+        Pattern::Number(n) => format!("{{ is: {} }}", n),
+        Pattern::Text(s) => format!("{{ is: {} }}", text(s)),
+        Pattern::Bool(b) => format!("{{ is: {} }}", b),
+        Pattern::Type(None, type_name) => format!("{{ type: {} }}", text(type_name)),
+        Pattern::Type(Some(name), type_name) => format!("{{ type: {}, bind: {} }}", text(type_name), text(name)),
+        Pattern::List(items, rest) => {
+            let items: Vec<String> = items.iter().map(pattern).collect();
+            match rest {
+                Some(rest) => format!("{{ items: [{}], rest: {} }}", items.join(", "), pattern(rest)),
+                None => format!("{{ items: [{}] }}", items.join(", ")),
+            }
+        }
+    }
+}
+
+fn var(name: &str) -> String {
+    let mut chars = name.chars();
+    let plain = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain { format!("$.{}", name) } else { format!("$[{}]", text(name)) }
+}
+
+fn assign(name: &str, value: &str) -> String {
+    format!("set($, {}, {})", text(name), value)
+}
+
+// `set` for one name, `unpack` for several.
+fn assign_vars(vars: &Vars, value: &str) -> String {
+    match vars {
+        Vars::One(name) => assign(name, value),
+        Vars::Unpack(..) => format!("unpack($, {}, {})", value, unpacked(vars)),
+    }
+}
+
+// What a list is unpacked into, as the runtime's `unpack` reads it.
+fn unpacked(vars: &Vars) -> String {
+    match vars {
+        Vars::Unpack(names, Some(rest)) => format!("{{ names: {}, rest: {} }}", texts(names), text(rest)),
+        Vars::Unpack(names, None) => format!("{{ names: {} }}", texts(names)),
+        Vars::One(_) => "null".to_string(),
+    }
+}
+
+// The third argument of `Fn` when a parameter is written `[a b]`.
+fn unpacks(params: &[Vars]) -> String {
+    if params.iter().all(|param| matches!(param, Vars::One(_))) {
+        return String::new();
+    }
+    let entries: Vec<String> = params.iter().map(unpacked).collect();
+    format!(", [{}]", entries.join(", "))
+}
+
+// The first argument of `Fn`: the parameters as they are written.
+fn params_text(params: &[Vars]) -> String {
+    texts(&params.iter().map(Vars::to_string).collect::<Vec<_>>())
+}
+
+fn texts<S: AsRef<str>>(items: &[S]) -> String {
+    format!("[{}]", items.iter().map(|s| text(s.as_ref())).collect::<Vec<_>>().join(", "))
+}
+
+// A JavaScript string literal.
+fn text(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() || c == '\u{2028}' || c == '\u{2029}' => out.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,1298 @@
+/*
+ * Lazy runtime for programs from `lazy transpile --target c`.
+ *
+ * A value is a tagged union. Text and lists are reference counted and shared
+ * between copies until one of them changes, like `Rc` in the interpreter:
+ * a change makes a copy first unless nothing else holds the value.
+ *
+ * Every function that takes a `Value` takes it over, and every `Value` a
+ * function returns belongs to the caller, who passes it on or lets go of it
+ * with `drop`. `get` reads a variable with a new reference, `set` stores one.
+ *
+ * Variables are numbered by the program (`S_name`). A call pushes a frame on
+ * `frames`, and a variable is looked up from the innermost frame out to the
+ * globals, so a function sees its caller's variables like in Lazy.
+ */
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <time.h>
+
+/* A program uses only some of these. */
+#ifdef __GNUC__
+#pragma GCC diagnostic ignored "-Wunused-function"
+#endif
+
+/* In the order `++` sorts them in. */
+typedef enum { NOTHING, BOOL, NUMBER, TEXT, LIST, FUNC, UNSET } Tag;
+
+typedef struct Text {
+    long refs;
+    size_t len, cap;
+    char *data;
+} Text;
+
+typedef struct List {
+    long refs;
+    size_t len, cap;
+    struct Value *items;
+} List;
+
+typedef struct Func Func;
+
+typedef struct Value {
+    Tag tag;
+    union {
+        int yes;
+        double num;
+        Text *text;
+        List *list;
+        const Func *func;
+    };
+} Value;
+
+/* `a b ...rest`, what `=`, `>>` or a parameter written `[a b]` unpacks a
+   list into: `count` names, then `rest`, or -1. */
+typedef struct {
+    int count;
+    const int *syms;
+    int rest;
+} Unpack;
+
+/* A function the program defines: `body` runs it in a frame that has its
+   parameters. `names` is the parameters for error messages. `unpacks`, if
+   any parameter is written `[a b]`, has an entry for each, with a count of
+   -1 for the others; `params` has -1 for the `[a b]` ones. */
+struct Func {
+    int nparams;
+    const int *params;
+    const char *names;
+    Value (*body)(void);
+    const Unpack *unpacks;
+};
+
+static void *alloc(size_t size) {
+    void *p = malloc(size ? size : 1);
+    if (!p) {
+        fputs("Error: out of memory\n", stderr);
+        exit(1);
+    }
+    return p;
+}
+
+static void *grow(void *p, size_t size) {
+    p = realloc(p, size ? size : 1);
+    if (!p) {
+        fputs("Error: out of memory\n", stderr);
+        exit(1);
+    }
+    return p;
+}
+
+/* --- VALUES --- */
+
+static Value none(void) {
+    Value v;
+    v.tag = NOTHING;
+    v.num = 0;
+    return v;
+}
+
+static Value boolean(int yes) {
+    Value v;
+    v.tag = BOOL;
+    v.yes = yes != 0;
+    return v;
+}
+
+static Value num(double n) {
+    Value v;
+    v.tag = NUMBER;
+    v.num = n;
+    return v;
+}
+
+static Value str(const char *data, size_t len) {
+    Text *t = alloc(sizeof(Text));
+    t->refs = 1;
+    t->len = t->cap = len;
+    t->data = alloc(len);
+    memcpy(t->data, data, len);
+    Value v;
+    v.tag = TEXT;
+    v.text = t;
+    return v;
+}
+
+static Value empty_list(size_t cap) {
+    List *l = alloc(sizeof(List));
+    l->refs = 1;
+    l->len = 0;
+    l->cap = cap;
+    l->items = alloc(cap * sizeof(Value));
+    Value v;
+    v.tag = LIST;
+    v.list = l;
+    return v;
+}
+
+/* `[a b c]`: takes over the `n` items. */
+static Value list(size_t n, const Value *items) {
+    Value v = empty_list(n);
+    if (n) memcpy(v.list->items, items, n * sizeof(Value));
+    v.list->len = n;
+    return v;
+}
+
+static Value func(const Func *f) {
+    Value v;
+    v.tag = FUNC;
+    v.func = f;
+    return v;
+}
+
+static Value retain(Value v) {
+    if (v.tag == TEXT) v.text->refs++;
+    if (v.tag == LIST) v.list->refs++;
+    return v;
+}
+
+static void drop(Value v) {
+    if (v.tag == TEXT && --v.text->refs == 0) {
+        free(v.text->data);
+        free(v.text);
+    }
+    if (v.tag == LIST && --v.list->refs == 0) {
+        for (size_t i = 0; i < v.list->len; i++) drop(v.list->items[i]);
+        free(v.list->items);
+        free(v.list);
+    }
+}
+
+/* The value to change in place: `v` itself if nothing else holds it. */
+static Value unshared(Value v) {
+    if (v.tag == TEXT && v.text->refs > 1) {
+        Value copy = str(v.text->data, v.text->len);
+        drop(v);
+        return copy;
+    }
+    if (v.tag == LIST && v.list->refs > 1) {
+        Value copy = empty_list(v.list->len);
+        for (size_t i = 0; i < v.list->len; i++) copy.list->items[i] = retain(v.list->items[i]);
+        copy.list->len = v.list->len;
+        drop(v);
+        return copy;
+    }
+    return v;
+}
+
+static void append_text(Text *t, const char *data, size_t len) {
+    if (t->len + len > t->cap) {
+        t->cap = (t->len + len) * 2;
+        t->data = grow(t->data, t->cap);
+    }
+    memcpy(t->data + t->len, data, len);
+    t->len += len;
+}
+
+static void append_item(List *l, Value item) {
+    if (l->len == l->cap) {
+        l->cap = l->cap ? l->cap * 2 : 4;
+        l->items = grow(l->items, l->cap * sizeof(Value));
+    }
+    l->items[l->len++] = item;
+}
+
+/* Text being put together, for `show`. */
+typedef struct {
+    char *data;
+    size_t len, cap;
+} Buf;
+
+static void put(Buf *b, const char *data, size_t len) {
+    if (b->len + len > b->cap) {
+        b->cap = (b->len + len) * 2 + 16;
+        b->data = grow(b->data, b->cap);
+    }
+    memcpy(b->data + b->len, data, len);
+    b->len += len;
+}
+
+static void puts_buf(Buf *b, const char *s) {
+    put(b, s, strlen(s));
+}
+
+static Value buf_text(Buf *b) {
+    Value v = str(b->data, b->len);
+    free(b->data);
+    return v;
+}
+
+/* A number the way Rust's `{}` shows an `f64`: the fewest digits that read
+   back as the same number, never with an exponent. */
+static void show_float(Buf *b, double n) {
+    if (isnan(n)) {
+        puts_buf(b, "NaN");
+        return;
+    }
+    if (isinf(n)) {
+        puts_buf(b, n > 0 ? "inf" : "-inf");
+        return;
+    }
+    if (n == 0) {
+        puts_buf(b, signbit(n) ? "-0" : "0");
+        return;
+    }
+    char sci[40];
+    for (int precision = 0; precision < 17; precision++) {
+        snprintf(sci, sizeof sci, "%.*e", precision, n);
+        if (strtod(sci, NULL) == n) break;
+    }
+    /* `sci` is [-]d.ddde[+-]x: collect the digits and where the point goes. */
+    char digits[24];
+    size_t count = 0;
+    const char *p = sci;
+    if (*p == '-') {
+        put(b, "-", 1);
+        p++;
+    }
+    for (; *p != 'e'; p++) {
+        if (*p != '.') digits[count++] = *p;
+    }
+    long point = strtol(p + 1, NULL, 10) + 1;
+    while (count > 1 && digits[count - 1] == '0') count--;
+    if (point <= 0) {
+        put(b, "0.", 2);
+        for (long i = 0; i < -point; i++) put(b, "0", 1);
+        put(b, digits, count);
+    } else if ((size_t)point >= count) {
+        put(b, digits, count);
+        for (size_t i = count; i < (size_t)point; i++) put(b, "0", 1);
+    } else {
+        put(b, digits, (size_t)point);
+        put(b, ".", 1);
+        put(b, digits + point, count - (size_t)point);
+    }
+}
+
+/* Whole numbers as an `i64`, which holds the biggest and smallest ones. */
+static void show_number(Buf *b, double n) {
+    if (isfinite(n) && n == trunc(n)) {
+        long long whole = n >= 9223372036854775807.0 ? INT64_MAX : n <= -9223372036854775808.0 ? INT64_MIN : (long long)n;
+        char s[24];
+        snprintf(s, sizeof s, "%lld", whole);
+        puts_buf(b, s);
+    } else {
+        show_float(b, n);
+    }
+}
+
+/* What `print` and `$` show. */
+static void show(Buf *b, Value v) {
+    switch (v.tag) {
+    case NUMBER:
+        show_number(b, v.num);
+        break;
+    case TEXT:
+        put(b, v.text->data, v.text->len);
+        break;
+    case BOOL:
+        puts_buf(b, v.yes ? "yes" : "no");
+        break;
+    case LIST:
+        put(b, "[", 1);
+        for (size_t i = 0; i < v.list->len; i++) {
+            Value item = v.list->items[i];
+            if (i) put(b, " ", 1);
+            if (item.tag == TEXT) put(b, "\"", 1);
+            show(b, item);
+            if (item.tag == TEXT) put(b, "\"", 1);
+        }
+        put(b, "]", 1);
+        break;
+    case FUNC:
+        puts_buf(b, "<function>");
+        break;
+    default:
+        break;
+    }
+}
+
+/* How a value is shown in error messages. */
+static void repr(Buf *b, Value v) {
+    if (v.tag == TEXT) {
+        put(b, "\"", 1);
+        show(b, v);
+        put(b, "\"", 1);
+    } else if (v.tag == NOTHING) {
+        puts_buf(b, "nothing");
+    } else if (v.tag == FUNC) {
+        puts_buf(b, "<function(");
+        puts_buf(b, v.func->names);
+        puts_buf(b, ")>");
+    } else {
+        show(b, v);
+    }
+}
+
+/* Strictly equal, like `><` and `<<` compare. */
+static int equal(Value a, Value b) {
+    if (a.tag != b.tag) return 0;
+    switch (a.tag) {
+    case NUMBER:
+        return a.num == b.num;
+    case TEXT:
+        return a.text->len == b.text->len && memcmp(a.text->data, b.text->data, a.text->len) == 0;
+    case BOOL:
+        return a.yes == b.yes;
+    case LIST:
+        if (a.list->len != b.list->len) return 0;
+        for (size_t i = 0; i < a.list->len; i++) {
+            if (!equal(a.list->items[i], b.list->items[i])) return 0;
+        }
+        return 1;
+    case FUNC:
+        return a.func == b.func;
+    default:
+        return 1;
+    }
+}
+
+/* Equal as `==` checks it: numbers within rounding error. */
+static int same(Value a, Value b) {
+    if (a.tag == NUMBER && b.tag == NUMBER) return fabs(a.num - b.num) < 2.220446049250313e-16 || a.num == b.num;
+    if (a.tag == LIST && b.tag == LIST) {
+        if (a.list->len != b.list->len) return 0;
+        for (size_t i = 0; i < a.list->len; i++) {
+            if (!same(a.list->items[i], b.list->items[i])) return 0;
+        }
+        return 1;
+    }
+    return equal(a, b);
+}
+
+/* Rust's `f64::total_cmp`: -NaN < -inf < ... < -0 < 0 < ... < inf < NaN. */
+static int compare_numbers(double a, double b) {
+    int64_t x, y;
+    memcpy(&x, &a, sizeof x);
+    memcpy(&y, &b, sizeof y);
+    x ^= (int64_t)((uint64_t)(x >> 63) >> 1);
+    y ^= (int64_t)((uint64_t)(y >> 63) >> 1);
+    return (x > y) - (x < y);
+}
+
+/* The order `++` and `--` sort in: nothing < booleans < numbers < text <
+   lists < functions. */
+static int compare(Value a, Value b) {
+    if (a.tag != b.tag) return a.tag < b.tag ? -1 : 1;
+    switch (a.tag) {
+    case NUMBER:
+        return compare_numbers(a.num, b.num);
+    case TEXT: {
+        size_t n = a.text->len < b.text->len ? a.text->len : b.text->len;
+        int order = n ? memcmp(a.text->data, b.text->data, n) : 0;
+        if (order) return order < 0 ? -1 : 1;
+        return (a.text->len > b.text->len) - (a.text->len < b.text->len);
+    }
+    case BOOL:
+        return a.yes - b.yes;
+    case LIST:
+        for (size_t i = 0; i < a.list->len && i < b.list->len; i++) {
+            int order = compare(a.list->items[i], b.list->items[i]);
+            if (order) return order;
+        }
+        return (a.list->len > b.list->len) - (a.list->len < b.list->len);
+    default:
+        return 0;
+    }
+}
+
+/* A number the way Rust parses an `f64`, or 0 if `s` isn't one. */
+static int parse_number(const char *s, size_t len, double *out) {
+    size_t i = 0;
+    if (i < len && (s[i] == '+' || s[i] == '-')) i++;
+    const char *words[] = {"infinity", "inf", "nan"};
+    for (int w = 0; w < 3; w++) {
+        size_t n = strlen(words[w]);
+        int match = len - i == n;
+        for (size_t j = 0; match && j < n; j++) match = (s[i + j] | 0x20) == words[w][j];
+        if (match) {
+            *out = w == 2 ? NAN : s[0] == '-' ? -INFINITY : INFINITY;
+            return 1;
+        }
+    }
+    size_t digits = 0;
+    while (i < len && s[i] >= '0' && s[i] <= '9') i++, digits++;
+    if (i < len && s[i] == '.') {
+        i++;
+        while (i < len && s[i] >= '0' && s[i] <= '9') i++, digits++;
+    }
+    if (!digits) return 0;
+    if (i < len && (s[i] == 'e' || s[i] == 'E')) {
+        i++;
+        if (i < len && (s[i] == '+' || s[i] == '-')) i++;
+        size_t exponent = 0;
+        while (i < len && s[i] >= '0' && s[i] <= '9') i++, exponent++;
+        if (!exponent) return 0;
+    }
+    if (i != len) return 0;
+    char *copy = alloc(len + 1);
+    memcpy(copy, s, len);
+    copy[len] = 0;
+    *out = strtod(copy, NULL);
+    free(copy);
+    return 1;
+}
+
+/* --- OPERATORS --- */
+
+static Value add(Value a, Value b) {
+    Value result = none();
+    if (a.tag == NUMBER && b.tag == NUMBER) {
+        result = num(a.num + b.num);
+    } else if (a.tag == TEXT && (b.tag == TEXT || b.tag == NUMBER)) {
+        Buf buf = {0};
+        put(&buf, a.text->data, a.text->len);
+        if (b.tag == TEXT) put(&buf, b.text->data, b.text->len);
+        else show_float(&buf, b.num);
+        result = buf_text(&buf);
+    } else if (a.tag == NUMBER && b.tag == TEXT) {
+        Buf buf = {0};
+        show_float(&buf, a.num);
+        put(&buf, b.text->data, b.text->len);
+        result = buf_text(&buf);
+    } else if (a.tag == LIST && b.tag == LIST) {
+        result = empty_list(a.list->len + b.list->len);
+        for (size_t i = 0; i < a.list->len; i++) append_item(result.list, retain(a.list->items[i]));
+        for (size_t i = 0; i < b.list->len; i++) append_item(result.list, retain(b.list->items[i]));
+    }
+    drop(a);
+    drop(b);
+    return result;
+}
+
+/* `x += ...`: adds to text or a list in place when `x` was the only one
+   holding it. */
+static Value append(Value a, Value b) {
+    if (a.tag == TEXT && (b.tag == TEXT || b.tag == NUMBER)) {
+        a = unshared(a);
+        if (b.tag == TEXT) {
+            append_text(a.text, b.text->data, b.text->len);
+        } else {
+            Buf buf = {0};
+            show_float(&buf, b.num);
+            append_text(a.text, buf.data, buf.len);
+            free(buf.data);
+        }
+        drop(b);
+        return a;
+    }
+    if (a.tag == LIST && b.tag == LIST) {
+        a = unshared(a);
+        for (size_t i = 0; i < b.list->len; i++) append_item(a.list, retain(b.list->items[i]));
+        drop(b);
+        return a;
+    }
+    return add(a, b);
+}
+
+#define NUMERIC(name, result)                                  \
+    static Value name(Value a, Value b) {                      \
+        if (a.tag != NUMBER || b.tag != NUMBER) {              \
+            drop(a);                                           \
+            drop(b);                                           \
+            return none();                                     \
+        }                                                      \
+        double x = a.num, y = b.num;                           \
+        return result;                                         \
+    }
+
+NUMERIC(sub, num(x - y))
+NUMERIC(mul, num(x * y))
+NUMERIC(divide, num(x / y))
+NUMERIC(modulo, num(fmod(x, y)))
+NUMERIC(gt, boolean(x > y))
+NUMERIC(lt, boolean(x < y))
+NUMERIC(ge, boolean(x >= y))
+NUMERIC(le, boolean(x <= y))
+
+/* `==` and `!=`: numbers within rounding error, text and booleans exactly,
+   and nothing for anything else. */
+static Value eq(Value a, Value b) {
+    Value result = none();
+    if (a.tag == NUMBER && b.tag == NUMBER) result = boolean(fabs(a.num - b.num) < 2.220446049250313e-16);
+    else if (a.tag == b.tag && (a.tag == TEXT || a.tag == BOOL)) result = boolean(equal(a, b));
+    drop(a);
+    drop(b);
+    return result;
+}
+
+static Value ne(Value a, Value b) {
+    Value result = eq(a, b);
+    if (result.tag == BOOL) result.yes = !result.yes;
+    return result;
+}
+
+/* An operator that isn't one: still works out both sides. */
+static Value no_op(Value a, Value b) {
+    drop(a);
+    drop(b);
+    return none();
+}
+
+/* `list[i]`, counting from the end for negative `i`. */
+static Value at(Value list, Value index) {
+    Value result = none();
+    if (list.tag == LIST && index.tag == NUMBER) {
+        double n = index.num;
+        long long i = isnan(n) ? 0 : n >= 9223372036854775807.0 ? INT64_MAX : n <= -9223372036854775808.0 ? INT64_MIN : (long long)n;
+        if (i < 0) i += (long long)list.list->len;
+        if (i >= 0 && (size_t)i < list.list->len) result = retain(list.list->items[i]);
+    }
+    drop(list);
+    drop(index);
+    return result;
+}
+
+/* `? cond {`: only yes is true. */
+static int truth(Value v) {
+    int yes = v.tag == BOOL && v.yes;
+    drop(v);
+    return yes;
+}
+
+/* --- VARIABLES AND CALLS --- */
+
+typedef struct {
+    int sym;
+    Value val;
+} Var;
+
+typedef struct {
+    Var *vars;
+    size_t len, cap;
+} Frame;
+
+static Value *globals;
+static Frame *frames;
+static size_t depth, frames_cap;
+
+static Value *lookup(int sym) {
+    for (size_t d = depth; d-- > 0;) {
+        for (size_t i = 0; i < frames[d].len; i++) {
+            if (frames[d].vars[i].sym == sym) return &frames[d].vars[i].val;
+        }
+    }
+    return globals[sym].tag == UNSET ? NULL : &globals[sym];
+}
+
+static Value get(int sym) {
+    Value *slot = lookup(sym);
+    return slot ? retain(*slot) : none();
+}
+
+/* `get` for a value about to be changed and stored back, so the variable
+   doesn't keep it shared in the meantime. */
+static Value take(int sym) {
+    Value *slot = lookup(sym);
+    if (!slot) return none();
+    Value v = *slot;
+    *slot = none();
+    return v;
+}
+
+static void bind(Frame *frame, int sym, Value v) {
+    if (frame->len == frame->cap) {
+        frame->cap = frame->cap ? frame->cap * 2 : 8;
+        frame->vars = grow(frame->vars, frame->cap * sizeof(Var));
+    }
+    frame->vars[frame->len].sym = sym;
+    frame->vars[frame->len].val = v;
+    frame->len++;
+}
+
+/* Changes the innermost variable called `sym`, or makes one in the current
+   call. */
+static void set(int sym, Value v) {
+    Value *slot = lookup(sym);
+    if (slot) {
+        drop(*slot);
+        *slot = v;
+    } else if (depth > 0) {
+        bind(&frames[depth - 1], sym, v);
+    } else {
+        globals[sym] = v;
+    }
+}
+
+static int has(int sym) {
+    Value *slot = lookup(sym);
+    return slot && slot->tag != NOTHING;
+}
+
+/* The variable as the current call has it, without looking further out. */
+static Value *own(int sym) {
+    if (depth == 0) return globals[sym].tag == UNSET ? NULL : &globals[sym];
+    Frame *frame = &frames[depth - 1];
+    for (size_t i = 0; i < frame->len; i++) {
+        if (frame->vars[i].sym == sym) return &frame->vars[i].val;
+    }
+    return NULL;
+}
+
+/* Gives the current call its own `sym`, whatever an outer one has. */
+static void define(int sym, Value v) {
+    Value *slot = own(sym);
+    if (slot) {
+        drop(*slot);
+        *slot = v;
+    } else if (depth > 0) {
+        bind(&frames[depth - 1], sym, v);
+    } else {
+        globals[sym] = v;
+    }
+}
+
+/* Gives each name its item of `v`, or nothing past the end, and the rest the
+   list of the items left over. Takes over `v`. */
+static void unpack(Value v, const Unpack *u, int defining) {
+    void (*give)(int, Value) = defining ? define : set;
+    size_t len = v.tag == LIST ? v.list->len : 0;
+    for (int i = 0; i < u->count; i++) {
+        give(u->syms[i], (size_t)i < len ? retain(v.list->items[i]) : none());
+    }
+    if (u->rest >= 0) {
+        size_t n = len > (size_t)u->count ? len - (size_t)u->count : 0;
+        Value rest = empty_list(n);
+        for (size_t i = 0; i < n; i++) rest.list->items[i] = retain(v.list->items[(size_t)u->count + i]);
+        rest.list->len = n;
+        give(u->rest, rest);
+    }
+    drop(v);
+}
+
+/* Parameters the call didn't pass stay unset, so they read the caller's.
+   A `[a b]` one gives its names in its place. */
+static void bind_params(Frame *frame, const Func *f, int argc, Value *args) {
+    for (int i = 0; i < argc; i++) {
+        if (i >= f->nparams) drop(args[i]);
+        else if (f->unpacks && f->unpacks[i].count >= 0) unpack(args[i], &f->unpacks[i], 1);
+        else bind(frame, f->params[i], args[i]);
+    }
+}
+
+/* A call returned from a function, made by `invoke` in place of it. */
+static const Func *tail_func;
+static Value *tail_args;
+static int tail_argc, tail_cap;
+
+static Value invoke(const Func *f, int argc, Value *args) {
+    if (depth == frames_cap) {
+        frames_cap = frames_cap ? frames_cap * 2 : 16;
+        frames = grow(frames, frames_cap * sizeof(Frame));
+        memset(frames + depth, 0, (frames_cap - depth) * sizeof(Frame));
+    }
+    Frame *frame = &frames[depth++];
+    frame->len = 0;
+    bind_params(frame, f, argc, args);
+    Value result;
+    for (;;) {
+        result = f->body();
+        if (!tail_func) break;
+        drop(result);
+        /* The called function takes over this call's frame: its parameters,
+           then the variables it had that aren't parameters. Frames may have
+           moved while the body ran. */
+        f = tail_func;
+        tail_func = NULL;
+        frame = &frames[depth - 1];
+        Frame old = *frame;
+        frame->vars = NULL;
+        frame->len = frame->cap = 0;
+        bind_params(frame, f, tail_argc, tail_args);
+        for (size_t i = 0; i < old.len; i++) {
+            int param = 0;
+            for (size_t j = 0; j < frame->len && !param; j++) param = frame->vars[j].sym == old.vars[i].sym;
+            if (param) drop(old.vars[i].val);
+            else bind(frame, old.vars[i].sym, old.vars[i].val);
+        }
+        free(old.vars);
+    }
+    frame = &frames[--depth];
+    for (size_t i = 0; i < frame->len; i++) drop(frame->vars[i].val);
+    frame->len = 0;
+    return result;
+}
+
+/* `f(a b)`: a call of whatever `f` holds, which does nothing unless it is a
+   function. Takes over the `argc` arguments. */
+static Value call(int sym, int argc, Value *args) {
+    Value f = get(sym);
+    if (f.tag == FUNC) return invoke(f.func, argc, args);
+    drop(f);
+    for (int i = 0; i < argc; i++) drop(args[i]);
+    return none();
+}
+
+/* `f*(x)` in an expression: the result is stored in `x` too. */
+static Value stored(int sym, Value v) {
+    set(sym, retain(v));
+    return v;
+}
+
+/* `-> f(x)` in a function: returned to `invoke`, which makes the call
+   without a new frame. */
+static Value tail(int sym, int argc, Value *args) {
+    Value f = get(sym);
+    if (f.tag != FUNC) return call(sym, argc, args);
+    if (argc > tail_cap) {
+        tail_cap = argc;
+        tail_args = grow(tail_args, (size_t)tail_cap * sizeof(Value));
+    }
+    if (argc) memcpy(tail_args, args, (size_t)argc * sizeof(Value));
+    tail_argc = argc;
+    tail_func = f.func;
+    return none();
+}
+
+/* `>> x list {`: anything but a list has no items. */
+static Value each(Value v) {
+    if (v.tag == LIST) return v;
+    drop(v);
+    return empty_list(0);
+}
+
+static size_t length(Value list) {
+    return list.list->len;
+}
+
+static Value item(Value list, size_t i) {
+    return retain(list.list->items[i]);
+}
+
+/* --- INPUT AND OUTPUT --- */
+
+static void print(Value v) {
+    if (v.tag != NOTHING) {
+        Buf buf = {0};
+        show(&buf, v);
+        put(&buf, "\n", 1);
+        fwrite(buf.data, 1, buf.len, stdout);
+        free(buf.data);
+    }
+    drop(v);
+}
+
+/* One line from stdin, trimmed, as a number if it is one. */
+static Value answer(const char *question) {
+    fputs(question, stdout);
+    fflush(stdout);
+    Buf line = {0};
+    int c;
+    while ((c = getchar()) != EOF && c != '\n') {
+        char byte = (char)c;
+        put(&line, &byte, 1);
+    }
+    const char *space = " \t\n\v\f\r";
+    size_t start = 0, end = line.len;
+    while (start < end && strchr(space, line.data[start])) start++;
+    while (end > start && strchr(space, line.data[end - 1])) end--;
+    double n;
+    Value v = parse_number(line.data + start, end - start, &n) ? num(n) : str(line.data + start, end - start);
+    free(line.data);
+    return v;
+}
+
+/* `+??` */
+static Value input(void) {
+    return answer("+? ");
+}
+
+/* --- BUILTINS --- */
+
+static Value len(Value v) {
+    double n = v.tag == LIST ? (double)v.list->len : v.tag == TEXT ? (double)v.text->len : 0;
+    drop(v);
+    return num(n);
+}
+
+static Value to_text(Value v) {
+    Buf buf = {0};
+    show(&buf, v);
+    drop(v);
+    return buf_text(&buf);
+}
+
+static Value to_number(Value v) {
+    double n = 0;
+    if (v.tag == TEXT && !parse_number(v.text->data, v.text->len, &n)) n = 0;
+    if (v.tag == NUMBER) n = v.num;
+    drop(v);
+    return num(n);
+}
+
+static Value not(Value v) {
+    int yes = v.tag == BOOL && !v.yes;
+    drop(v);
+    return boolean(yes);
+}
+
+static uint64_t rng_state;
+
+/* `?=(max)`: xorshift, like the interpreter, from a seed that changes. */
+static Value random_below(Value max) {
+    uint64_t n = 0;
+    if (max.tag == NUMBER && max.num > 0) n = max.num >= 18446744073709551615.0 ? UINT64_MAX : (uint64_t)max.num;
+    drop(max);
+    if (n == 0) return num(0);
+    if (rng_state == 0) rng_state = ((uint64_t)time(NULL) * 6364136223846793005u + (uint64_t)(uintptr_t)&n) | 1;
+    uint64_t x = rng_state;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    rng_state = x;
+    return num((double)(x % n));
+}
+
+static Value push(Value l, Value v) {
+    if (l.tag != LIST) {
+        drop(l);
+        drop(v);
+        return none();
+    }
+    l = unshared(l);
+    append_item(l.list, v);
+    return l;
+}
+
+static Value pop(Value l) {
+    if (l.tag != LIST || l.list->len == 0) {
+        drop(l);
+        return none();
+    }
+    l = unshared(l);
+    drop(l.list->items[--l.list->len]);
+    return l;
+}
+
+static Value reverse(Value l) {
+    if (l.tag != LIST) {
+        drop(l);
+        return none();
+    }
+    l = unshared(l);
+    for (size_t i = 0, j = l.list->len; i + 1 < j; i++, j--) {
+        Value swap = l.list->items[i];
+        l.list->items[i] = l.list->items[j - 1];
+        l.list->items[j - 1] = swap;
+    }
+    return l;
+}
+
+static Value contains(Value l, Value v) {
+    int found = 0;
+    if (l.tag == LIST) {
+        for (size_t i = 0; i < l.list->len && !found; i++) found = equal(l.list->items[i], v);
+    }
+    drop(l);
+    drop(v);
+    return boolean(found);
+}
+
+static Value unique(Value l) {
+    if (l.tag != LIST) {
+        drop(l);
+        return none();
+    }
+    Value out = empty_list(0);
+    for (size_t i = 0; i < l.list->len; i++) {
+        Value v = l.list->items[i];
+        int seen = 0;
+        for (size_t j = 0; j < out.list->len && !seen; j++) seen = equal(out.list->items[j], v);
+        if (!seen) append_item(out.list, retain(v));
+    }
+    drop(l);
+    return out;
+}
+
+static Value join(Value l, Value sep) {
+    Buf buf = {0};
+    if (l.tag == LIST && sep.tag == TEXT) {
+        for (size_t i = 0; i < l.list->len; i++) {
+            if (i) put(&buf, sep.text->data, sep.text->len);
+            show(&buf, l.list->items[i]);
+        }
+    }
+    drop(l);
+    drop(sep);
+    return buf_text(&buf);
+}
+
+static Value split(Value s, Value sep) {
+    Value out = empty_list(0);
+    if (s.tag == TEXT && sep.tag == TEXT && sep.text->len > 0) {
+        const char *data = s.text->data, *at = data, *end = data + s.text->len;
+        size_t n = sep.text->len;
+        for (const char *p = data; p + n <= end;) {
+            if (memcmp(p, sep.text->data, n) == 0) {
+                append_item(out.list, str(at, (size_t)(p - at)));
+                p += n;
+                at = p;
+            } else {
+                p++;
+            }
+        }
+        append_item(out.list, str(at, (size_t)(end - at)));
+    }
+    drop(s);
+    drop(sep);
+    return out;
+}
+
+/* An item with what it sorts by. */
+typedef struct {
+    Value key, item;
+} Keyed;
+
+/* A stable merge sort by `compare`. */
+static void sort_keyed(Keyed *items, Keyed *tmp, size_t n, int descending) {
+    if (n <= 1) return;
+    size_t half = n / 2;
+    sort_keyed(items, tmp, half, descending);
+    sort_keyed(items + half, tmp, n - half, descending);
+    size_t i = 0, j = half, k = 0;
+    while (i < half && j < n) {
+        int order = compare(items[i].key, items[j].key);
+        if (descending) order = -order;
+        tmp[k++] = order > 0 ? items[j++] : items[i++];
+    }
+    while (i < half) tmp[k++] = items[i++];
+    while (j < n) tmp[k++] = items[j++];
+    memcpy(items, tmp, n * sizeof(Keyed));
+}
+
+/* Whether `r` goes before `l`. `yes` only says that the first argument goes
+   first, so on `no` the comparator is asked the other way round: equal items
+   keep their order. */
+static int right_first(const Func *cmp, Value l, Value r, int descending) {
+    Value result = invoke(cmp, 2, (Value[]){retain(l), retain(r)});
+    int first;
+    if (result.tag == NUMBER) {
+        first = descending ? result.num < 0 : result.num > 0;
+    } else if (descending) {
+        first = result.tag == BOOL && result.yes;
+    } else if (result.tag == BOOL && result.yes) {
+        first = 0;
+    } else {
+        Value other = invoke(cmp, 2, (Value[]){retain(r), retain(l)});
+        first = other.tag == BOOL && other.yes;
+        drop(other);
+    }
+    drop(result);
+    return first;
+}
+
+/* A user comparator may not be a consistent order, so this is the exact
+   merge the interpreter does. */
+static void merge_by(Value *items, Value *tmp, size_t n, const Func *cmp, int descending) {
+    if (n <= 1) return;
+    size_t half = n / 2;
+    merge_by(items, tmp, half, cmp, descending);
+    merge_by(items + half, tmp, n - half, cmp, descending);
+    size_t i = 0, j = half, k = 0;
+    while (i < half && j < n) {
+        tmp[k++] = right_first(cmp, items[i], items[j], descending) ? items[j++] : items[i++];
+    }
+    while (i < half) tmp[k++] = items[i++];
+    while (j < n) tmp[k++] = items[j++];
+    memcpy(items, tmp, n * sizeof(Value));
+}
+
+/* `++(list -> f)`: by key when `f` takes one parameter, as a comparator
+   when it takes two. */
+static Value sort(Value l, Value by, int descending) {
+    if (l.tag != LIST) {
+        drop(l);
+        drop(by);
+        return none();
+    }
+    l = unshared(l);
+    size_t n = l.list->len;
+    if (by.tag == FUNC && by.func->nparams >= 2) {
+        Value *tmp = alloc(n * sizeof(Value));
+        merge_by(l.list->items, tmp, n, by.func, descending);
+        free(tmp);
+        return l;
+    }
+    Keyed *keyed = alloc(n * sizeof(Keyed)), *tmp = alloc(n * sizeof(Keyed));
+    for (size_t i = 0; i < n; i++) {
+        keyed[i].item = l.list->items[i];
+        keyed[i].key = by.tag == FUNC ? invoke(by.func, 1, (Value[]){retain(keyed[i].item)}) : keyed[i].item;
+    }
+    sort_keyed(keyed, tmp, n, descending);
+    for (size_t i = 0; i < n; i++) {
+        l.list->items[i] = keyed[i].item;
+        if (by.tag == FUNC) drop(keyed[i].key);
+    }
+    free(keyed);
+    free(tmp);
+    return l;
+}
+
+/* --- ERRORS --- */
+
+/* Stops the program like an error in Lazy: `Error: line N: ...` and exit
+   code 1. Takes over `message`. */
+static void fail(int line, Buf *message) {
+    fflush(stdout);
+    fprintf(stderr, "Error: line %d: %.*s\n", line, (int)message->len, message->data);
+    exit(1);
+}
+
+static size_t chars(const char *s, size_t len) {
+    size_t n = 0;
+    for (size_t i = 0; i < len; i++) n += ((unsigned char)s[i] & 0xC0) != 0x80;
+    return n;
+}
+
+/* Where `expected` and `actual` differ, a line each, counted in `count`
+   after the first 10. */
+static void differences(Value expected, Value actual, Buf *path, Buf *out, size_t *count) {
+    char index[32];
+    if (expected.tag == LIST && actual.tag == LIST) {
+        size_t x = expected.list->len, y = actual.list->len;
+        if (x != y && (*count)++ < 10) {
+            char line[96];
+            snprintf(line, sizeof line, ": expected %zu item(s), got %zu", x, y);
+            puts_buf(out, "\n  ");
+            if (path->len) put(out, path->data, path->len);
+            else puts_buf(out, "list");
+            puts_buf(out, line);
+        }
+        for (size_t i = 0; i < x || i < y; i++) {
+            size_t outer = path->len;
+            snprintf(index, sizeof index, "[%zu]", i);
+            puts_buf(path, index);
+            if (i < x && i < y) {
+                Value a = expected.list->items[i], b = actual.list->items[i];
+                if (same(a, b)) {
+                } else if (a.tag == LIST && b.tag == LIST) {
+                    differences(a, b, path, out, count);
+                } else if ((*count)++ < 10) {
+                    puts_buf(out, "\n  ");
+                    put(out, path->data, path->len);
+                    puts_buf(out, ": expected ");
+                    repr(out, a);
+                    puts_buf(out, ", got ");
+                    repr(out, b);
+                }
+            } else if (i < x) {
+                if ((*count)++ < 10) {
+                    puts_buf(out, "\n  ");
+                    put(out, path->data, path->len);
+                    puts_buf(out, ": expected ");
+                    repr(out, expected.list->items[i]);
+                    puts_buf(out, ", missing");
+                }
+            } else if ((*count)++ < 10) {
+                puts_buf(out, "\n  ");
+                put(out, path->data, path->len);
+                puts_buf(out, ": unexpected ");
+                repr(out, actual.list->items[i]);
+            }
+            path->len = outer;
+        }
+    } else if (expected.tag == TEXT && actual.tag == TEXT && path->len == 0) {
+        const char *x = expected.text->data, *y = actual.text->data;
+        size_t n = 0;
+        while (n < expected.text->len && n < actual.text->len && x[n] == y[n]) n++;
+        while (n > 0 && n < expected.text->len && ((unsigned char)x[n] & 0xC0) == 0x80) n--;
+        size_t same = chars(x, n);
+        if ((*count)++ < 10) {
+            puts_buf(out, "\n             ");
+            for (size_t i = 0; i < same; i++) put(out, " ", 1);
+            snprintf(index, sizeof index, "%zu", same + 1);
+            puts_buf(out, "^ first difference at character ");
+            puts_buf(out, index);
+        }
+    }
+}
+
+/* `==(actual -> expected)` */
+static Value assert_equal(int line, Value actual, Value expected) {
+    if (!same(actual, expected)) {
+        Buf message = {0}, path = {0};
+        puts_buf(&message, "assertion failed: values are not equal\n  expected: ");
+        repr(&message, expected);
+        puts_buf(&message, "\n  actual:   ");
+        repr(&message, actual);
+        size_t count = 0;
+        differences(expected, actual, &path, &message, &count);
+        if (count > 10) {
+            char more[48];
+            snprintf(more, sizeof more, "\n  ...and %zu more", count - 10);
+            puts_buf(&message, more);
+        }
+        fail(line, &message);
+    }
+    drop(actual);
+    drop(expected);
+    return none();
+}
+
+/* `?!(cond)` */
+static Value check(int line, Value cond) {
+    if (!truth(cond)) {
+        Buf message = {0};
+        puts_buf(&message, "assertion failed");
+        fail(line, &message);
+    }
+    return none();
+}
+
+/* `?!(cond -> message)` */
+static Value check_message(int line, Value cond, Value why) {
+    if (!truth(cond)) {
+        Buf message = {0};
+        puts_buf(&message, "assertion failed: ");
+        show(&message, why);
+        fail(line, &message);
+    }
+    drop(why);
+    return none();
+}
+
+/* --- PATTERNS --- */
+
+/* A `?>` arm's pattern: anything, a value equal to `num` or `text` with the
+   tag `tag`, any value with the tag, or a list of `count` items followed by
+   any more when it has a `rest`. `sym` is the variable it binds, or -1. */
+typedef enum { MATCH_ANY, MATCH_IS, MATCH_TYPE, MATCH_ITEMS } Kind;
+
+typedef struct Pattern {
+    Kind kind;
+    int sym;
+    Tag tag;
+    double num;
+    const char *text;
+    size_t len, count;
+    const struct Pattern *items, *rest;
+} Pattern;
+
+/* What each name an arm bound had before, given back by `unbind`. */
+typedef struct {
+    int sym, had;
+    Value val;
+} Saved;
+
+static Saved *saved;
+static size_t saved_len, saved_cap;
+
+/* Gives the current call its own `sym`, shadowing any outer one. */
+static void shadow(int sym, Value v) {
+    if (saved_len == saved_cap) {
+        saved_cap = saved_cap ? saved_cap * 2 : 16;
+        saved = grow(saved, saved_cap * sizeof(Saved));
+    }
+    Value *slot = own(sym);
+    Saved *s = &saved[saved_len++];
+    s->sym = sym;
+    s->had = slot != NULL;
+    if (slot) {
+        s->val = *slot;
+        *slot = v;
+    } else if (depth > 0) {
+        bind(&frames[depth - 1], sym, v);
+    } else {
+        globals[sym] = v;
+    }
+}
+
+static size_t binding(void) {
+    return saved_len;
+}
+
+/* Gives back what was shadowed since `binding` returned `mark`. */
+static void unbind(size_t mark) {
+    while (saved_len > mark) {
+        Saved s = saved[--saved_len];
+        if (depth == 0) {
+            drop(globals[s.sym]);
+            if (s.had) globals[s.sym] = s.val;
+            else globals[s.sym].tag = UNSET;
+            continue;
+        }
+        Frame *frame = &frames[depth - 1];
+        size_t i = 0;
+        while (i < frame->len && frame->vars[i].sym != s.sym) i++;
+        if (i == frame->len) continue;
+        drop(frame->vars[i].val);
+        if (s.had) {
+            frame->vars[i].val = s.val;
+        } else {
+            memmove(&frame->vars[i], &frame->vars[i + 1], (frame->len - i - 1) * sizeof(Var));
+            frame->len--;
+        }
+    }
+}
+
+static int matches(Value v, const Pattern *p) {
+    switch (p->kind) {
+    case MATCH_ANY:
+        break;
+    case MATCH_IS:
+        if (v.tag != p->tag) return 0;
+        if (v.tag == NUMBER && !same(v, num(p->num))) return 0;
+        if (v.tag == BOOL && v.yes != (p->num != 0)) return 0;
+        if (v.tag == TEXT && (v.text->len != p->len || memcmp(v.text->data, p->text, p->len) != 0)) return 0;
+        break;
+    case MATCH_TYPE:
+        if (v.tag != p->tag) return 0;
+        break;
+    case MATCH_ITEMS: {
+        if (v.tag != LIST || v.list->len < p->count || (!p->rest && v.list->len != p->count)) return 0;
+        for (size_t i = 0; i < p->count; i++) {
+            if (!matches(v.list->items[i], &p->items[i])) return 0;
+        }
+        if (p->rest) {
+            size_t n = v.list->len - p->count;
+            Value rest = empty_list(n);
+            for (size_t i = 0; i < n; i++) rest.list->items[i] = retain(v.list->items[p->count + i]);
+            rest.list->len = n;
+            int ok = matches(rest, p->rest);
+            drop(rest);
+            if (!ok) return 0;
+        }
+        break;
+    }
+    }
+    if (p->sym >= 0) shadow(p->sym, retain(v));
+    return 1;
+}
+
+/* Whether `v` matches the arm's pattern, binding its names if so. */
+static int match(Value v, const Pattern *p) {
+    size_t mark = binding();
+    if (matches(v, p)) return 1;
+    unbind(mark);
+    return 0;
+}
+
+/* After the last arm of a `?>`. */
+static void no_match(int line, Value v) {
+    Buf message = {0};
+    puts_buf(&message, "no pattern matches ");
+    repr(&message, v);
+    fail(line, &message);
+}
+
+/* Runs the program with `count` global variables, all unset. */
+static int run(int count, Value (*program)(void)) {
+    globals = alloc((size_t)count * sizeof(Value));
+    for (int i = 0; i < count; i++) globals[i].tag = UNSET;
+    drop(program());
+    fflush(stdout);
+    return 0;
+}
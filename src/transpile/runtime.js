@@ -0,0 +1,506 @@
+// Lazy runtime for programs from `lazy transpile --target js`.
+//
+// Values are JavaScript numbers, strings, booleans and arrays, and `Fn` for
+// functions. Nothing is null, and so is undefined, which is what reading a
+// variable nobody set gives. Arrays are never changed in place, so a copy of
+// a list never sees the original change.
+//
+// Variables live in scopes chained through their prototypes: a call's scope
+// inherits from its caller's, so a function sees its caller's variables like
+// in Lazy. `set` changes the innermost one or creates it in the current call.
+//
+// Output and `+?` go through `io`. Define `globalThis.lazyIO` with `write`,
+// `prompt` and `readLine` before this script runs to send them elsewhere;
+// otherwise Node uses stdout and stdin, and a browser the console and
+// `window.prompt`.
+const lazy = (() => {
+  "use strict";
+
+  // `unpacks`, if any parameter is written `[a b]`, has what each unpacks its
+  // argument into, or null for the others.
+  class Fn {
+    constructor(params, body, unpacks = []) {
+      this.params = params;
+      this.body = body;
+      this.unpacks = unpacks;
+    }
+  }
+
+  // A call returned from a function, made by `call` in place of it.
+  class Tail {
+    constructor(fn, args) {
+      this.fn = fn;
+      this.args = args;
+    }
+  }
+
+  class LazyError extends Error {
+    constructor(line, message) {
+      super(`line ${line}: ${message}`);
+    }
+  }
+
+  function defaultIO() {
+    if (typeof process !== "undefined" && typeof require === "function") {
+      const fs = require("fs");
+      return {
+        write: (text) => fs.writeSync(1, text),
+        prompt: (text) => fs.writeSync(1, text),
+        error: (text) => fs.writeSync(2, text),
+        readLine() {
+          const byte = Buffer.alloc(1);
+          const bytes = [];
+          for (;;) {
+            let n;
+            try {
+              n = fs.readSync(0, byte, 0, 1, null);
+            } catch (e) {
+              if (e.code === "EAGAIN") continue;
+              if (e.code === "EOF") n = 0;
+              else throw e;
+            }
+            if (n === 0 && bytes.length === 0) return null;
+            if (n === 0 || byte[0] === 10) break;
+            bytes.push(byte[0]);
+          }
+          return Buffer.from(bytes).toString("utf8").replace(/\r$/, "");
+        },
+      };
+    }
+    let line = "";
+    let question = "";
+    return {
+      write(text) {
+        const lines = (line + text).split("\n");
+        line = lines.pop();
+        lines.forEach((l) => console.log(l));
+      },
+      prompt: (text) => { question = text; },
+      error: (text) => console.error(text.trimEnd()),
+      readLine: () => window.prompt(question),
+    };
+  }
+
+  const io = { ...defaultIO(), ...(globalThis.lazyIO ?? {}) };
+
+  // --- VALUES ---
+
+  const nothing = (v) => v === null || v === undefined;
+  const isNum = (v) => typeof v === "number";
+  const isText = (v) => typeof v === "string";
+
+  // Numbers as Lazy prints them: whole numbers without a point, and never
+  // with an exponent.
+  function showFloat(n) {
+    if (Number.isNaN(n)) return "NaN";
+    if (n === Infinity) return "inf";
+    if (n === -Infinity) return "-inf";
+    if (Object.is(n, -0)) return "-0";
+    const s = String(n);
+    const e = s.indexOf("e");
+    if (e < 0) return s;
+    const sign = s.startsWith("-") ? "-" : "";
+    const [int, frac = ""] = s.slice(sign.length, e).split(".");
+    const digits = int + frac;
+    const point = int.length + Number(s.slice(e + 1));
+    if (point <= 0) return `${sign}0.${"0".repeat(-point)}${digits}`;
+    if (point >= digits.length) return `${sign}${digits}${"0".repeat(point - digits.length)}`;
+    return `${sign}${digits.slice(0, point)}.${digits.slice(point)}`;
+  }
+
+  function show(v) {
+    if (nothing(v)) return "";
+    if (isNum(v)) {
+      if (!Number.isInteger(v)) return showFloat(v);
+      if (v >= 2 ** 63) return "9223372036854775807";
+      if (v <= -(2 ** 63)) return "-9223372036854775808";
+      return BigInt(v).toString();
+    }
+    if (isText(v)) return v;
+    if (typeof v === "boolean") return v ? "yes" : "no";
+    if (Array.isArray(v)) return `[${v.map((x) => (isText(x) ? `"${x}"` : show(x))).join(" ")}]`;
+    return "<function>";
+  }
+
+  // How a value is shown in error messages.
+  function repr(v) {
+    if (isText(v)) return `"${v}"`;
+    if (nothing(v)) return "nothing";
+    if (v instanceof Fn) return `<function(${v.params.join(" ")})>`;
+    return show(v);
+  }
+
+  // Strictly equal, like `><` and `<<` compare.
+  function equal(a, b) {
+    if (nothing(a) || nothing(b)) return nothing(a) && nothing(b);
+    if (Array.isArray(a) && Array.isArray(b)) {
+      return a.length === b.length && a.every((x, i) => equal(x, b[i]));
+    }
+    return a === b;
+  }
+
+  // Equal as `==` checks it: numbers within rounding error.
+  function same(a, b) {
+    if (isNum(a) && isNum(b)) return Math.abs(a - b) < Number.EPSILON || a === b;
+    if (Array.isArray(a) && Array.isArray(b)) {
+      return a.length === b.length && a.every((x, i) => same(x, b[i]));
+    }
+    return equal(a, b);
+  }
+
+  const rank = (v) =>
+    nothing(v) ? 0 : typeof v === "boolean" ? 1 : isNum(v) ? 2 : isText(v) ? 3 : Array.isArray(v) ? 4 : 5;
+
+  // The order `++` and `--` sort in: nothing < booleans < numbers < text <
+  // lists < functions.
+  function compare(a, b) {
+    if (isNum(a) && isNum(b)) {
+      if (Number.isNaN(a) || Number.isNaN(b)) return Number.isNaN(a) - Number.isNaN(b);
+      if (a === b) return Object.is(b, -0) - Object.is(a, -0);
+      return a < b ? -1 : 1;
+    }
+    if (isText(a) && isText(b)) {
+      const x = [...a];
+      const y = [...b];
+      for (let i = 0; i < x.length && i < y.length; i++) {
+        const order = x[i].codePointAt(0) - y[i].codePointAt(0);
+        if (order !== 0) return Math.sign(order);
+      }
+      return Math.sign(x.length - y.length);
+    }
+    if (typeof a === "boolean" && typeof b === "boolean") return a - b;
+    if (Array.isArray(a) && Array.isArray(b)) {
+      for (let i = 0; i < a.length && i < b.length; i++) {
+        const order = compare(a[i], b[i]);
+        if (order !== 0) return order;
+      }
+      return Math.sign(a.length - b.length);
+    }
+    return Math.sign(rank(a) - rank(b));
+  }
+
+  function parseNumber(text) {
+    if (!/^[+-]?(?:(?:\d+\.?\d*|\.\d+)(?:[eE][+-]?\d+)?|inf|infinity|nan)$/i.test(text)) return null;
+    const word = text.replace(/^[+-]/, "").toLowerCase();
+    if (word === "nan") return NaN;
+    if (word.startsWith("inf")) return text.startsWith("-") ? -Infinity : Infinity;
+    return Number(text);
+  }
+
+  // --- OPERATORS ---
+
+  function add(a, b) {
+    if (isNum(a) && isNum(b)) return a + b;
+    if (isText(a) && isText(b)) return a + b;
+    if (isText(a) && isNum(b)) return a + showFloat(b);
+    if (isNum(a) && isText(b)) return showFloat(a) + b;
+    if (Array.isArray(a) && Array.isArray(b)) return a.concat(b);
+    return null;
+  }
+
+  const numeric = (op) => (a, b) => (isNum(a) && isNum(b) ? op(a, b) : null);
+  const sub = numeric((a, b) => a - b);
+  const mul = numeric((a, b) => a * b);
+  const div = numeric((a, b) => a / b);
+  const rem = numeric((a, b) => a % b);
+  const gt = numeric((a, b) => a > b);
+  const lt = numeric((a, b) => a < b);
+  const ge = numeric((a, b) => a >= b);
+  const le = numeric((a, b) => a <= b);
+
+  function eq(a, b) {
+    if (isNum(a) && isNum(b)) return Math.abs(a - b) < Number.EPSILON;
+    if ((isText(a) && isText(b)) || (typeof a === "boolean" && typeof b === "boolean")) return a === b;
+    return null;
+  }
+
+  function ne(a, b) {
+    if (isNum(a) && isNum(b)) return Math.abs(a - b) >= Number.EPSILON;
+    if ((isText(a) && isText(b)) || (typeof a === "boolean" && typeof b === "boolean")) return a !== b;
+    return null;
+  }
+
+  function at(list, index) {
+    if (!Array.isArray(list) || !isNum(index)) return null;
+    let i = Number.isNaN(index) ? 0 : Math.trunc(index);
+    if (i < 0) i += list.length;
+    return i >= 0 && i < list.length ? list[i] ?? null : null;
+  }
+
+  // --- VARIABLES AND CALLS ---
+
+  function set($, name, value) {
+    for (let scope = $; scope !== null; scope = Object.getPrototypeOf(scope)) {
+      if (Object.hasOwn(scope, name)) {
+        scope[name] = value;
+        return value;
+      }
+    }
+    $[name] = value;
+    return value;
+  }
+
+  // `a b ...rest = list`: each name gets its item, or nothing past the end,
+  // and the rest the items left over. `define` gives them to `$` itself.
+  function unpack($, v, { names, rest }, define = false) {
+    const items = Array.isArray(v) ? v : [];
+    const give = define ? (name, value) => { $[name] = value; } : (name, value) => set($, name, value);
+    names.forEach((name, i) => give(name, items[i] ?? null));
+    if (rest !== undefined) give(rest, items.slice(names.length));
+  }
+
+  // Parameters the call didn't pass stay unset, so they read the caller's.
+  // The names of `[a b]` ones come after all of them.
+  function scope(parent, fn, args) {
+    const $ = Object.create(parent);
+    fn.params.forEach((param, i) => {
+      if (i >= args.length) return;
+      if (fn.unpacks[i]) unpack($, args[i], fn.unpacks[i], true);
+      else $[param] = args[i];
+    });
+    return $;
+  }
+
+  function call($, fn, args) {
+    if (!(fn instanceof Fn)) return null;
+    let inner = scope($, fn, args);
+    for (;;) {
+      const result = fn.body(inner);
+      if (!(result instanceof Tail)) return result ?? null;
+      // The called function takes over this call's scope.
+      const next = scope(Object.getPrototypeOf(inner), result.fn, result.args);
+      for (const name of Object.keys(inner)) {
+        if (!Object.hasOwn(next, name)) next[name] = inner[name];
+      }
+      ({ fn } = result);
+      inner = next;
+    }
+  }
+
+  // `-> f(x)`: returned to `call`, which makes the call without a new frame.
+  const tail = (fn, args) => (fn instanceof Fn ? new Tail(fn, args) : null);
+
+  // `>> x list {`: anything but a list has no items.
+  const each = (v) => (Array.isArray(v) ? v : []);
+
+  // --- PATTERNS ---
+  //
+  // A `?>` arm's pattern is an object: `{}` matches anything, `{ is: 5 }` a
+  // value equal to 5, `{ type: "list" }` any list, and `{ items, rest }` a
+  // list of those items followed by any more when it has a rest. Any of them
+  // can have `bind`, the name that gets the value.
+
+  function typeName(v) {
+    if (nothing(v)) return "nothing";
+    if (typeof v === "boolean") return "bool";
+    if (isNum(v)) return "number";
+    if (isText(v)) return "text";
+    return Array.isArray(v) ? "list" : "function";
+  }
+
+  function matches(pattern, v, binds) {
+    if ("is" in pattern) return isNum(pattern.is) ? isNum(v) && same(pattern.is, v) : v === pattern.is;
+    if ("type" in pattern && typeName(v) !== pattern.type) return false;
+    if ("items" in pattern) {
+      const { items, rest } = pattern;
+      if (!Array.isArray(v) || (rest ? v.length < items.length : v.length !== items.length)) return false;
+      if (!items.every((item, i) => matches(item, v[i], binds))) return false;
+      if (rest && !matches(rest, v.slice(items.length), binds)) return false;
+    }
+    if ("bind" in pattern) binds.push([pattern.bind, v ?? null]);
+    return true;
+  }
+
+  // Bound names shadow the current scope's own until `unbind`.
+  function bind($, binds) {
+    return binds.map(([name, value]) => {
+      const before = Object.hasOwn($, name) ? { value: $[name] } : null;
+      $[name] = value;
+      return [name, before];
+    });
+  }
+
+  function unbind($, saved) {
+    for (const [name, before] of saved.reverse()) {
+      if (before) $[name] = before.value;
+      else delete $[name];
+    }
+  }
+
+  // `?> value {`: the first arm whose pattern matches and whose guard is yes,
+  // with what it bound, for `unbind` when the arm ends.
+  function match($, v, patterns, guards, line) {
+    for (let i = 0; i < patterns.length; i++) {
+      const binds = [];
+      if (!matches(patterns[i], v, binds)) continue;
+      const saved = bind($, binds);
+      if (guards[i] === null || guards[i]() === true) return [i, saved];
+      unbind($, saved);
+    }
+    throw new LazyError(line, `no pattern matches ${repr(v)}`);
+  }
+
+  // --- INPUT AND OUTPUT ---
+
+  function print(v) {
+    if (!nothing(v)) io.write(`${show(v)}\n`);
+  }
+
+  function answer(question) {
+    io.prompt(question);
+    const text = (io.readLine() ?? "").trim();
+    const n = parseNumber(text);
+    return n === null ? text : n;
+  }
+
+  const input = () => answer("+? ");
+
+  // `+? a b : "Number {?}: "` asks once per name, numbering the prompts
+  // when it has `{?}`.
+  function read($, names, question = "+? ", numbered = false) {
+    names.forEach((name, i) => {
+      set($, name, answer(numbered ? question.replaceAll("{?}", String(i + 1)) : question));
+    });
+  }
+
+  // --- BUILTINS ---
+
+  const len = (v) => (Array.isArray(v) ? v.length : isText(v) ? new TextEncoder().encode(v).length : 0);
+  const text = (...args) => (args.length ? show(args[0]) : "");
+
+  function number(v) {
+    if (isText(v)) return parseNumber(v) ?? 0;
+    return isNum(v) ? v : 0;
+  }
+
+  const not = (v) => (typeof v === "boolean" ? !v : false);
+
+  function random(max) {
+    const n = isNum(max) && max > 0 ? Math.floor(max) : 0;
+    return Math.floor(Math.random() * n);
+  }
+
+  const push = (...args) => (Array.isArray(args[0]) && args.length > 1 ? [...args[0], args[1]] : null);
+  const pop = (list) => (Array.isArray(list) && list.length ? list.slice(0, -1) : null);
+  const reverse = (list) => (Array.isArray(list) ? [...list].reverse() : null);
+  const contains = (...args) => Array.isArray(args[0]) && args.length > 1 && args[0].some((x) => equal(x, args[1]));
+
+  function unique(list) {
+    if (!Array.isArray(list)) return null;
+    const out = [];
+    list.forEach((x) => {
+      if (!out.some((y) => equal(x, y))) out.push(x);
+    });
+    return out;
+  }
+
+  function join(list, sep) {
+    if (!Array.isArray(list) || !isText(sep)) return "";
+    return list.map((x) => (isText(x) ? x : show(x))).join(sep);
+  }
+
+  function split(s, sep) {
+    if (!isText(s) || !isText(sep) || sep === "") return [];
+    return s.split(sep);
+  }
+
+  // `++(list -> f)`: by key when `f` takes one parameter, as a comparator
+  // when it takes two.
+  function sort($, list, by, descending = false) {
+    if (!Array.isArray(list)) return null;
+    const direction = (order) => (descending ? -order : order);
+    if (by instanceof Fn && by.params.length >= 2) return mergeSort($, [...list], by, descending);
+    if (by instanceof Fn) {
+      const keyed = list.map((item) => [call($, by, [item]), item]);
+      keyed.sort(([a], [b]) => direction(compare(a, b)));
+      return keyed.map(([, item]) => item);
+    }
+    return [...list].sort((a, b) => direction(compare(a, b)));
+  }
+
+  function mergeSort($, items, cmp, descending) {
+    if (items.length <= 1) return items;
+    const left = mergeSort($, items.slice(0, items.length >> 1), cmp, descending);
+    const right = mergeSort($, items.slice(items.length >> 1), cmp, descending);
+    const merged = [];
+    let i = 0;
+    let j = 0;
+    while (i < left.length && j < right.length) {
+      merged.push(rightFirst($, cmp, left[i], right[j], descending) ? right[j++] : left[i++]);
+    }
+    return merged.concat(left.slice(i), right.slice(j));
+  }
+
+  // `yes` only says that the first argument goes first, so on `no` the
+  // comparator is asked the other way round: equal items keep their order.
+  function rightFirst($, cmp, l, r, descending) {
+    const result = call($, cmp, [l, r]);
+    if (isNum(result)) return descending ? result < 0 : result > 0;
+    if (descending) return result === true;
+    return result !== true && call($, cmp, [r, l]) === true;
+  }
+
+  function differences(expected, actual, path, out) {
+    if (Array.isArray(expected) && Array.isArray(actual)) {
+      if (expected.length !== actual.length) {
+        out.push(`${path || "list"}: expected ${expected.length} item(s), got ${actual.length}`);
+      }
+      for (let i = 0; i < Math.max(expected.length, actual.length); i++) {
+        const itemPath = `${path}[${i}]`;
+        if (i < expected.length && i < actual.length) {
+          const [x, y] = [expected[i], actual[i]];
+          if (same(x, y)) continue;
+          if (Array.isArray(x) && Array.isArray(y)) differences(x, y, itemPath, out);
+          else out.push(`${itemPath}: expected ${repr(x)}, got ${repr(y)}`);
+        } else if (i < expected.length) {
+          out.push(`${itemPath}: expected ${repr(expected[i])}, missing`);
+        } else {
+          out.push(`${itemPath}: unexpected ${repr(actual[i])}`);
+        }
+      }
+    } else if (isText(expected) && isText(actual) && path === "") {
+      const [x, y] = [[...expected], [...actual]];
+      let same = 0;
+      while (same < x.length && same < y.length && x[same] === y[same]) same++;
+      out.push(`          ${" ".repeat(same + 1)}^ first difference at character ${same + 1}`);
+    }
+  }
+
+  // `==(actual -> expected)`
+  function assertEqual(line, actual, expected) {
+    if (same(actual, expected)) return null;
+    let message = `assertion failed: values are not equal\n  expected: ${repr(expected)}\n  actual:   ${repr(actual)}`;
+    const diffs = [];
+    differences(expected, actual, "", diffs);
+    diffs.slice(0, 10).forEach((diff) => {
+      message += `\n  ${diff}`;
+    });
+    if (diffs.length > 10) message += `\n  ...and ${diffs.length - 10} more`;
+    throw new LazyError(line, message);
+  }
+
+  // `?!(bool -> message)`
+  function assert(line, ...args) {
+    if (args[0] === true) return null;
+    throw new LazyError(line, args.length > 1 ? `assertion failed: ${show(args[1])}` : "assertion failed");
+  }
+
+  // Runs the program with a fresh global scope. An error stops it like in
+  // Lazy: with `Error: line N: ...` and exit code 1.
+  function run(main) {
+    try {
+      main(Object.create(null));
+    } catch (e) {
+      if (!(e instanceof LazyError)) throw e;
+      io.error(`Error: ${e.message}\n`);
+      if (typeof process !== "undefined") process.exitCode = 1;
+    }
+  }
+
+  return {
+    Fn, io, run, print, set, unpack, call, tail, each, input, read, match, unbind,
+    add, sub, mul, div, rem, gt, lt, ge, le, eq, ne, at,
+    len, text, number, not, random, push, pop, reverse, sort, contains, unique, join, split,
+    assertEqual, assert,
+  };
+})();
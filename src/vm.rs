@@ -0,0 +1,595 @@
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::pattern::{Pattern, Vars};
+use crate::resolve::{frame_names, Address, Slot, Symbols};
+use crate::{append, binary, in_place, index_value, Expr, Function, Interpreter, Name, Statement, Stmt, Value, BUILTINS};
+
+// --- BYTECODE VM ---
+//
+// `--vm` compiles the syntax tree into a flat list of instructions for a stack
+// machine instead of walking it. Operators are resolved to `BinOp` once, and
+// constants, names and nested functions live in tables the instructions
+// index into. Variables are addressed by the slots `resolve` gives them and
+// found the same way the tree-walker finds them, so programs behave exactly
+// alike; `call_value` compiles each function body the first time it is called.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Greater,
+    Less,
+    Equal,
+    NotEqual,
+    GreaterEqual,
+    LessEqual,
+}
+
+impl BinOp {
+    pub(crate) fn from_symbol(op: &str) -> Option<BinOp> {
+        Some(match op {
+            "+" => BinOp::Add,
+            "-" => BinOp::Sub,
+            "*" => BinOp::Mul,
+            "/" => BinOp::Div,
+            "%" => BinOp::Rem,
+            ">" => BinOp::Greater,
+            "<" => BinOp::Less,
+            "==" => BinOp::Equal,
+            "!=" => BinOp::NotEqual,
+            ">=" => BinOp::GreaterEqual,
+            "<=" => BinOp::LessEqual,
+            _ => return None,
+        })
+    }
+}
+
+// Operands are indexes into the chunk's tables, or jump targets.
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    // Start of the statement on this line: stop after an error.
+    Line(usize),
+    Const(usize),
+    Load(Slot),
+    Store(Slot),
+    List(usize),
+    Index,
+    Binary(BinOp),
+    // `x += ...` with the current value and the operand on the stack.
+    Update(Slot, BinOp),
+    // An operator `BinOp` doesn't know, which gives nothing like in `apply_op`.
+    Unknown,
+    // `func` is where to find a user function, `None` for a builtin.
+    Call { name: usize, func: Option<Slot>, args: usize, mutates: bool },
+    // `f*(x)` stores the result back into `x`.
+    CallStore { name: usize, func: Option<Slot>, args: usize, target: Slot },
+    // `-> f(x)` for a user function, see `return_call`.
+    TailCall { name: usize, func: Slot, args: usize },
+    Input,
+    Read(usize),
+    Import(usize),
+    Define(usize, usize),
+    Print,
+    Pop,
+    Return,
+    Jump(usize),
+    JumpUnlessTrue(usize),
+    JumpIfError(usize),
+    // Pops the value only when it is nothing, for `x += ...`.
+    JumpIfNothing(usize),
+    // Takes the list for a `>>` loop, or jumps past a loop over anything else.
+    IterStart(usize),
+    // Stores the next item, or jumps past the loop when there is none. With
+    // no slot it pushes the item for an `Unpack` after it.
+    IterNext(Option<Slot>, usize),
+    // Pops a value and unpacks it into the names of `vars[i]`.
+    Unpack(usize),
+    // Binds what the pattern takes from the `?>` value on the stack, or jumps
+    // to the next arm when it doesn't match.
+    Match(usize, usize),
+    // Pops the guard, and unbinds and jumps to the next arm unless it's yes.
+    Guard(usize),
+    // Ends an arm, giving what its pattern bound back.
+    Unbind,
+    // After the last arm: fails with the value on the stack.
+    NoMatch,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Chunk {
+    // What the symbols in the code are numbers in.
+    symbols: Rc<Symbols>,
+    // The names the frame of the function this is the body of has slots for.
+    locals: Vec<String>,
+    code: Vec<Op>,
+    constants: Vec<Value>,
+    names: Vec<String>,
+    functions: Vec<Rc<Function>>,
+    inputs: Vec<(Vec<String>, Option<String>, bool)>,
+    imports: Vec<(String, Vec<(String, String)>)>,
+    patterns: Vec<Pattern>,
+    vars: Vec<Vars>,
+    // The file the code is in, which the functions it defines remember.
+    file: Option<PathBuf>,
+}
+
+pub(crate) fn compile(stmts: &[Stmt], symbols: &Rc<Symbols>, file: Option<&Path>) -> Chunk {
+    let mut chunk = Chunk { symbols: symbols.clone(), file: file.map(Path::to_path_buf), ..Chunk::default() };
+    chunk.block(stmts);
+    chunk
+}
+
+fn compile_function(func: &Function, symbols: &Rc<Symbols>) -> Chunk {
+    let mut chunk = Chunk {
+        symbols: symbols.clone(),
+        locals: frame_names(&func.params, &func.body).0,
+        file: func.file.clone(),
+        ..Chunk::default()
+    };
+    chunk.block(&func.body);
+    chunk
+}
+
+impl Chunk {
+    fn emit(&mut self, op: Op) -> usize {
+        self.code.push(op);
+        self.code.len() - 1
+    }
+
+    // Points the jump at `at` to the next instruction.
+    fn patch(&mut self, at: usize) {
+        let target = self.code.len();
+        match &mut self.code[at] {
+            Op::Jump(t) | Op::JumpUnlessTrue(t) | Op::JumpIfError(t) | Op::JumpIfNothing(t)
+            | Op::IterStart(t) | Op::IterNext(_, t) | Op::Match(_, t) | Op::Guard(t) => *t = target,
+            _ => {}
+        }
+    }
+
+    fn name(&mut self, name: &str) -> usize {
+        match self.names.iter().position(|n| n == name) {
+            Some(i) => i,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() - 1
+            }
+        }
+    }
+
+    // Where a function the code calls by name is.
+    fn slot(&self, name: &str) -> Slot {
+        match self.locals.iter().position(|local| local == name) {
+            Some(i) => Slot::Local(i),
+            None => Slot::Symbol(self.symbols.symbol(name)),
+        }
+    }
+
+    // Where the resolver put a variable.
+    fn var(&self, name: &Name) -> Slot {
+        match name.address() {
+            Address::Local(i) => Slot::Local(i),
+            Address::Global(symbol) => Slot::Global(symbol),
+            Address::Unresolved => self.slot(name),
+        }
+    }
+
+    fn unpack(&mut self, vars: &Vars) -> usize {
+        self.vars.push(vars.clone());
+        self.emit(Op::Unpack(self.vars.len() - 1))
+    }
+
+    fn constant(&mut self, val: Value) -> usize {
+        self.constants.push(val);
+        self.constants.len() - 1
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.statement(stmt);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt) {
+        self.emit(Op::Line(stmt.line));
+        match &stmt.kind {
+            Statement::Print(expr) => {
+                self.expr(expr);
+                self.emit(Op::Print);
+            }
+            Statement::Assign(vars, expr) => {
+                self.expr(expr);
+                match vars {
+                    Vars::One(name) => self.emit(Op::Store(self.var(name))),
+                    Vars::Unpack(..) => self.unpack(vars),
+                };
+            }
+            Statement::AugAssign(name, op, expr) => {
+                let name = self.var(name);
+                self.emit(Op::Load(name));
+                let skip = self.emit(Op::JumpIfNothing(0));
+                self.expr(expr);
+                match BinOp::from_symbol(op) {
+                    Some(op) => self.emit(Op::Update(name, op)),
+                    None => {
+                        self.emit(Op::Unknown);
+                        self.emit(Op::Store(name))
+                    }
+                };
+                self.patch(skip);
+            }
+            Statement::IncDec(name, op) => {
+                let name = self.var(name);
+                self.emit(Op::Load(name));
+                let op = match op.as_str() {
+                    "++" => Some(BinOp::Add),
+                    "--" => Some(BinOp::Sub),
+                    _ => None,
+                };
+                if let Some(op) = op {
+                    let one = self.constant(Value::Number(1.0));
+                    self.emit(Op::Const(one));
+                    self.emit(Op::Binary(op));
+                }
+                self.emit(Op::Store(name));
+            }
+            Statement::If(cond, then_block, else_ifs, else_block) => {
+                let mut ends = Vec::new();
+                for (cond, block) in std::iter::once((cond, then_block)).chain(else_ifs.iter().map(|(c, b)| (c, b))) {
+                    self.expr(cond);
+                    let next = self.emit(Op::JumpUnlessTrue(0));
+                    self.block(block);
+                    ends.push(self.emit(Op::Jump(0)));
+                    self.patch(next);
+                }
+                self.block(else_block);
+                for end in ends {
+                    self.patch(end);
+                }
+            }
+            Statement::While(cond, body) => {
+                let top = self.code.len();
+                self.expr(cond);
+                let exit = self.emit(Op::JumpUnlessTrue(0));
+                let failed = self.emit(Op::JumpIfError(0));
+                self.block(body);
+                self.emit(Op::Jump(top));
+                self.patch(exit);
+                self.patch(failed);
+            }
+            Statement::For(vars, list_expr, body) => {
+                self.expr(list_expr);
+                let start = self.emit(Op::IterStart(0));
+                let next = match vars {
+                    Vars::One(name) => self.emit(Op::IterNext(Some(self.var(name)), 0)),
+                    Vars::Unpack(..) => {
+                        let next = self.emit(Op::IterNext(None, 0));
+                        self.unpack(vars);
+                        next
+                    }
+                };
+                self.block(body);
+                self.emit(Op::Jump(next));
+                self.patch(start);
+                self.patch(next);
+            }
+            Statement::Match(subject, arms) => {
+                self.expr(subject);
+                let mut ends = Vec::new();
+                for arm in arms {
+                    self.patterns.push(arm.pattern.clone());
+                    let next = self.emit(Op::Match(self.patterns.len() - 1, 0));
+                    let guard = arm.guard.as_ref().map(|guard| {
+                        self.expr(guard);
+                        self.emit(Op::Guard(0))
+                    });
+                    self.block(&arm.body);
+                    self.emit(Op::Unbind);
+                    ends.push(self.emit(Op::Jump(0)));
+                    self.patch(next);
+                    if let Some(guard) = guard { self.patch(guard); }
+                }
+                self.emit(Op::NoMatch);
+                for end in ends {
+                    self.patch(end);
+                }
+                self.emit(Op::Pop);
+            }
+            Statement::FunctionDef(name, params, body) => {
+                self.function(name, Function::new(params.clone(), body.clone()));
+            }
+            Statement::QuickFunctionDef(name, params, expr) => {
+                let body = vec![Stmt { line: stmt.line, end_line: stmt.line, kind: Statement::Return(expr.clone()) }];
+                self.function(name, Function::new(params.clone(), body));
+            }
+            Statement::FunctionCall(name, args, mutates) => {
+                self.call(name, args, *mutates);
+                self.emit(Op::Pop);
+            }
+            Statement::Return(Expr::FunctionCall(name, args, false)) if !BUILTINS.iter().any(|b| b.symbol == name) => {
+                for arg in args {
+                    self.expr(arg);
+                }
+                let func = self.slot(name);
+                let name = self.name(name);
+                self.emit(Op::TailCall { name, func, args: args.len() });
+            }
+            Statement::Return(expr) => {
+                self.expr(expr);
+                self.emit(Op::Return);
+            }
+            Statement::Input(vars, prompt, is_iter) => {
+                self.inputs.push((vars.clone(), prompt.clone(), *is_iter));
+                self.emit(Op::Read(self.inputs.len() - 1));
+            }
+            Statement::Import(path, names) => {
+                self.imports.push((path.clone(), names.clone()));
+                self.emit(Op::Import(self.imports.len() - 1));
+            }
+            Statement::Comment(_) => {}
+        }
+    }
+
+    fn function(&mut self, name: &str, func: Function) {
+        let name = self.symbols.symbol(name);
+        self.functions.push(Rc::new(Function { file: self.file.clone(), ..func }));
+        self.emit(Op::Define(name, self.functions.len() - 1));
+    }
+
+    fn binary(&mut self, op: &str) {
+        match BinOp::from_symbol(op) {
+            Some(op) => self.emit(Op::Binary(op)),
+            None => self.emit(Op::Unknown),
+        };
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], mutates: bool) {
+        for arg in args {
+            self.expr(arg);
+        }
+        let func = match BUILTINS.iter().any(|b| b.symbol == name) {
+            true => None,
+            false => Some(self.slot(name)),
+        };
+        let name = self.name(name);
+        let op = match args.first() {
+            Some(Expr::Variable(var)) if mutates => Op::CallStore { name, func, args: args.len(), target: self.var(var) },
+            _ => Op::Call { name, func, args: args.len(), mutates },
+        };
+        self.emit(op);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Number(n) => {
+                let c = self.constant(Value::Number(*n));
+                self.emit(Op::Const(c));
+            }
+            Expr::Text(s) => {
+                let c = self.constant(Value::Text(Rc::new(s.clone())));
+                self.emit(Op::Const(c));
+            }
+            Expr::Bool(b) => {
+                let c = self.constant(Value::Bool(*b));
+                self.emit(Op::Const(c));
+            }
+            Expr::Variable(name) => {
+                self.emit(Op::Load(self.var(name)));
+            }
+            Expr::List(items) => {
+                for item in items {
+                    self.expr(item);
+                }
+                self.emit(Op::List(items.len()));
+            }
+            Expr::Index(list, index) => {
+                self.expr(list);
+                self.expr(index);
+                self.emit(Op::Index);
+            }
+            Expr::BinaryOp(left, op, right) => {
+                self.expr(left);
+                self.expr(right);
+                self.binary(op);
+            }
+            Expr::FunctionCall(name, args, mutates) => self.call(name, args, *mutates),
+            Expr::Input => {
+                self.emit(Op::Input);
+            }
+        }
+    }
+}
+
+impl Interpreter {
+    // The function's body as bytecode, compiled against this interpreter's
+    // symbols.
+    pub(crate) fn function_code(&self, func: &Function) -> Rc<Chunk> {
+        let mut code = func.code.borrow_mut();
+        match &*code {
+            Some(chunk) if Rc::ptr_eq(&chunk.symbols, &self.symbols) => chunk.clone(),
+            _ => code.insert(Rc::new(compile_function(func, &self.symbols))).clone(),
+        }
+    }
+
+    // Like `run_block`: the value of a `->`, or `Some(Nothing)` after an error.
+    pub(crate) fn run_chunk(&mut self, chunk: &Chunk) -> Option<Value> {
+        // What the `?>` arms we're in bound, innermost last, given back
+        // however the chunk ends.
+        let mut bound = Vec::new();
+        let result = self.run_code(chunk, &mut bound);
+        while let Some(saved) = bound.pop() {
+            self.unbind(saved);
+        }
+        result
+    }
+
+    fn run_code(&mut self, chunk: &Chunk, bound: &mut Vec<Vec<(usize, Option<Value>)>>) -> Option<Value> {
+        let mut stack: Vec<Value> = Vec::new();
+        // Items still to come for each `>>` loop we're in, innermost last.
+        let mut loops: Vec<(Rc<Vec<Value>>, usize)> = Vec::new();
+        let mut pc = 0;
+        while let Some(&op) = chunk.code.get(pc) {
+            pc += 1;
+            match op {
+                Op::Line(line) => {
+                    if self.error.is_some() { return Some(Value::Nothing); }
+                    self.line = line;
+                    if !self.tick() { return Some(Value::Nothing); }
+                    if let Some(profiler) = &mut self.profiler { profiler.hit(line); }
+                }
+                Op::Const(c) => stack.push(chunk.constants[c].clone()),
+                Op::Load(slot) => stack.push(self.load(slot)),
+                Op::Store(slot) => {
+                    let val = stack.pop().unwrap_or(Value::Nothing);
+                    self.store(slot, val);
+                }
+                Op::List(n) => {
+                    let items = stack.split_off(stack.len() - n);
+                    let list = self.sized(Value::List(Rc::new(items)));
+                    stack.push(list);
+                }
+                Op::Index => {
+                    let index = stack.pop().unwrap_or(Value::Nothing);
+                    let list = stack.pop().unwrap_or(Value::Nothing);
+                    stack.push(index_value(list, index));
+                }
+                Op::Binary(op) => {
+                    let right = stack.pop().unwrap_or(Value::Nothing);
+                    let left = stack.pop().unwrap_or(Value::Nothing);
+                    let val = self.sized(binary(&left, op, &right));
+                    stack.push(val);
+                }
+                Op::Update(slot, op) => {
+                    let operand = stack.pop().unwrap_or(Value::Nothing);
+                    let current = stack.pop().unwrap_or(Value::Nothing);
+                    self.release(slot, &current);
+                    let val = self.sized(append(current, op, &operand));
+                    self.store(slot, val);
+                }
+                Op::Unknown => {
+                    stack.truncate(stack.len().saturating_sub(2));
+                    stack.push(Value::Nothing);
+                }
+                Op::Call { name, func, args, mutates } => {
+                    let args = stack.split_off(stack.len() - args);
+                    let result = self.call_op(&chunk.names[name], func, args, mutates);
+                    stack.push(self.sized(result));
+                }
+                Op::CallStore { name, func, args, target } => {
+                    let args = stack.split_off(stack.len() - args);
+                    if func.is_none() && in_place(&chunk.names[name], args.len()) && let Some(val) = args.first() {
+                        self.release(target, val);
+                    }
+                    let result = self.call_op(&chunk.names[name], func, args, true);
+                    let result = self.sized(result);
+                    self.store(target, result.clone());
+                    stack.push(result);
+                }
+                Op::TailCall { name, func, args } => {
+                    let args = stack.split_off(stack.len() - args);
+                    let fn_val = self.load(func);
+                    return Some(self.return_call(&chunk.names[name], fn_val, args));
+                }
+                Op::Input => {
+                    let input = self.read_input("+? ");
+                    stack.push(self.parse_input_value(&input));
+                }
+                Op::Read(i) => {
+                    let (vars, prompt, is_iter) = &chunk.inputs[i];
+                    self.read_vars(vars, prompt, *is_iter);
+                }
+                Op::Import(i) => {
+                    let (path, names) = &chunk.imports[i];
+                    self.import(path, names);
+                }
+                Op::Define(name, func) => self.define(name, Value::Function(chunk.functions[func].clone())),
+                Op::Print => {
+                    let val = stack.pop().unwrap_or(Value::Nothing);
+                    if val != Value::Nothing && self.error.is_none() {
+                        let text = self.render(&val, false);
+                        if self.error.is_none() { self.console.write(&format!("{}\n", text)); }
+                    }
+                }
+                Op::Pop => {
+                    stack.pop();
+                }
+                Op::Return => return Some(stack.pop().unwrap_or(Value::Nothing)),
+                Op::Jump(target) => {
+                    // Back to the top of a loop: one more time round.
+                    if target < pc && !self.tick() { return Some(Value::Nothing); }
+                    pc = target;
+                }
+                Op::JumpUnlessTrue(target) => {
+                    if !matches!(stack.pop(), Some(Value::Bool(true))) { pc = target; }
+                }
+                Op::JumpIfError(target) => {
+                    if self.error.is_some() { pc = target; }
+                }
+                Op::JumpIfNothing(target) => {
+                    if stack.last() == Some(&Value::Nothing) {
+                        stack.pop();
+                        pc = target;
+                    }
+                }
+                Op::IterStart(target) => match stack.pop() {
+                    Some(Value::List(items)) => loops.push((items, 0)),
+                    _ => pc = target,
+                },
+                Op::IterNext(var, target) => match loops.last_mut().and_then(|(items, i)| {
+                    *i += 1;
+                    items.get(*i - 1).cloned()
+                }) {
+                    Some(item) => match var {
+                        Some(var) => self.store(var, item),
+                        None => stack.push(item),
+                    },
+                    None => {
+                        loops.pop();
+                        pc = target;
+                    }
+                },
+                Op::Unpack(vars) => {
+                    let val = stack.pop().unwrap_or(Value::Nothing);
+                    self.assign_vars(&chunk.vars[vars], val);
+                }
+                Op::Match(pattern, next) => {
+                    let mut binds = Vec::new();
+                    let subject = stack.last().unwrap_or(&Value::Nothing);
+                    if self.error.is_none() && chunk.patterns[pattern].matches(subject, &mut binds) {
+                        let saved = self.bind(binds);
+                        bound.push(saved);
+                    } else {
+                        pc = next;
+                    }
+                }
+                Op::Guard(next) => {
+                    if !matches!(stack.pop(), Some(Value::Bool(true))) {
+                        if let Some(saved) = bound.pop() { self.unbind(saved); }
+                        pc = next;
+                    }
+                }
+                Op::Unbind => {
+                    if let Some(saved) = bound.pop() { self.unbind(saved); }
+                }
+                Op::NoMatch => {
+                    let subject = stack.last().cloned().unwrap_or(Value::Nothing);
+                    self.no_match(&subject);
+                }
+            }
+        }
+        None
+    }
+
+    fn call_op(&mut self, name: &str, func: Option<Slot>, args: Vec<Value>, mutates: bool) -> Value {
+        match func {
+            Some(slot) => {
+                let fn_val = self.load(slot);
+                self.call_user(name, &fn_val, args)
+            }
+            None => self.call_function(name, args, mutates),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+// Builds programs with `lazy build` and runs what it writes from somewhere
+// else, after the sources are gone, to check that everything the program
+// imports went into the bundle at the end of the executable.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use lazy::build::Bundle;
+
+const GAME: &str = "\
+<- \"lib/dice.lazy\" : roll
+<- \"words.lazy\" : shout
+shout(\"rolled \" + $(roll(2)))
+";
+
+const DICE: &str = "<- \"faces.lazy\" : faces\nroll(n) ~> n * faces\n";
+const FACES: &str = "faces = 6\n";
+const WORDS: &str = "shout(text) ~> text + \"!\"\n";
+
+// Writes `files` into a directory of their own, emptied first.
+fn scratch(case: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("build").join(case);
+    let _ = fs::remove_dir_all(&dir);
+    for (name, code) in files {
+        let file = dir.join(name);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, code).unwrap();
+    }
+    dir
+}
+
+fn lazy(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lazy")).args(args).current_dir(dir).output().unwrap()
+}
+
+#[test]
+fn built_program_runs_without_its_sources() {
+    let dir = scratch("game", &[
+        ("src/game.lazy", GAME),
+        ("src/lib/dice.lazy", DICE),
+        ("src/lib/faces.lazy", FACES),
+        ("shared/words.lazy", WORDS),
+    ]);
+    let output = lazy(&dir, &["build", "src/game.lazy", "-o", "game", "-I", "shared"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Built game\n");
+
+    // The bundle is at the very end, up to `MAGIC`, with paths from the
+    // script's directory, and modules found with `-I` by their import.
+    let built = fs::read(dir.join("game")).unwrap();
+    assert!(built.ends_with(b"LAZYBNDL"));
+    let bundle = Bundle::from_bytes(&built).unwrap();
+    let mut files: Vec<(&Path, &str)> = bundle.files().collect();
+    assert_eq!(files[0], (Path::new("game.lazy"), GAME));
+    files.sort();
+    assert_eq!(files, [
+        (Path::new("game.lazy"), GAME),
+        (Path::new("lib/dice.lazy"), DICE),
+        (Path::new("lib/faces.lazy"), FACES),
+        (Path::new("words.lazy"), WORDS),
+    ]);
+
+    let elsewhere = scratch("elsewhere", &[]);
+    fs::create_dir_all(&elsewhere).unwrap();
+    fs::rename(dir.join("game"), elsewhere.join("game")).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    let output = Command::new(elsewhere.join("game")).current_dir(env!("CARGO_TARGET_TMPDIR")).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "rolled 12!\n");
+}
+
+#[test]
+fn missing_module_fails_the_build() {
+    let dir = scratch("missing", &[("game.lazy", "<- \"nowhere.lazy\" : x\nx\n")]);
+    let output = lazy(&dir, &["build", "game.lazy"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot find module \"nowhere.lazy\""), "{:?}", output);
+    assert!(!dir.join("game").exists());
+}
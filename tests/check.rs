@@ -0,0 +1,41 @@
+// Runs `lazy check` on every program in `tests/check`, one per kind of
+// mistake it reports, and compares what it prints with the `.out` file next
+// to it. It exits with an error only when something is an error.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/check");
+    let mut files: Vec<PathBuf> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lazy"))
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn check_matches_snapshots() {
+    let files = programs();
+    assert!(!files.is_empty(), "no programs in tests/check");
+    for file in &files {
+        let expected = fs::read_to_string(file.with_extension("out")).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_lazy"))
+            .arg("check")
+            .arg(file.file_name().unwrap())
+            .current_dir(file.parent().unwrap())
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), expected, "{}", file.display());
+        let errors = expected.lines().any(|line| line.contains(": error: "));
+        assert_eq!(output.status.success(), !errors, "{}", file.display());
+    }
+}
+
+#[test]
+fn check_needs_a_file() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lazy")).arg("check").output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,42 @@
+// Runs `lazy` with options it knows and ones it doesn't.
+
+use std::process::{Command, Output};
+
+fn lazy(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lazy")).args(args).current_dir(env!("CARGO_TARGET_TMPDIR")).output().unwrap()
+}
+
+#[test]
+fn help_lists_the_options() {
+    for flag in ["--help", "-h"] {
+        let output = lazy(&[flag]);
+        assert!(output.status.success(), "{:?}", output);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("Usage: lazy [options] [file.lazy]\n"), "{}", stdout);
+        assert!(stdout.contains("--max-statements <n>"), "{}", stdout);
+    }
+}
+
+#[test]
+fn unknown_flags_are_not_files() {
+    let output = lazy(&["--nope", "program.lazy"]);
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Error: unknown flag --nope\nUsage: lazy [options] [file.lazy]\n"), "{}", stderr);
+    assert!(output.stdout.is_empty(), "{:?}", output);
+}
+
+#[test]
+fn commands_reject_unknown_flags_too() {
+    for (args, usage) in [
+        (&["build", "--nope", "game.lazy"][..], "Usage: lazy build "),
+        (&["debug", "-x", "game.lazy"][..], "Usage: lazy debug "),
+        (&["test", "--verbose"][..], "Usage: lazy test "),
+        (&["transpile", "--target", "js", "--nope", "game.lazy"][..], "Usage: lazy transpile "),
+    ] {
+        let output = lazy(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(usage), "{:?}: {}", args, stderr);
+    }
+}
@@ -0,0 +1,46 @@
+// Runs every program in `tests/corpus` and compares what it prints with the
+// `.out` file next to it. A `.in` file holds the answers to its `+?` prompts.
+// Each program runs on both engines, with and without the optimizer, and all
+// four must print exactly the snapshot.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lazy::console::{BufferConsole, ScriptedConsole};
+use lazy::Interpreter;
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut files: Vec<PathBuf> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lazy"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn run(file: &Path, bytecode: bool, optimize: bool) -> String {
+    let answers = fs::read_to_string(file.with_extension("in")).unwrap_or_default();
+    let output = BufferConsole::new();
+    let mut interp = Interpreter::new();
+    interp.set_console(ScriptedConsole::new(answers.lines(), output.clone()));
+    interp.use_bytecode(bytecode);
+    interp.set_optimize(optimize);
+    match interp.run_file(file) {
+        Ok(()) => output.contents(),
+        Err(e) => format!("{}Error: {}\n", output.contents(), e),
+    }
+}
+
+#[test]
+fn corpus_matches_snapshots() {
+    let files = programs();
+    assert!(!files.is_empty(), "no programs in tests/corpus");
+    for file in &files {
+        let expected = fs::read_to_string(file.with_extension("out")).unwrap();
+        for (bytecode, optimize) in [(false, false), (false, true), (true, false), (true, true)] {
+            assert_eq!(run(file, bytecode, optimize), expected,
+                "{} (vm: {}, optimized: {})", file.display(), bytecode, optimize);
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// Drives `lazy debug` through piped stdin, the way someone at the prompt
+// would, and checks where it stops and what it shows.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+const PROGRAM: &str = "add(a b) => {\n  total = a + b\n  -> total\n}\ntwice(x) => {\n  y = add(x x)\n  -> y\n}\nr = twice(3)\nr\n\"done\"\n";
+
+// Writes `files` into a directory of their own and debugs the first one.
+fn debug(case: &str, files: &[(&str, &str)], commands: &str) -> Output {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("debugger").join(case);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for (name, code) in files {
+        fs::write(dir.join(name), code).unwrap();
+    }
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lazy"))
+        .arg("debug")
+        .arg(files[0].0)
+        .current_dir(PathBuf::from(&dir))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(commands.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn session(case: &str, files: &[(&str, &str)], commands: &str, expected: &str) {
+    let output = debug(case, files, commands);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), expected, "{}", case);
+    assert!(output.status.success(), "{}: {:?}", case, output);
+}
+
+#[test]
+fn step_next_and_out() {
+    session("step", &[("p.lazy", PROGRAM)], "s\ns\ns\ns\ns\nbt\np a + b\no\nn\nc\n", "\
+Debugging p.lazy. Type 'help' for commands.
+<main> line 1: add(a b) => {
+(debug) <main> line 5: twice(x) => {
+(debug) <main> line 9: r = twice(3)
+(debug) twice line 6: y = add(x x)
+(debug) add line 2: total = a + b
+(debug) add line 3: -> total
+(debug)   #0 add at line 3
+  #1 twice at line 6
+  #2 <main> at line 9
+(debug) 6
+(debug) twice line 7: -> y
+(debug) <main> line 10: r
+(debug) 6
+done
+Program finished.
+");
+}
+
+#[test]
+fn next_steps_over_calls() {
+    session("next", &[("p.lazy", PROGRAM)], "n\nn\nn\nn\nc\n", "\
+Debugging p.lazy. Type 'help' for commands.
+<main> line 1: add(a b) => {
+(debug) <main> line 5: twice(x) => {
+(debug) <main> line 9: r = twice(3)
+(debug) <main> line 10: r
+(debug) 6
+<main> line 11: \"done\"
+(debug) done
+Program finished.
+");
+}
+
+#[test]
+fn breakpoints() {
+    session("break", &[("p.lazy", PROGRAM)], "b 10\nb add\nbreaks\nc\nv\nd 2\nbreaks\nc\nc\n", "\
+Debugging p.lazy. Type 'help' for commands.
+<main> line 1: add(a b) => {
+(debug) Breakpoint 1 at line 10
+(debug) Breakpoint 2 at function add
+(debug)   1: line 10
+  2: function add
+(debug) add line 2: total = a + b
+(debug) globals:
+  add(a b)
+  twice(x)
+twice:
+  x = 3
+add:
+  a = 3
+  b = 3
+(debug) (debug)   1: line 10
+(debug) <main> line 10: r
+(debug) 6
+done
+Program finished.
+");
+}
+
+#[test]
+fn list_and_quit() {
+    session("quit", &[("p.lazy", PROGRAM)], "b 10\nc\nl\nq\n", "\
+Debugging p.lazy. Type 'help' for commands.
+<main> line 1: add(a b) => {
+(debug) Breakpoint 1 at line 10
+(debug) <main> line 10: r
+(debug)      5  twice(x) => {
+     6    y = add(x x)
+     7    -> y
+     8  }
+     9  r = twice(3)
+>   10  r
+    11  \"done\"
+(debug) ");
+}
+
+#[test]
+fn stops_in_modules() {
+    let files = [
+        ("main.lazy", "<- \"shapes.lazy\" : area\n<- \"shapes.lazy\"\nx = area(1)\ny = shapes.area(2)\n"),
+        ("shapes.lazy", "pi = 3\narea(r) => {\n  -> pi * r * r\n}\n"),
+    ];
+    session("modules", &files, "b area\nb 3\nc\nc\nc\nbt\nc\n", "\
+Debugging main.lazy. Type 'help' for commands.
+<main> line 1: <- \"shapes.lazy\" : area
+(debug) Breakpoint 1 at function area
+(debug) Breakpoint 2 at line 3
+(debug) <main> line 3: x = area(1)
+(debug) area line 3 of shapes.lazy: -> pi * r * r
+(debug) shapes.area line 3 of shapes.lazy: -> pi * r * r
+(debug)   #0 shapes.area at line 3 of shapes.lazy
+  #1 <main> at line 4
+(debug) Program finished.
+");
+}
+
+#[test]
+fn end_of_input_lets_the_program_finish() {
+    session("eof", &[("p.lazy", PROGRAM)], "b add\n", "\
+Debugging p.lazy. Type 'help' for commands.
+<main> line 1: add(a b) => {
+(debug) Breakpoint 1 at function add
+(debug) \n6
+done
+Program finished.
+");
+}
+
+#[test]
+fn errors_exit_with_1() {
+    let output = debug("error", &[("p.lazy", "x = 1\n==(x 2)\n\"after\"\n")], "c\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("assertion failed"), "{:?}", output);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("after"), "{:?}", output);
+}
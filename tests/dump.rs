@@ -0,0 +1,50 @@
+// Runs `lazy --dump-ast` and `lazy --dump-ast=json` on every program in
+// `tests/dump` and compares the trees with the `.sexpr` and `.json` files
+// next to it. Syntax errors go to stderr in the S-expression form and under
+// `errors` in the JSON, and both exit with an error.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/dump");
+    let mut files: Vec<PathBuf> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lazy"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn dump(flag: &str, file: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lazy")).arg(flag).arg(file).output().unwrap()
+}
+
+#[test]
+fn dumps_match_snapshots() {
+    let files = programs();
+    assert!(!files.is_empty(), "no programs in tests/dump");
+    for file in &files {
+        let json = dump("--dump-ast=json", file);
+        assert_eq!(String::from_utf8_lossy(&json.stdout), fs::read_to_string(file.with_extension("json")).unwrap(),
+            "{}", file.display());
+        let has_errors = !String::from_utf8_lossy(&json.stdout).contains("\"errors\": []");
+        assert_eq!(json.status.success(), !has_errors, "{}", file.display());
+
+        let sexpr = dump("--dump-ast", file);
+        assert_eq!(String::from_utf8_lossy(&sexpr.stdout), fs::read_to_string(file.with_extension("sexpr")).unwrap(),
+            "{}", file.display());
+        assert_eq!(sexpr.status.success(), !has_errors, "{}", file.display());
+        assert_eq!(sexpr.stderr.is_empty(), !has_errors, "{}", file.display());
+    }
+}
+
+#[test]
+fn syntax_errors_are_listed() {
+    let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/dump/errors.lazy");
+    let output = dump("--dump-ast", &file);
+    assert_eq!(String::from_utf8_lossy(&output.stderr),
+        "Error: line 2: Unexpected '}' without an open block\n\
+         Error: line 3: Unexpected '??' without matching '?' (Orphaned Else)\n");
+}
@@ -0,0 +1,66 @@
+// Formats every program in `tests/corpus`, and `tests/fmt/every_syntax.lazy`
+// which has every kind of statement and expression, and checks that the
+// result parses back to the same program and that formatting it again
+// changes nothing.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lazy::fmt::format_source;
+use lazy::{parse_program, ChildMut, Statement, Stmt};
+
+fn programs() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let mut files: Vec<PathBuf> = ["corpus", "fmt"].iter()
+        .flat_map(|dir| fs::read_dir(root.join(dir)).unwrap())
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lazy"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn parse(file: &Path, code: &str) -> Vec<Stmt> {
+    let (mut stmts, errors) = parse_program(code);
+    assert!(errors.is_empty(), "{}: {:?}", file.display(), errors);
+    forget_lines(&mut stmts);
+    stmts
+}
+
+// Formatting moves code between lines; only what the program says counts.
+fn forget_lines(stmts: &mut Vec<Stmt>) {
+    for stmt in stmts {
+        stmt.line = 0;
+        stmt.end_line = 0;
+        if let Statement::Match(_, arms) = &mut stmt.kind {
+            for arm in arms {
+                arm.line = 0;
+                arm.end_line = 0;
+            }
+        }
+        for child in stmt.children_mut() {
+            if let ChildMut::Block(block) = child {
+                forget_lines(block);
+            }
+        }
+    }
+}
+
+#[test]
+fn formatting_keeps_the_program() {
+    let files = programs();
+    assert!(files.iter().any(|file| file.ends_with("fmt/every_syntax.lazy")), "no tests/fmt/every_syntax.lazy");
+    for file in &files {
+        let code = fs::read_to_string(file).unwrap();
+        let formatted = format_source(&code).unwrap();
+        assert_eq!(parse(file, &formatted), parse(file, &code), "{} means something else formatted:\n{}", file.display(), formatted);
+        assert_eq!(format_source(&formatted).unwrap(), formatted, "{} changes when formatted twice", file.display());
+    }
+}
+
+#[test]
+fn every_syntax_is_not_already_formatted() {
+    let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fmt/every_syntax.lazy");
+    let code = fs::read_to_string(file).unwrap();
+    assert_ne!(format_source(&code).unwrap(), code, "the formatter has nothing to do on it");
+}
@@ -0,0 +1,164 @@
+// Starts `lazy lsp` and talks to it the way an editor does: initialize, open a
+// document, ask for hover, definition, symbols and completion, then shut down.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use lazy::json::Json;
+
+const URI: &str = "file:///project/main.lazy";
+const TEXT: &str = "// Adds two numbers.\nadd(a b) => {\n  -> a + b\n}\ntotal = add(1 2)\n#(total)\nmissing + 1\n";
+
+struct Client {
+    server: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    next_id: usize,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut server = Command::new(env!("CARGO_BIN_EXE_lazy"))
+            .arg("lsp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let input = server.stdin.take().unwrap();
+        let output = BufReader::new(server.stdout.take().unwrap());
+        Client { server, input, output, next_id: 1 }
+    }
+
+    fn send(&mut self, message: Json) {
+        let body = message.to_string();
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.input.flush().unwrap();
+    }
+
+    fn receive(&mut self) -> Json {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            assert!(self.output.read_line(&mut header).unwrap() > 0, "the server closed its output");
+            let header = header.trim_end();
+            if header.is_empty() { break; }
+            if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        self.output.read_exact(&mut body).unwrap();
+        Json::parse(&String::from_utf8(body).unwrap()).unwrap()
+    }
+
+    fn notify(&mut self, method: &str, params: Json) {
+        self.send(Json::object(vec![("jsonrpc", Json::str("2.0")), ("method", Json::str(method)), ("params", params)]));
+    }
+
+    // The result of the request, which must be the next message.
+    fn request(&mut self, method: &str, params: Json) -> Json {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.send(Json::object(vec![
+            ("jsonrpc", Json::str("2.0")),
+            ("id", Json::Number(id as f64)),
+            ("method", Json::str(method)),
+            ("params", params),
+        ]));
+        let reply = self.receive();
+        assert_eq!(reply.get("id").and_then(Json::as_f64), Some(id as f64), "{}", reply);
+        reply.get("result").cloned().unwrap_or_else(|| panic!("{} failed: {}", method, reply))
+    }
+}
+
+// The document and a 0-based position in it.
+fn at(line: usize, character: usize) -> Json {
+    Json::object(vec![
+        ("textDocument", Json::object(vec![("uri", Json::str(URI))])),
+        ("position", Json::object(vec![
+            ("line", Json::Number(line as f64)),
+            ("character", Json::Number(character as f64)),
+        ])),
+    ])
+}
+
+fn number(json: &Json, path: &[&str]) -> f64 {
+    json.at(path).and_then(Json::as_f64).unwrap_or_else(|| panic!("no {:?} in {}", path, json))
+}
+
+fn text<'a>(json: &'a Json, path: &[&str]) -> &'a str {
+    json.at(path).and_then(Json::as_str).unwrap_or_else(|| panic!("no {:?} in {}", path, json))
+}
+
+fn items(json: &Json) -> &[Json] {
+    match json {
+        Json::Array(items) => items,
+        other => panic!("expected an array, got {}", other),
+    }
+}
+
+#[test]
+fn editor_session() {
+    let mut client = Client::start();
+
+    let init = client.request("initialize", Json::object(vec![("capabilities", Json::object(vec![]))]));
+    assert_eq!(text(&init, &["serverInfo", "name"]), "lazy");
+    assert_eq!(number(&init, &["capabilities", "textDocumentSync"]), 1.0);
+    client.notify("initialized", Json::object(vec![]));
+
+    client.notify("textDocument/didOpen", Json::object(vec![("textDocument", Json::object(vec![
+        ("uri", Json::str(URI)),
+        ("languageId", Json::str("lazy")),
+        ("version", Json::Number(1.0)),
+        ("text", Json::str(TEXT)),
+    ]))]));
+    let published = client.receive();
+    assert_eq!(text(&published, &["method"]), "textDocument/publishDiagnostics");
+    assert_eq!(text(&published, &["params", "uri"]), URI);
+    let diagnostics = items(published.at(&["params", "diagnostics"]).unwrap());
+    assert_eq!(diagnostics.len(), 1, "{}", published);
+    assert_eq!(number(&diagnostics[0], &["range", "start", "line"]), 6.0);
+    assert_eq!(number(&diagnostics[0], &["severity"]), 2.0);
+    assert_eq!(text(&diagnostics[0], &["message"]), "`missing` is never assigned, so it is always nothing");
+
+    let hover = client.request("textDocument/hover", at(4, 9));
+    assert_eq!(text(&hover, &["contents", "value"]), "```lazy\nadd(a b)\n```");
+    let hover = client.request("textDocument/hover", at(5, 0));
+    assert!(text(&hover, &["contents", "value"]).starts_with("```lazy\n#("), "{}", hover);
+    assert_eq!(client.request("textDocument/hover", at(0, 0)), Json::Null);
+
+    let definition = client.request("textDocument/definition", at(4, 9));
+    assert_eq!(text(&definition, &["uri"]), URI);
+    assert_eq!(number(&definition, &["range", "start", "line"]), 1.0);
+    assert_eq!(number(&definition, &["range", "start", "character"]), 0.0);
+    assert_eq!(number(&definition, &["range", "end", "character"]), 3.0);
+
+    let symbols = client.request("textDocument/documentSymbol", Json::object(vec![
+        ("textDocument", Json::object(vec![("uri", Json::str(URI))])),
+    ]));
+    let symbols = items(&symbols);
+    assert_eq!(symbols.len(), 1, "{:?}", symbols);
+    assert_eq!(text(&symbols[0], &["name"]), "add");
+    assert_eq!(text(&symbols[0], &["detail"]), "(a b)");
+    assert_eq!(number(&symbols[0], &["range", "start", "line"]), 1.0);
+    assert_eq!(number(&symbols[0], &["range", "end", "line"]), 3.0);
+
+    let completion = client.request("textDocument/completion", at(5, 0));
+    let labels: Vec<&str> = items(&completion).iter().map(|item| text(item, &["label"])).collect();
+    assert!(labels.contains(&"add") && labels.contains(&"total"), "{:?}", labels);
+    let add = items(&completion).iter().find(|item| text(item, &["label"]) == "add").unwrap();
+    assert_eq!(text(add, &["detail"]), "add(a b)");
+
+    assert_eq!(client.request("shutdown", Json::Null), Json::Null);
+    client.notify("exit", Json::Null);
+    assert_eq!(client.server.wait().unwrap().code(), Some(0));
+}
+
+#[test]
+fn exit_without_shutdown_fails() {
+    let mut client = Client::start();
+    client.request("initialize", Json::object(vec![]));
+    client.notify("exit", Json::Null);
+    assert_eq!(client.server.wait().unwrap().code(), Some(1));
+}
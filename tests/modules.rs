@@ -0,0 +1,161 @@
+// Imports: what a module exposes and how it is found, and imports that can't
+// be done, which stop the program with an error the embedder gets back
+// instead of printing it and going on.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use lazy::console::BufferConsole;
+use lazy::{Error, Interpreter};
+
+// Writes `files` into a directory of their own and runs the first one, with
+// `search_path` (relative to that directory) for `-I`.
+fn run_with(case: &str, files: &[(&str, &[u8])], search_path: &[&str]) -> (String, Result<(), Error>) {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("modules").join(case);
+    let _ = fs::remove_dir_all(&dir);
+    for (name, code) in files {
+        let file = dir.join(name);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, code).unwrap();
+    }
+    let output = BufferConsole::new();
+    let mut interp = Interpreter::new();
+    interp.set_console(output.clone());
+    for path in search_path {
+        interp.add_search_path(dir.join(path));
+    }
+    let result = interp.run_file(PathBuf::from(&dir).join(files[0].0));
+    (output.contents(), result)
+}
+
+fn run(case: &str, files: &[(&str, &[u8])]) -> (String, Result<(), Error>) {
+    run_with(case, files, &[])
+}
+
+fn prints(case: &str, files: &[(&str, &[u8])], search_path: &[&str], expected: &str) {
+    let (output, result) = run_with(case, files, search_path);
+    assert!(result.is_ok(), "{}: {:?}", case, result);
+    assert_eq!(output, expected, "{}", case);
+}
+
+const SHAPES: &[u8] = b"pi = 3\narea(r) ~> pi * r * r\nsquare(x) ~> x * x\n";
+
+#[test]
+fn namespaced_names() {
+    prints("namespaced", &[
+        ("main.lazy", b"<- \"shapes.lazy\"\nshapes.pi\nshapes.area(2)\nshapes.square(5)\n"),
+        ("shapes.lazy", SHAPES),
+    ], &[], "3\n12\n25\n");
+}
+
+#[test]
+fn module_keeps_its_own_names() {
+    // `area` reads the module's `pi`, not the importer's.
+    prints("own_names", &[
+        ("main.lazy", b"pi = 100\n<- \"shapes.lazy\" : area\narea(1)\npi\n"),
+        ("shapes.lazy", SHAPES),
+    ], &[], "3\n100\n");
+}
+
+#[test]
+fn selected_names() {
+    prints("selected", &[
+        ("main.lazy", b"<- \"shapes.lazy\" : area square\narea(1)\nsquare(4)\nshapes.pi\n"),
+        ("shapes.lazy", SHAPES),
+    ], &[], "3\n16\n3\n");
+}
+
+#[test]
+fn module_runs_once() {
+    prints("once", &[
+        ("main.lazy", b"<- \"counter.lazy\"\n<- \"other.lazy\"\n<- \"counter.lazy\" : n\nn\n"),
+        ("counter.lazy", b"\"loading counter\"\nn = 1\n"),
+        ("other.lazy", b"<- \"counter.lazy\"\n\"loading other\"\n"),
+    ], &[], "loading counter\nloading other\n1\n");
+}
+
+#[test]
+fn search_path() {
+    prints("search_path", &[
+        ("app/main.lazy", b"<- \"shapes.lazy\" : square\n<- \"local.lazy\"\nsquare(3)\nlocal.x\n"),
+        ("app/local.lazy", b"x = \"next to main\"\n"),
+        ("lib/shapes.lazy", SHAPES),
+    ], &["lib"], "9\nnext to main\n");
+}
+
+#[test]
+fn search_path_comes_after_the_importing_file() {
+    prints("search_order", &[
+        ("app/main.lazy", b"<- \"util.lazy\"\nutil.x\n"),
+        ("app/util.lazy", b"x = \"app\"\n"),
+        ("lib/util.lazy", b"x = \"lib\"\n"),
+    ], &["lib"], "app\n");
+}
+
+fn fails(case: &str, files: &[(&str, &[u8])], line: usize, message: &str) {
+    let (output, result) = run(case, files);
+    match result {
+        Err(Error::Runtime(err)) => {
+            assert_eq!(err.line, line, "{}", case);
+            assert_eq!(err.message, message, "{}", case);
+        }
+        other => panic!("{}: expected a runtime error, got {:?}", case, other),
+    }
+    assert_eq!(output, "", "{}: the program went on after the import", case);
+}
+
+#[test]
+fn missing_module() {
+    fails("missing", &[("main.lazy", b"<- \"nowhere.lazy\"\n\"after\"\n")], 1, "cannot find module \"nowhere.lazy\"");
+}
+
+#[test]
+fn import_cycle() {
+    fails("cycle", &[
+        ("a.lazy", b"<- \"b.lazy\"\n\"after\"\n"),
+        ("b.lazy", b"<- \"a.lazy\"\n"),
+    ], 1, "import cycle: a.lazy -> b.lazy -> a.lazy");
+}
+
+#[test]
+fn missing_name() {
+    fails("name", &[
+        ("main.lazy", b"<- \"shapes.lazy\" : area volume\n\"after\"\n"),
+        ("shapes.lazy", b"area(w h) ~> w * h\n"),
+    ], 1, "module \"shapes.lazy\" has no \"volume\"");
+}
+
+#[test]
+fn unreadable_module() {
+    let (_, result) = run("unreadable", &[("main.lazy", b"<- \"bytes.lazy\"\n"), ("bytes.lazy", b"\xff\xfe\n")]);
+    match result {
+        Err(Error::Runtime(err)) => assert!(err.message.starts_with("cannot read module \"bytes.lazy\": "), "{}", err.message),
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn clashing_modules() {
+    fails("clash", &[
+        ("main.lazy", b"<- \"a/util.lazy\"\n<- \"b/util.lazy\"\n\"after\"\n"),
+        ("a/util.lazy", b"x = 1\n"),
+        ("b/util.lazy", b"y = 2\n"),
+    ], 2, "module \"util.lazy\" clashes with \"util.lazy\" (both are named \"util\")");
+}
+
+#[test]
+fn module_with_syntax_errors() {
+    let (output, result) = run("syntax", &[
+        ("main.lazy", b"\"before\"\n<- \"bad.lazy\"\n\"after\"\n"),
+        ("bad.lazy", b"x = 1\n}\n"),
+    ]);
+    match result {
+        Err(Error::Parse(errors)) => {
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].line, 2);
+            assert_eq!(errors[0].message, "Unexpected '}' without an open block (in module \"bad.lazy\")");
+        }
+        other => panic!("expected syntax errors, got {:?}", other),
+    }
+    assert_eq!(output, "before\n");
+}
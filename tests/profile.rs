@@ -0,0 +1,92 @@
+// Runs programs with `--profile` and `--folded-stacks` and checks the counts
+// in the report. Times change from run to run, so only the names and counts
+// are compared.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const MAIN: &str = "\
+<- \"shapes.lazy\" : area
+sq(x) ~> x * x
+twice(x) => {
+  -> sq(x) + sq(x)
+}
+total = 0
+>> i [1 2 3] {
+  total = total + area(i) + twice(i)
+}
+total
+";
+
+const SHAPES: &str = "pi = 3\narea(r) => {\n  -> pi * r * r\n}\n";
+
+fn scratch(case: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("profile").join(case);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("main.lazy"), MAIN).unwrap();
+    fs::write(dir.join("shapes.lazy"), SHAPES).unwrap();
+    dir
+}
+
+fn lazy(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lazy")).args(args).current_dir(dir).output().unwrap()
+}
+
+// The lines of the report after the first one that starts with `heading`, up
+// to the next blank line, each without its time columns.
+fn section(report: &str, heading: &str, keep: usize) -> Vec<String> {
+    report.lines()
+        .skip_while(|line| !line.trim_start().starts_with(heading))
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .map(|line| line.split_whitespace().take(keep).collect::<Vec<_>>().join(" "))
+        .collect()
+}
+
+#[test]
+fn counts_calls_and_lines_in_each_file() {
+    let dir = scratch("counts");
+    for engine in [&[][..], &["--vm"][..]] {
+        let output = lazy(&dir, &[engine, &["--profile", "main.lazy"]].concat());
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "70\n");
+        let report = String::from_utf8_lossy(&output.stderr);
+
+        let mut functions = section(&report, "function", 2);
+        functions.sort();
+        assert_eq!(functions, ["<main> 1", "area 3", "sq 6", "twice 3"], "{:?}", engine);
+
+        // Defining a function runs its first line once too.
+        assert_eq!(section(&report, "line", 2), [
+            "2 7", "4 3", "8 3", "1 1", "3 1", "6 1", "7 1", "10 1",
+        ], "{:?}", engine);
+        // A module's lines are shown with its own code, not the program's.
+        let shapes = report.split("\nshapes.lazy\n").nth(1).unwrap_or_else(|| panic!("{}", report));
+        let shapes: Vec<&str> = shapes.lines().skip(1).map(str::trim).collect();
+        assert_eq!(shapes[0].split_whitespace().take(2).collect::<Vec<_>>(), ["3", "3"], "{:?}", engine);
+        assert!(shapes[0].ends_with("-> pi * r * r"), "{}", report);
+        assert!(shapes[1].ends_with("pi = 3"), "{}", report);
+    }
+}
+
+#[test]
+fn writes_folded_stacks() {
+    let dir = scratch("folded");
+    let output = lazy(&dir, &["--folded-stacks", "out.folded", "main.lazy"]);
+    assert!(output.status.success(), "{:?}", output);
+    let folded = fs::read_to_string(dir.join("out.folded")).unwrap();
+    let stacks: Vec<&str> = folded.lines().map(|line| {
+        let (stack, micros) = line.rsplit_once(' ').unwrap();
+        assert!(micros.parse::<u64>().is_ok(), "{}", line);
+        stack
+    }).collect();
+    assert_eq!(stacks, ["<main>", "<main>;area", "<main>;twice", "<main>;twice;sq"]);
+}
+
+#[test]
+fn folded_stacks_needs_a_file() {
+    let output = lazy(Path::new(env!("CARGO_TARGET_TMPDIR")), &["--folded-stacks"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "Error: --folded-stacks needs a file\n");
+}
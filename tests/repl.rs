@@ -0,0 +1,155 @@
+// Types into `lazy` with no file, the REPL, through a pipe and checks what it
+// answers. Prompts are taken out of the output; errors go to stderr.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+fn repl(args: &[&str], input: &str) -> (String, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lazy"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stdout = stdout.replace("lazy> ", "").replace("...> ", "");
+    let banner = stdout.find('\n').map_or(0, |i| i + 1);
+    (stdout[banner..].to_string(), String::from_utf8(output.stderr).unwrap())
+}
+
+fn answers(input: &str, expected: &str) {
+    for engine in [&[][..], &["--vm"][..]] {
+        let (stdout, stderr) = repl(engine, input);
+        assert_eq!(stdout, expected, "{:?}", engine);
+        assert_eq!(stderr, "", "{:?}", engine);
+    }
+}
+
+#[test]
+fn shows_each_value() {
+    answers("1 + 2\n\"hi\"\nx = 5\nx * 2\n", "3\nhi\n10\n");
+}
+
+#[test]
+fn shows_nothing() {
+    answers("f() => {\n}\nf()\n", "nothing\n");
+}
+
+#[test]
+fn shows_what_a_star_call_changed() {
+    answers("list = [3 1 2]\n++(list)*\n^(list -> 4)*\nlist\n", "[1 2 3]\n[1 2 3 4]\n[1 2 3 4]\n");
+}
+
+#[test]
+fn waits_for_open_blocks() {
+    answers("add(a b) => {\n  -> a + b\n}\nadd(2 3)\n>> x [1 2] {\n  x * 10\n}\n", "5\n10\n20\n");
+}
+
+#[test]
+fn joins_open_brackets() {
+    answers("xs = [1 2\n3]\nxs\n#(\nxs)\n", "[1 2 3]\n3\n");
+}
+
+#[test]
+fn waits_for_a_question_chain() {
+    let input = "x = 5\n? x > 10 {\n  \"big\"\n}\n?? x > 3 {\n  \"medium\"\n}\n?? {\n  \"small\"\n}\n\"after\"\n";
+    answers(input, "medium\nafter\n");
+    // A `?` with no `??` after it runs as soon as the next line comes.
+    answers("? yes {\n  \"then\"\n}\n\"next\"\n", "then\nnext\n");
+}
+
+#[test]
+fn runs_what_is_left_when_input_ends() {
+    answers("\"first\"\n? yes {\n  \"last\"\n}", "first\nlast\n");
+    answers("1\nexit\n2\n", "1\n");
+    answers("", "");
+}
+
+#[test]
+fn errors_stop_the_input_but_not_the_session() {
+    let (stdout, stderr) = repl(&[], "x = 1\nnope + 1\n==(1 2)\nx\n");
+    assert_eq!(stdout, "1\n");
+    assert!(stderr.contains("Error: line 1: undefined variable `nope`"), "{}", stderr);
+    assert!(stderr.contains("assertion failed"), "{}", stderr);
+}
+
+#[test]
+fn keeps_the_limits() {
+    let (_, stderr) = repl(&["--max-statements", "50"], "@ yes {\n}\n\"still here\"\n");
+    assert!(stderr.contains("ran more than 50 statements"), "{}", stderr);
+}
+
+// --- Commands ---
+
+fn scratch(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("repl");
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+#[test]
+fn vars_lists_variables_and_functions() {
+    answers("x = 1\nadd(a b) ~> a + b\nt = \"hi\"\n:vars\n", "add(a b)\nt = \"hi\"\nx = 1\n");
+}
+
+#[test]
+fn ast_shows_the_parse() {
+    answers(":ast x = 1 + 2\n", "(assign x (+ 1 2))\n");
+    let (stdout, _) = repl(&[], ":ast=json x\n");
+    assert!(stdout.contains("\"type\": \"Print\""), "{}", stdout);
+    assert!(stdout.contains("\"errors\": []"), "{}", stdout);
+}
+
+#[test]
+fn type_names_the_value() {
+    answers("t = \"hi\"\n:type t\n:type [1 2]\n:type 1 + 1\n:type 1 == 1\n", "text\nlist\nnumber\nbool\n");
+    let (_, stderr) = repl(&[], ":type nope\n");
+    assert!(stderr.contains("undefined variable `nope`"), "{}", stderr);
+}
+
+#[test]
+fn load_runs_a_file_in_the_session() {
+    let file = scratch("load.lazy");
+    fs::write(&file, "double(x) ~> x * 2\nloaded = \"yes\"\nloaded\n").unwrap();
+    answers(&format!(":load {}\ndouble(21)\n", file.display()), "yes\n42\n");
+    let (_, stderr) = repl(&[], ":load /no/such/file.lazy\n");
+    assert!(stderr.starts_with("Error: "), "{}", stderr);
+}
+
+#[test]
+fn save_writes_what_ran() {
+    let file = scratch("saved.lazy");
+    let input = format!(
+        "x = 1\ny = nope\nz = x + 1 // two\nadd(a b) => {{\n  -> a + b\n}}\n==(1 2)\nxs = [1\n2]\n:save {}\n",
+        file.display());
+    let (stdout, _) = repl(&[], &input);
+    assert!(stdout.ends_with(&format!("Saved to {}\n", file.display())), "{}", stdout);
+    let saved = fs::read_to_string(&file).unwrap();
+    assert_eq!(saved, "x = 1\nz = x + 1 // two\nadd(a b) => {\n  -> a + b\n}\nxs = [1 2]\n");
+    // What was saved runs on its own.
+    let output = Command::new(env!("CARGO_BIN_EXE_lazy")).arg(&file).output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+}
+
+#[test]
+fn reset_forgets_the_program_but_keeps_the_flags() {
+    let (stdout, stderr) = repl(&["--max-statements", "50"], "x = 1\n:reset\n:vars\nx\n@ yes {\n}\n");
+    assert_eq!(stdout, "");
+    assert!(stderr.contains("undefined variable `x`"), "{}", stderr);
+    assert!(stderr.contains("ran more than 50 statements"), "{}", stderr);
+}
+
+#[test]
+fn help_lists_commands_and_symbols() {
+    let (stdout, _) = repl(&[], ":help\n:help ><\n");
+    assert!(stdout.contains(":reset"), "{}", stdout);
+    assert!(stdout.ends_with("><(list -> val)            Does list contain val?\n"), "{}", stdout);
+    let (_, stderr) = repl(&[], ":nope\n");
+    assert_eq!(stderr, "Error: unknown command :nope (try :help)\n");
+}
@@ -0,0 +1,84 @@
+// Runs `lazy` with each sandbox flag on a program that goes past it, on both
+// engines, and checks the exit code and message the README promises.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn program(name: &str, code: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("sandbox");
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join(name);
+    fs::write(&file, code).unwrap();
+    file
+}
+
+fn lazy(args: &[&str], file: &PathBuf) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lazy")).args(args).arg(file).output().unwrap()
+}
+
+fn stops(name: &str, code: &str, flags: &[&str], exit_code: i32, message: &str) {
+    let file = program(name, code);
+    for engine in [None, Some("--vm")] {
+        let args: Vec<&str> = engine.into_iter().chain(flags.iter().copied()).collect();
+        let output = lazy(&args, &file);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(exit_code), "{} {:?}: {}", name, args, stderr);
+        assert!(stderr.contains(message), "{} {:?}: {}", name, args, stderr);
+    }
+}
+
+#[test]
+fn statements_limit_exits_with_3() {
+    stops("count.lazy", "n = 0\n@ yes {\n  n += 1\n}\n", &["--max-statements", "1000"], 3, "ran more than 1000 statements");
+}
+
+#[test]
+fn statements_limit_stops_an_empty_loop() {
+    stops("empty.lazy", "@ yes {\n}\n", &["--max-statements", "1000"], 3, "ran more than 1000 statements");
+    stops("empty_for.lazy", ">> x [1 2 3] {\n}\n", &["--max-statements", "2"], 3, "ran more than 2 statements");
+}
+
+#[test]
+fn value_size_limit_exits_with_4() {
+    let code = "items = []\n@ yes {\n  items = items + [1]\n}\n";
+    stops("grow.lazy", code, &["--max-value-size", "100"], 4, "made a list of size 101, more than 100");
+}
+
+#[test]
+fn timeout_exits_with_5() {
+    stops("forever.lazy", "@ yes {\n}\n", &["--timeout", "0.05"], 5, "ran longer than 50ms");
+}
+
+#[test]
+fn no_input_exits_with_6() {
+    stops("ask.lazy", "+? name\nname\n", &["--no-input"], 6, "`+?` input is disabled");
+}
+
+#[test]
+fn timeout_must_be_seconds() {
+    let file = program("quick.lazy", "1\n");
+    for seconds in ["-1", "NaN", "soon"] {
+        let output = lazy(&["--timeout", seconds], &file);
+        assert_eq!(output.status.code(), Some(2), "--timeout {}", seconds);
+        assert!(output.stdout.is_empty(), "--timeout {} ran the program", seconds);
+    }
+}
+
+#[test]
+fn value_size_limit_stops_a_list_that_holds_itself() {
+    // Twenty-two doublings: a list of two lists, which prints as four million
+    // numbers.
+    let code = format!("a = [1]\n{}t = $(a)\n\"done\"\n", "a = [a a]\n".repeat(22));
+    stops("doubling.lazy", &code, &["--max-value-size", "1000", "--timeout", "1"], 4, "line 24: made a text longer than 1000 bytes");
+    let code = format!("a = [1]\n{}a\n", "a = [a a]\n".repeat(22));
+    stops("print_doubling.lazy", &code, &["--max-value-size", "1000"], 4, "made a text longer than 1000 bytes");
+}
+
+#[test]
+fn timeout_stops_a_long_builtin() {
+    let code = "items = []\ni = 0\n@ i < 20000 {\n  ^(items -> i)*\n  i += 1\n}\nunique = <<(items)\n";
+    stops("unique.lazy", code, &["--timeout", "1"], 5, "line 7: ran longer than 1s");
+    let code = format!("a = [1]\nb = [1]\n{}==(a -> b)\n", "a = [a a]\nb = [b b]\n".repeat(30));
+    stops("compare.lazy", &code, &["--timeout", "0.1"], 5, "ran longer than 100ms");
+}
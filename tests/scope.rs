@@ -0,0 +1,121 @@
+// Variables as `run` resolves them: a function sees and changes its callers'
+// variables, on both engines and with or without the optimizer, and a name
+// nothing gives a value is an error before the program prints anything.
+
+use lazy::console::BufferConsole;
+use lazy::{Error, Interpreter};
+
+// What the program prints with each engine, optimized and not.
+fn outputs(code: &str) -> Vec<(String, Result<(), Error>)> {
+    let program = lazy::parse(code).unwrap();
+    let mut outputs = Vec::new();
+    for bytecode in [false, true] {
+        for optimize in [false, true] {
+            let output = BufferConsole::new();
+            let mut interp = Interpreter::new();
+            interp.set_console(output.clone());
+            interp.use_bytecode(bytecode);
+            interp.set_optimize(optimize);
+            let result = interp.run(&program).map(|_| ());
+            outputs.push((output.contents(), result));
+        }
+    }
+    outputs
+}
+
+fn prints(code: &str, expected: &str) {
+    for (output, result) in outputs(code) {
+        assert!(result.is_ok(), "{:?}", result);
+        assert_eq!(output, expected);
+    }
+}
+
+#[test]
+fn functions_see_their_callers_variables() {
+    prints("\
+count = 0
+bump() => {
+  count = count + 1
+  seen = seen + 1
+}
+report() ~> \"level \" + level
+outer() => {
+  seen = 10
+  level = 3
+  bump()
+  bump()
+  report()
+  -> seen
+}
+outer()
+count
+seen
+", "level 3\n12\n2\n");
+}
+
+#[test]
+fn assignments_reach_the_variable_they_change() {
+    prints("\
+[a b] = [5 6]
+swap() => {
+  [a b] = [b a]
+  a += 1
+  fresh = a
+}
+swap()
+a
+b
+fresh
+items = [1]
+grow(n) => {
+  >> i [1 2 3] {
+    ^(items -> i * n)*
+  }
+}
+grow(10)
+items
+", "7\n5\n[1 10 20 30]\n");
+}
+
+#[test]
+fn tail_calls_keep_the_caller_variables() {
+    prints("\
+down(n acc) => {
+  ? n == 0 {
+    -> acc + extra
+  }
+  extra = 100
+  -> down(n - 1 acc + n)
+}
+down(4 0)
+", "110\n");
+}
+
+#[test]
+fn undefined_names_are_reported_before_any_output() {
+    let code = "\"start\"\nshow() => {\n  -> totl\n}\nshow()\navrage([1 2])\n";
+    for (output, result) in outputs(code) {
+        assert_eq!(output, "");
+        let Err(Error::Parse(errors)) = result else { panic!("{:?}", result) };
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(errors, [
+            "line 3: undefined variable `totl`",
+            "line 6: call to undefined function `avrage`",
+        ]);
+    }
+
+    for engine in [&[][..], &["--vm"][..]] {
+        let dir = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("scope");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("typo.lazy"), code).unwrap();
+        let output = std::process::Command::new(env!("CARGO_BIN_EXE_lazy"))
+            .args(engine)
+            .arg("typo.lazy")
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "", "{:?}", engine);
+        assert!(String::from_utf8_lossy(&output.stderr).starts_with("Error: line 3: undefined variable `totl`\n"), "{:?}", output);
+        assert_eq!(output.status.code(), Some(1));
+    }
+}
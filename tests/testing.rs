@@ -0,0 +1,78 @@
+// Runs `lazy test` on the `*_test.lazy` files in `tests/testing` and checks
+// what it finds, what it reports for each test and how it exits.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn lazy_test(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_lazy")).arg("test").args(args).current_dir(dir).output().unwrap()
+}
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testing")
+}
+
+#[test]
+fn finds_runs_and_reports_every_test() {
+    let output = lazy_test(&fixtures(), &[]);
+    // `math.lazy` isn't a test file, and `helper` isn't a test. The top level
+    // of `math_test.lazy` prints nothing, but a test can.
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "\
+./math_test.lazy
+  PASS test_add
+  FAIL test_lists
+       line 10: assertion failed: values are not equal
+         expected: [2 4 5 8]
+         actual:   [2 4 6]
+         list: expected 4 item(s), got 3
+         [2]: expected 5, got 6
+         [3]: expected 8, missing
+./nested/text_test.lazy
+a test can print
+  FAIL test_greeting
+       line 3: assertion failed: values are not equal
+         expected: \"hello there\"
+         actual:   \"hello world\"
+                          ^ first difference at character 7
+1 passed, 2 failed
+");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn runs_only_the_files_given() {
+    let output = lazy_test(&fixtures(), &["nested"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("nested/text_test.lazy\n"), "{}", stdout);
+    assert!(stdout.ends_with("0 passed, 1 failed\n"), "{}", stdout);
+}
+
+#[test]
+fn passes_when_every_test_passes() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("testing");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("ok_test.lazy"), "test_one() ~> ==(1 + 1 -> 2)\ntest_two() ~> ?!(yes -> \"always\")\n").unwrap();
+    let output = lazy_test(&dir, &["ok_test.lazy"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok_test.lazy\n  PASS test_one\n  PASS test_two\n2 passed, 0 failed\n");
+    assert!(output.status.success(), "{:?}", output);
+
+    // Nothing to run is a failure too.
+    let empty = dir.join("empty");
+    fs::create_dir_all(&empty).unwrap();
+    let output = lazy_test(&empty, &[]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "0 passed, 0 failed\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn undefined_names_fail_before_the_test_runs() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("testing");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("typo_test.lazy"), "\"top\"\ntest_typo() ~> ==(totl -> 1)\n").unwrap();
+    let output = lazy_test(&dir, &["typo_test.lazy"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("  FAIL test_typo\n       line 2: "), "{}", stdout);
+    assert!(stdout.contains("totl"), "{}", stdout);
+    assert_eq!(output.status.code(), Some(1));
+}
@@ -0,0 +1,73 @@
+// Transpiles every program in `tests/corpus` and checks that the result prints
+// the same `.out` snapshot the interpreter is held to. Running the JavaScript
+// needs `node`, and the C `cc`; without them only the transpiling is checked.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use lazy::transpile::{transpile, Target};
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/corpus");
+    let mut files: Vec<PathBuf> = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lazy"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn has(tool: &str) -> bool {
+    Command::new(tool).arg("--version").stdout(Stdio::null()).stderr(Stdio::null()).status().is_ok()
+}
+
+// Output then errors, like the interpreter's snapshots.
+fn run(command: &mut Command, input: &Path) -> String {
+    let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    let answers = fs::read(input.with_extension("in")).unwrap_or_default();
+    child.stdin.take().unwrap().write_all(&answers).unwrap();
+    let output = child.wait_with_output().unwrap();
+    String::from_utf8_lossy(&output.stdout).to_string() + &String::from_utf8_lossy(&output.stderr)
+}
+
+#[test]
+fn javascript_matches_snapshots() {
+    let node = has("node");
+    if !node { eprintln!("node not found: only transpiling"); }
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("js");
+    fs::create_dir_all(&out_dir).unwrap();
+    for file in programs() {
+        let code = fs::read_to_string(&file).unwrap();
+        let js = transpile(&code, &file.display().to_string(), Target::Js)
+            .unwrap_or_else(|e| panic!("{}: {}", file.display(), e));
+        if !node { continue; }
+        let script = out_dir.join(file.with_extension("js").file_name().unwrap());
+        fs::write(&script, js).unwrap();
+        let expected = fs::read_to_string(file.with_extension("out")).unwrap();
+        assert_eq!(run(Command::new("node").arg(&script), &file), expected, "{}", file.display());
+    }
+}
+
+#[test]
+fn c_matches_snapshots() {
+    let cc = has("cc");
+    if !cc { eprintln!("cc not found: only transpiling"); }
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("c");
+    fs::create_dir_all(&out_dir).unwrap();
+    for file in programs() {
+        let code = fs::read_to_string(&file).unwrap();
+        let c = transpile(&code, &file.display().to_string(), Target::C)
+            .unwrap_or_else(|e| panic!("{}: {}", file.display(), e));
+        if !cc { continue; }
+        let source = out_dir.join(file.with_extension("c").file_name().unwrap());
+        let program = source.with_extension("");
+        fs::write(&source, c).unwrap();
+        let status = Command::new("cc").args(["-O2", "-Wall", "-Werror", "-o"]).arg(&program).arg(&source).arg("-lm")
+            .status().unwrap();
+        assert!(status.success(), "{}: cc failed", source.display());
+        let expected = fs::read_to_string(file.with_extension("out")).unwrap();
+        assert_eq!(run(&mut Command::new(&program), &file), expected, "{}", file.display());
+    }
+}